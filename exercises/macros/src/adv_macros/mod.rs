@@ -1,2 +1,3 @@
+pub mod util_dto;
 pub mod util_func;
 pub mod util_struct;
@@ -0,0 +1,546 @@
+use chrono::NaiveDate;
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, Field, Fields, LitInt, LitStr, Type};
+use utils::validation::Regex;
+
+fn named_fields(input: &DeriveInput) -> syn::Result<Vec<&Field>> {
+    if let Data::Struct(data_struct) = &input.data {
+        if let Fields::Named(named) = &data_struct.fields {
+            return Ok(named.named.iter().collect());
+        }
+    }
+    Err(syn::Error::new_spanned(
+        &input.ident,
+        "only structs with named fields are supported",
+    ))
+}
+
+/// `Option<T>`, however it is spelled
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+fn some_or_none<T: quote::ToTokens>(val: &Option<T>) -> proc_macro2::TokenStream {
+    match val {
+        Some(val) => quote! { Some(#val) },
+        None => quote! { None },
+    }
+}
+
+// ----- #[derive(Validate)] -----
+
+/// `min = .., max = ..` of `length(..)`, `range(..)` and `date(..)`
+fn parse_bounds<T: syn::parse::Parse>(
+    meta: &ParseNestedMeta,
+) -> syn::Result<(Option<T>, Option<T>)> {
+    let (mut min, mut max) = (None, None);
+    meta.parse_nested_meta(|bound| {
+        if bound.path.is_ident("min") {
+            min = Some(bound.value()?.parse::<T>()?);
+        } else if bound.path.is_ident("max") {
+            max = Some(bound.value()?.parse::<T>()?);
+        } else {
+            return Err(bound.error("expected `min` or `max`"));
+        }
+        Ok(())
+    })?;
+    if min.is_none() && max.is_none() {
+        return Err(meta.error("expected `min`, `max` or both"));
+    }
+    Ok((min, max))
+}
+
+fn check_date_bound(bound: &Option<LitStr>) -> syn::Result<()> {
+    if let Some(bound) = bound {
+        let text = bound.value();
+        if text != "today" && NaiveDate::parse_from_str(&text, "%Y-%m-%d").is_err() {
+            return Err(syn::Error::new_spanned(
+                bound,
+                "expected `today` or a `YYYY-MM-DD` date",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A pattern that does not compile fails the build, not the first request.
+fn check_regex(pattern: &LitStr) -> syn::Result<()> {
+    Regex::new(&pattern.value())
+        .map(|_| ())
+        .map_err(|err| syn::Error::new_spanned(pattern, format!("invalid regex: {err}")))
+}
+
+/// The checks of one field: `required` on the field itself, the others on the
+/// value, i.e. on the content of an `Option` that is there.
+fn field_checks(field: &Field) -> syn::Result<proc_macro2::TokenStream> {
+    let ident = field.ident.as_ref().unwrap();
+    let name = LitStr::new(&ident.to_string(), Span::call_site());
+    let mut required = false;
+    let mut checks: Vec<proc_macro2::TokenStream> = vec![];
+    for attr in &field.attrs {
+        if !attr.path().is_ident("validate") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("required") {
+                required = true;
+            } else if meta.path.is_ident("email") {
+                checks.push(quote! {
+                    ::utils::validation::email(::std::convert::AsRef::<str>::as_ref(val))
+                });
+            } else if meta.path.is_ident("length") {
+                let (min, max) = parse_bounds::<LitInt>(&meta)?;
+                let (min, max) = (some_or_none(&min), some_or_none(&max));
+                checks.push(quote! { ::utils::validation::length(val, #min, #max) });
+            } else if meta.path.is_ident("range") {
+                // `Expr` for negative bounds
+                let (min, max) = parse_bounds::<Expr>(&meta)?;
+                let (min, max) = (some_or_none(&min), some_or_none(&max));
+                checks.push(quote! { ::utils::validation::range(val, #min, #max) });
+            } else if meta.path.is_ident("date") {
+                let (min, max) = parse_bounds::<LitStr>(&meta)?;
+                check_date_bound(&min)?;
+                check_date_bound(&max)?;
+                let (min, max) = (some_or_none(&min), some_or_none(&max));
+                checks.push(quote! { ::utils::validation::date(val, #min, #max) });
+            } else if meta.path.is_ident("regex") {
+                let pattern = meta.value()?.parse::<LitStr>()?;
+                check_regex(&pattern)?;
+                // compiled once, on first use
+                checks.push(quote! {{
+                    static RE: ::std::sync::OnceLock<::utils::validation::Regex> =
+                        ::std::sync::OnceLock::new();
+                    let re = RE.get_or_init(|| {
+                        ::utils::validation::Regex::new(#pattern)
+                            .expect("regex checked when expanded")
+                    });
+                    ::utils::validation::regex(::std::convert::AsRef::<str>::as_ref(val), re)
+                }});
+            } else {
+                return Err(meta
+                    .error("expected `required`, `email`, `length`, `range`, `date` or `regex`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let mut output = quote! {};
+    if required {
+        output.extend(quote! {
+            if let Some(message) = ::utils::validation::required(&self.#ident) {
+                errors.add(#name, message);
+            }
+        });
+    }
+    if !checks.is_empty() {
+        let checks = quote! {
+            #(
+                if let Some(message) = #checks {
+                    errors.add(#name, message);
+                }
+            )*
+        };
+        output.extend(match is_option(&field.ty) {
+            true => quote! {
+                if let Some(val) = &self.#ident {
+                    #checks
+                }
+            },
+            false => quote! {
+                let val = &self.#ident;
+                #checks
+            },
+        });
+    }
+    Ok(output)
+}
+
+pub(crate) fn create_validate(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let struct_name = &input.ident;
+    let checks = named_fields(&input).and_then(|fields| {
+        fields
+            .into_iter()
+            .map(field_checks)
+            .collect::<syn::Result<Vec<_>>>()
+    });
+    let checks = match checks {
+        Ok(checks) => checks,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let output = quote! {
+        impl #impl_generics ::utils::validation::Validate for #struct_name #ty_generics #where_clause {
+            fn validate(&self) -> Result<(), ::utils::validation::ValidationErrors> {
+                let mut errors = ::utils::validation::ValidationErrors::new();
+                #( { #checks } )*
+                errors.into_result()
+            }
+        }
+    };
+    TokenStream::from(output)
+}
+
+// ----- #[derive(Dto)] -----
+
+/// `from = "Model"` and `into = "Model"` of the `#[dto(..)]` on the struct
+fn dto_models(input: &DeriveInput) -> syn::Result<(Vec<syn::Path>, Vec<syn::Path>)> {
+    let (mut from, mut into) = (vec![], vec![]);
+    for attr in &input.attrs {
+        if !attr.path().is_ident("dto") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            let target = if meta.path.is_ident("from") {
+                &mut from
+            } else if meta.path.is_ident("into") {
+                &mut into
+            } else {
+                return Err(meta.error("expected `from` or `into`"));
+            };
+            target.push(meta.value()?.parse::<LitStr>()?.parse::<syn::Path>()?);
+            Ok(())
+        })?;
+    }
+    if from.is_empty() && into.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "expected #[dto(from = \"Model\")], #[dto(into = \"Model\")] or both",
+        ));
+    }
+    Ok((from, into))
+}
+
+fn is_skipped(field: &Field) -> syn::Result<bool> {
+    let mut skip = false;
+    for attr in &field.attrs {
+        if attr.path().is_ident("dto") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    skip = true;
+                    return Ok(());
+                }
+                Err(meta.error("expected `skip`"))
+            })?;
+        }
+    }
+    Ok(skip)
+}
+
+pub(crate) fn create_dto(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let struct_name = &input.ident;
+    let parsed = dto_models(&input).and_then(|models| {
+        let fields = named_fields(&input)?
+            .into_iter()
+            .map(|field| Ok((field, is_skipped(field)?)))
+            .collect::<syn::Result<Vec<_>>>()?;
+        Ok((models, fields))
+    });
+    let ((from, into), fields) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    // model to dto, through the getters of `record!`/`#[record]`
+    let from_fields = fields.iter().map(|(field, skip)| {
+        let ident = field.ident.as_ref().unwrap();
+        match skip {
+            true => quote! { #ident: ::std::default::Default::default() },
+            false => quote! { #ident: model.#ident().clone() },
+        }
+    });
+    let from_fields = quote! { #(#from_fields),* };
+    // dto to model, through the setters, over the model's defaults;
+    // an `Option` is set when it holds a value
+    let into_fields = fields
+        .iter()
+        .filter(|(_, skip)| !skip)
+        .map(|(field, _)| {
+            let ident = field.ident.as_ref().unwrap();
+            let setter = syn::Ident::new(&format!("set_{}", ident), ident.span());
+            match is_option(&field.ty) {
+                true => quote! {
+                    if let Some(val) = dto.#ident {
+                        model.#setter(val);
+                    }
+                },
+                false => quote! { model.#setter(dto.#ident); },
+            }
+        })
+        .collect::<Vec<_>>();
+    let into_fields = quote! { #(#into_fields)* };
+
+    let output = quote! {
+        #(
+            impl ::std::convert::From<&#from> for #struct_name {
+                fn from(model: &#from) -> Self {
+                    Self { #from_fields }
+                }
+            }
+        )*
+        #(
+            impl ::std::convert::From<#struct_name> for #into {
+                fn from(dto: #struct_name) -> Self {
+                    let mut model = <#into as ::std::default::Default>::default();
+                    #into_fields
+                    model
+                }
+            }
+        )*
+    };
+    TokenStream::from(output)
+}
+
+// ----- #[derive(ApiSchema)] -----
+
+/// The `///` lines of an item, joined, for the `description` of its schema.
+fn doc_text(attrs: &[syn::Attribute]) -> String {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(text),
+                        ..
+                    }),
+                ..
+            }) => Some(text.value().trim().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `skip` and `default` of the `#[serde(..)]` on a field; the other keys are
+/// left to serde.
+fn serde_field_flags(field: &Field) -> (bool, bool) {
+    let (mut skip, mut default) = (false, false);
+    for attr in &field.attrs {
+        if !attr.path().is_ident("serde") {
+            continue;
+        }
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") || meta.path.is_ident("skip_deserializing") {
+                skip = true;
+            } else if meta.path.is_ident("default") {
+                default = true;
+            }
+            if meta.input.peek(syn::Token![=]) {
+                meta.value()?.parse::<Expr>()?;
+            } else if meta.input.peek(syn::token::Paren) {
+                meta.parse_nested_meta(|nested| {
+                    if nested.input.peek(syn::Token![=]) {
+                        nested.value()?.parse::<Expr>()?;
+                    }
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        });
+    }
+    (skip, default)
+}
+
+/// `Vec<T>` or `Option<Vec<T>>`, measured in items rather than chars.
+fn is_list(ty: &Type) -> bool {
+    let Type::Path(type_path) = ty else {
+        return false;
+    };
+    let Some(segment) = type_path.path.segments.last() else {
+        return false;
+    };
+    if segment.ident == "Vec" {
+        return true;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) if segment.ident == "Option" => {
+            args.args.iter().any(|arg| match arg {
+                syn::GenericArgument::Type(inner) => is_list(inner),
+                _ => false,
+            })
+        }
+        _ => false,
+    }
+}
+
+/// What of `#[validate(..)]` a schema can say: lengths, bounds, patterns, emails.
+fn field_constraints(field: &Field) -> syn::Result<Vec<proc_macro2::TokenStream>> {
+    let (min_len, max_len) = match is_list(&field.ty) {
+        true => ("minItems", "maxItems"),
+        false => ("minLength", "maxLength"),
+    };
+    let mut constraints = vec![];
+    let mut bound = |key: &str, val: &dyn quote::ToTokens| {
+        constraints.push(quote! { extra.insert(#key.to_string(), ::utils::openapi::json!(#val)); });
+    };
+    for attr in &field.attrs {
+        if !attr.path().is_ident("validate") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("email") {
+                bound("format", &"email");
+            } else if meta.path.is_ident("length") {
+                let (min, max) = parse_bounds::<LitInt>(&meta)?;
+                min.iter().for_each(|min| bound(min_len, min));
+                max.iter().for_each(|max| bound(max_len, max));
+            } else if meta.path.is_ident("range") {
+                let (min, max) = parse_bounds::<Expr>(&meta)?;
+                min.iter().for_each(|min| bound("minimum", min));
+                max.iter().for_each(|max| bound("maximum", max));
+            } else if meta.path.is_ident("regex") {
+                bound("pattern", &meta.value()?.parse::<LitStr>()?);
+            } else if meta.path.is_ident("date") {
+                let _ = parse_bounds::<LitStr>(&meta)?;
+            }
+            Ok(())
+        })?;
+    }
+    let doc = doc_text(&field.attrs);
+    if !doc.is_empty() {
+        bound("description", &doc);
+    }
+    Ok(constraints)
+}
+
+/// `rename_all` of the `#[serde(..)]` on an enum, for the names of its variants.
+fn serde_rename_all(input: &DeriveInput) -> syn::Result<Option<String>> {
+    let mut rename_all = None;
+    for attr in &input.attrs {
+        if !attr.path().is_ident("serde") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") {
+                rename_all = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.input.peek(syn::Token![=]) {
+                meta.value()?.parse::<Expr>()?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(rename_all)
+}
+
+fn renamed(variant: &str, rename_all: Option<&str>) -> syn::Result<String> {
+    let snake = || {
+        let mut out = String::new();
+        for (i, c) in variant.chars().enumerate() {
+            if c.is_uppercase() && i > 0 {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        }
+        out
+    };
+    Ok(match rename_all {
+        None => variant.to_string(),
+        Some("lowercase") => variant.to_lowercase(),
+        Some("UPPERCASE") => variant.to_uppercase(),
+        Some("snake_case") => snake(),
+        Some("SCREAMING_SNAKE_CASE") => snake().to_uppercase(),
+        Some("kebab-case") => snake().replace('_', "-"),
+        Some(other) => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                format!("rename_all = \"{other}\" is not supported by ApiSchema"),
+            ))
+        }
+    })
+}
+
+fn struct_schema(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let mut fields = vec![];
+    for field in named_fields(input)? {
+        let (skip, default) = serde_field_flags(field);
+        if skip {
+            continue;
+        }
+        let name = field.ident.as_ref().unwrap().to_string();
+        let ty = &field.ty;
+        let constraints = field_constraints(field)?;
+        fields.push(quote! {
+            (
+                #name,
+                ::utils::openapi::with_constraints(
+                    <#ty as ::utils::openapi::ApiSchema>::reference(components),
+                    {
+                        let mut extra = ::utils::openapi::Map::new();
+                        #(#constraints)*
+                        ::utils::openapi::Value::Object(extra)
+                    },
+                ),
+                #default || <#ty as ::utils::openapi::ApiSchema>::OPTIONAL,
+            )
+        });
+    }
+    let description = doc_text(&input.attrs);
+    Ok(quote! {
+        ::utils::openapi::object_schema(#description, vec![#(#fields),*])
+    })
+}
+
+/// Unit variants only, as the strings serde reads and writes.
+fn enum_schema(input: &DeriveInput, data: &syn::DataEnum) -> syn::Result<proc_macro2::TokenStream> {
+    let rename_all = serde_rename_all(input)?;
+    let mut names = vec![];
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "only enums of unit variants are supported",
+            ));
+        }
+        names.push(renamed(&variant.ident.to_string(), rename_all.as_deref())?);
+    }
+    let description = doc_text(&input.attrs);
+    Ok(quote! {{
+        let mut schema = ::utils::openapi::json!({ "type": "string", "enum": [#(#names),*] });
+        if !#description.is_empty() {
+            schema["description"] = ::utils::openapi::json!(#description);
+        }
+        schema
+    }})
+}
+
+pub(crate) fn create_api_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let type_name = &input.ident;
+    let schema = match &input.data {
+        Data::Enum(data) => enum_schema(&input, data),
+        _ => struct_schema(&input),
+    };
+    let schema = match schema {
+        Ok(schema) => schema,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let name = type_name.to_string();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let output = quote! {
+        impl #impl_generics ::utils::openapi::ApiSchema for #type_name #ty_generics #where_clause {
+            fn schema_name() -> Option<String> {
+                Some(#name.to_string())
+            }
+
+            fn schema(components: &mut ::utils::openapi::Components) -> ::utils::openapi::Value {
+                #schema
+            }
+        }
+    };
+    TokenStream::from(output)
+}
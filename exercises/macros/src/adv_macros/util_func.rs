@@ -1,7 +1,6 @@
 use indexmap::IndexMap;
 use proc_macro::TokenStream;
 use proc_macro2::{Ident, Span};
-use quote::__private::ext::RepToTokensExt;
 use quote::quote;
 use syn::punctuated::Punctuated;
 use syn::{
@@ -289,7 +288,7 @@ pub(crate) fn create_crud(input: TokenStream) -> TokenStream {
     let insert_params_str = insert_params.join(",");
     // map from DB rows to struct:
     let mut stmt_map_db_struct: Vec<proc_macro2::TokenStream> = vec![];
-    for (field_name) in map_col_idents.keys() {
+    for field_name in map_col_idents.keys() {
         let tmp_ident = quote! {row.get::<_,_>(#field_name) };
         stmt_map_db_struct.push(tmp_ident);
     }
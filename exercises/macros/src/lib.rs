@@ -20,3 +20,25 @@ pub fn with(attr: TokenStream, item: TokenStream) -> TokenStream {
 pub fn derive_crud(input: TokenStream) -> TokenStream {
     adv_macros::util_func::create_crud(input)
 }
+
+/// `#[validate(required, email, length(min = 1, max = 64), range(min = 0, max = 2),
+/// date(min = "1900-01-01", max = "today"), regex = "^[a-z]+$")]` on fields,
+/// checked by `utils::validation::Validate::validate`.
+#[proc_macro_derive(Validate, attributes(validate))]
+pub fn derive_validate(input: TokenStream) -> TokenStream {
+    adv_macros::util_dto::create_validate(input)
+}
+
+/// `#[dto(from = "Model")]` and/or `#[dto(into = "Model")]` on the struct,
+/// `#[dto(skip)]` on fields the model does not have.
+#[proc_macro_derive(Dto, attributes(dto))]
+pub fn derive_dto(input: TokenStream) -> TokenStream {
+    adv_macros::util_dto::create_dto(input)
+}
+
+/// The OpenAPI schema of a struct or a unit enum, as `utils::openapi::ApiSchema`;
+/// it follows `#[serde(default, skip, rename_all)]`, `#[validate(..)]` and `///` docs.
+#[proc_macro_derive(ApiSchema)]
+pub fn derive_api_schema(input: TokenStream) -> TokenStream {
+    adv_macros::util_dto::create_api_schema(input)
+}
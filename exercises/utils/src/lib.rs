@@ -2,4 +2,7 @@ pub mod common_utils;
 pub mod error;
 pub mod format;
 pub mod log;
+pub mod metrics;
+pub mod openapi;
 pub mod serde;
+pub mod validation;
@@ -1,5 +1,5 @@
 use std::env;
-use std::sync::OnceLock;
+use std::sync::Mutex;
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_appender::rolling;
 use tracing_appender::rolling::Rotation;
@@ -10,7 +10,8 @@ use tracing_subscriber::{fmt, EnvFilter, Registry};
 const CONFIG_FILE: &str = "config.toml";
 // static INIT: Once = Once::new();
 // static GUARD: OnceCell<tracing_appender::non_blocking::WorkerGuard> = OnceCell::new();
-static GUARDS: OnceLock<Vec<WorkerGuard>> = OnceLock::new();
+/// keep the non-blocking writers alive until `flush_logger`
+static GUARDS: Mutex<Vec<WorkerGuard>> = Mutex::new(Vec::new());
 
 pub fn load_config_file() -> String {
     std::fs::read_to_string(CONFIG_FILE).unwrap()
@@ -85,6 +86,22 @@ pub fn init_logger() {
     //     .with(fmt_stdout)
     //     .with(fmt_file)
     //     .init();
-    let _ = GUARDS.get_or_init(|| guards);
-    tracing_subscriber::registry().with(layers).init();
+    // tests call this once per case, keep the first subscriber
+    if tracing_subscriber::registry()
+        .with(layers)
+        .try_init()
+        .is_ok()
+    {
+        GUARDS
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .extend(guards);
+    }
+}
+
+/// Writes out what the non-blocking writers still buffer and stops them, so
+/// the events logged after it are lost; the last step on the way out.
+pub fn flush_logger() {
+    let guards = std::mem::take(&mut *GUARDS.lock().unwrap_or_else(|err| err.into_inner()));
+    drop(guards);
 }
@@ -0,0 +1,215 @@
+//! Counters and histograms by label, rendered in the Prometheus text format.
+//! Meant for `static`s, e.g.
+//! `static REQUESTS: CounterVec = CounterVec::new("requests_total", "Requests", &["status"]);`
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Seconds, from 5ms to 10s.
+pub const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Escapes a label value as the text format wants it.
+fn escape(val: &str) -> String {
+    val.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn label_set(names: &[&str], vals: &[String], extra: Option<(&str, &str)>) -> String {
+    let mut pairs = names
+        .iter()
+        .zip(vals)
+        .map(|(name, val)| format!("{name}=\"{}\"", escape(val)))
+        .collect::<Vec<_>>();
+    if let Some((name, val)) = extra {
+        pairs.push(format!("{name}=\"{}\"", escape(val)));
+    }
+    match pairs.is_empty() {
+        true => String::new(),
+        false => format!("{{{}}}", pairs.join(",")),
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn label_vals(names: &[&str], vals: &[&str]) -> Vec<String> {
+    assert_eq!(
+        names.len(),
+        vals.len(),
+        "label values do not match {names:?}"
+    );
+    vals.iter().map(|val| val.to_string()).collect()
+}
+
+/// A monotonic count per label values.
+pub struct CounterVec {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    values: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl CounterVec {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+    ) -> Self {
+        Self {
+            name,
+            help,
+            labels,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// `vals` in the order of the label names.
+    pub fn inc(&self, vals: &[&str]) {
+        self.add(vals, 1);
+    }
+
+    pub fn add(&self, vals: &[&str], n: u64) {
+        let key = label_vals(self.labels, vals);
+        *self
+            .values
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .entry(key)
+            .or_default() += n;
+    }
+
+    pub fn get(&self, vals: &[&str]) -> u64 {
+        let key = label_vals(self.labels, vals);
+        let values = self.values.lock().unwrap_or_else(|err| err.into_inner());
+        values.get(&key).copied().unwrap_or_default()
+    }
+
+    pub fn render(&self, out: &mut String) {
+        header(out, self.name, self.help, "counter");
+        let values = self.values.lock().unwrap_or_else(|err| err.into_inner());
+        for (vals, count) in values.iter() {
+            let labels = label_set(self.labels, vals, None);
+            let _ = writeln!(out, "{}{labels} {count}", self.name);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistogramData {
+    /// per bucket, not cumulative
+    pub buckets: Vec<u64>,
+    pub sum: f64,
+    pub count: u64,
+}
+
+/// Observations per label values, counted in `buckets`, upper bounds ascending.
+pub struct HistogramVec {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    buckets: &'static [f64],
+    values: Mutex<BTreeMap<Vec<String>, HistogramData>>,
+}
+
+impl HistogramVec {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+        buckets: &'static [f64],
+    ) -> Self {
+        Self {
+            name,
+            help,
+            labels,
+            buckets,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn observe(&self, vals: &[&str], val: f64) {
+        let key = label_vals(self.labels, vals);
+        let mut values = self.values.lock().unwrap_or_else(|err| err.into_inner());
+        let data = values.entry(key).or_insert_with(|| HistogramData {
+            buckets: vec![0; self.buckets.len()],
+            ..HistogramData::default()
+        });
+        if let Some(i) = self.buckets.iter().position(|bound| val <= *bound) {
+            data.buckets[i] += 1;
+        }
+        data.sum += val;
+        data.count += 1;
+    }
+
+    pub fn observe_duration(&self, vals: &[&str], elapsed: Duration) {
+        self.observe(vals, elapsed.as_secs_f64());
+    }
+
+    pub fn get(&self, vals: &[&str]) -> Option<HistogramData> {
+        let key = label_vals(self.labels, vals);
+        let values = self.values.lock().unwrap_or_else(|err| err.into_inner());
+        values.get(&key).cloned()
+    }
+
+    pub fn render(&self, out: &mut String) {
+        header(out, self.name, self.help, "histogram");
+        let values = self.values.lock().unwrap_or_else(|err| err.into_inner());
+        for (vals, data) in values.iter() {
+            let mut cumulative = 0;
+            for (bound, count) in self.buckets.iter().zip(&data.buckets) {
+                cumulative += count;
+                let labels = label_set(self.labels, vals, Some(("le", &bound.to_string())));
+                let _ = writeln!(out, "{}_bucket{labels} {cumulative}", self.name);
+            }
+            let labels = label_set(self.labels, vals, Some(("le", "+Inf")));
+            let _ = writeln!(out, "{}_bucket{labels} {}", self.name, data.count);
+            let labels = label_set(self.labels, vals, None);
+            let _ = writeln!(out, "{}_sum{labels} {}", self.name, data.sum);
+            let _ = writeln!(out, "{}_count{labels} {}", self.name, data.count);
+        }
+    }
+}
+
+/// Values read when rendering, e.g. the state of a pool; `samples` are the
+/// label values, in the order of `labels`, and the value.
+pub fn render_gauge(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    labels: &[&str],
+    samples: &[(Vec<String>, f64)],
+) {
+    render_samples(out, name, help, "gauge", labels, samples);
+}
+
+/// Like `render_gauge`, for totals kept elsewhere that only ever go up.
+pub fn render_counter(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    labels: &[&str],
+    samples: &[(Vec<String>, f64)],
+) {
+    render_samples(out, name, help, "counter", labels, samples);
+}
+
+fn render_samples(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    kind: &str,
+    labels: &[&str],
+    samples: &[(Vec<String>, f64)],
+) {
+    header(out, name, help, kind);
+    for (vals, val) in samples {
+        let _ = writeln!(out, "{name}{} {val}", label_set(labels, vals, None));
+    }
+}
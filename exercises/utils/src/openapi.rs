@@ -0,0 +1,143 @@
+//! OpenAPI 3 schemas of the types an API takes and returns, implemented by
+//! `#[derive(macros::ApiSchema)]` and by the structs of `record!`.
+
+use chrono::{NaiveDate, NaiveDateTime};
+use std::collections::{BTreeMap, HashMap};
+
+pub use serde_json::{json, Map, Value};
+
+/// `#/components/schemas` of the document being built, by schema name.
+pub type Components = BTreeMap<String, Value>;
+
+pub trait ApiSchema {
+    /// Whether a struct field of this type may be left out.
+    const OPTIONAL: bool = false;
+
+    /// Set for types that get a schema of their own under `#/components/schemas`.
+    fn schema_name() -> Option<String> {
+        None
+    }
+
+    /// The schema itself; the ones of named types it uses go to `components`.
+    fn schema(components: &mut Components) -> Value;
+
+    /// What a user of the type embeds: a `$ref` for named types, registered in
+    /// `components` on first use, the schema itself otherwise.
+    fn reference(components: &mut Components) -> Value {
+        let Some(name) = Self::schema_name() else {
+            return Self::schema(components);
+        };
+        if !components.contains_key(&name) {
+            // a placeholder first, for types that refer to themselves
+            components.insert(name.clone(), Value::Null);
+            let schema = Self::schema(components);
+            components.insert(name.clone(), schema);
+        }
+        json!({ "$ref": format!("#/components/schemas/{name}") })
+    }
+}
+
+macro_rules! scalar_schema {
+    ($($ty:ty => $schema:tt),* $(,)?) => {
+        $(
+            impl ApiSchema for $ty {
+                fn schema(_: &mut Components) -> Value {
+                    json!($schema)
+                }
+            }
+        )*
+    };
+}
+
+scalar_schema! {
+    bool => { "type": "boolean" },
+    i16 => { "type": "integer", "format": "int32", "minimum": i16::MIN, "maximum": i16::MAX },
+    i32 => { "type": "integer", "format": "int32" },
+    i64 => { "type": "integer", "format": "int64" },
+    u32 => { "type": "integer", "format": "int32", "minimum": 0 },
+    u64 => { "type": "integer", "format": "int64", "minimum": 0 },
+    f64 => { "type": "number", "format": "double" },
+    String => { "type": "string" },
+    NaiveDate => { "type": "string", "format": "date" },
+    // not `date-time`, that is RFC 3339 with an offset and chrono writes none
+    NaiveDateTime => {
+        "type": "string",
+        "pattern": r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(\.\d+)?$",
+        "example": "2024-01-02T03:04:05"
+    },
+    Value => {},
+}
+
+impl<T: ApiSchema> ApiSchema for Option<T> {
+    const OPTIONAL: bool = true;
+
+    fn schema(components: &mut Components) -> Value {
+        T::reference(components)
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for Vec<T> {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "type": "array", "items": T::reference(components) })
+    }
+}
+
+impl<V: ApiSchema> ApiSchema for BTreeMap<String, V> {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "type": "object", "additionalProperties": V::reference(components) })
+    }
+}
+
+impl<V: ApiSchema> ApiSchema for HashMap<String, V> {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "type": "object", "additionalProperties": V::reference(components) })
+    }
+}
+
+impl ApiSchema for Map<String, Value> {
+    fn schema(_: &mut Components) -> Value {
+        json!({ "type": "object" })
+    }
+}
+
+/// The object schema of a struct; `fields` are `(name, schema, optional)`.
+pub fn object_schema(description: &str, fields: Vec<(&str, Value, bool)>) -> Value {
+    let required = fields
+        .iter()
+        .filter(|(_, _, optional)| !optional)
+        .map(|(name, _, _)| *name)
+        .collect::<Vec<_>>();
+    let properties = fields
+        .into_iter()
+        .map(|(name, schema, _)| (name.to_string(), schema))
+        .collect::<Map<_, _>>();
+    let mut schema = json!({ "type": "object", "properties": properties });
+    if !required.is_empty() {
+        schema["required"] = json!(required);
+    }
+    if !description.is_empty() {
+        schema["description"] = json!(description);
+    }
+    schema
+}
+
+/// `extra` merged into `schema`, or next to the `$ref` of a named one.
+pub fn with_constraints(schema: Value, extra: Value) -> Value {
+    let Value::Object(extra) = extra else {
+        return schema;
+    };
+    if extra.is_empty() {
+        return schema;
+    }
+    match schema {
+        // siblings of a `$ref` are ignored by OpenAPI 3.0
+        Value::Object(map) if map.contains_key("$ref") => {
+            json!({ "allOf": [Value::Object(map)], "description": extra.get("description") })
+        }
+        Value::Object(mut map) => {
+            map.extend(extra);
+            Value::Object(map)
+        }
+        other => other,
+    }
+}
@@ -0,0 +1,195 @@
+//! Runtime side of `#[derive(macros::Validate)]`: the checks the generated
+//! `validate` calls field by field, and the errors it collects.
+//!
+//! Each check returns the message for a failed value, `None` when it passes.
+
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+pub use regex::Regex;
+
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+/// Every failed check, the messages by field name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct ValidationErrors {
+    fields: BTreeMap<String, Vec<String>>,
+}
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.fields
+            .entry(field.to_string())
+            .or_default()
+            .push(message.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn fields(&self) -> &BTreeMap<String, Vec<String>> {
+        &self.fields
+    }
+
+    pub fn get(&self, field: &str) -> Option<&Vec<String>> {
+        self.fields.get(field)
+    }
+
+    pub fn into_result(self) -> Result<(), Self> {
+        match self.is_empty() {
+            true => Ok(()),
+            false => Err(self),
+        }
+    }
+}
+
+impl Display for ValidationErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let fields = self
+            .fields
+            .iter()
+            .map(|(field, messages)| format!("{field} {}", messages.join(", ")))
+            .collect::<Vec<_>>();
+        f.write_str(&fields.join("; "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+/// Values `required` can find missing.
+pub trait Presence {
+    fn is_present(&self) -> bool;
+}
+
+impl<T> Presence for Option<T> {
+    fn is_present(&self) -> bool {
+        self.is_some()
+    }
+}
+
+impl Presence for String {
+    fn is_present(&self) -> bool {
+        !self.trim().is_empty()
+    }
+}
+
+impl<T> Presence for Vec<T> {
+    fn is_present(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+/// Values `length` can measure; text in chars, not bytes.
+pub trait Length {
+    fn length(&self) -> usize;
+}
+
+impl Length for String {
+    fn length(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl<T> Length for Vec<T> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Values `date` can bound, compared by their day.
+pub trait DateBounded {
+    fn day(&self) -> NaiveDate;
+}
+
+impl DateBounded for NaiveDate {
+    fn day(&self) -> NaiveDate {
+        *self
+    }
+}
+
+impl DateBounded for NaiveDateTime {
+    fn day(&self) -> NaiveDate {
+        self.date()
+    }
+}
+
+pub fn required<T: Presence>(val: &T) -> Option<String> {
+    (!val.is_present()).then(|| "is required".to_string())
+}
+
+pub fn length<T: Length>(val: &T, min: Option<usize>, max: Option<usize>) -> Option<String> {
+    let len = val.length();
+    match (min, max) {
+        (Some(min), Some(max)) if len < min || len > max => {
+            Some(format!("must be {min} to {max} long"))
+        }
+        (Some(min), None) if len < min => Some(format!("must be at least {min} long")),
+        (None, Some(max)) if len > max => Some(format!("must be at most {max} long")),
+        _ => None,
+    }
+}
+
+pub fn range<T: PartialOrd + Display>(val: &T, min: Option<T>, max: Option<T>) -> Option<String> {
+    match (min, max) {
+        (Some(min), Some(max)) if *val < min || *val > max => {
+            Some(format!("must be between {min} and {max}"))
+        }
+        (Some(min), None) if *val < min => Some(format!("must be at least {min}")),
+        (None, Some(max)) if *val > max => Some(format!("must be at most {max}")),
+        _ => None,
+    }
+}
+
+pub fn regex(val: &str, re: &Regex) -> Option<String> {
+    (!re.is_match(val)).then(|| format!("must match {}", re.as_str()))
+}
+
+/// A pragmatic check of `local@domain.tld`, not the full RFC 5322 grammar.
+pub fn email(val: &str) -> Option<String> {
+    let valid = match val.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && local.len() <= 64
+                && domain.len() <= 255
+                && !val.chars().any(|c| c.is_whitespace() || c.is_control())
+                && !domain.contains('@')
+                && domain.contains('.')
+                && domain
+                    .split('.')
+                    .all(|label| !label.is_empty() && !label.starts_with('-'))
+        }
+        None => false,
+    };
+    (!valid).then(|| "must be an email address".to_string())
+}
+
+/// `min` and `max` are `YYYY-MM-DD` or `today`, both inclusive.
+pub fn date<T: DateBounded>(val: &T, min: Option<&str>, max: Option<&str>) -> Option<String> {
+    let bound = |text: &str| match text {
+        "today" => Local::now().date_naive(),
+        _ => NaiveDate::parse_from_str(text, "%Y-%m-%d")
+            .unwrap_or_else(|err| panic!("invalid date bound {text}: {err}")),
+    };
+    let day = val.day();
+    if let Some(min) = min {
+        if day < bound(min) {
+            return Some(format!("must not be before {min}"));
+        }
+    }
+    if let Some(max) = max {
+        if day > bound(max) {
+            return Some(format!("must not be after {max}"));
+        }
+    }
+    None
+}
@@ -0,0 +1 @@
+drop table if exists test_rec;
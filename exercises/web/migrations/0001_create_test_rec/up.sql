@@ -0,0 +1,8 @@
+-- table of models::sample_rec::SampleRecord
+create table if not exists test_rec
+(
+    id_        bigint primary key,
+    name_      varchar   not null,
+    available  boolean   not null default false,
+    created_at timestamp not null default localtimestamp
+);
@@ -0,0 +1,15 @@
+-- table of models::user::User
+create table if not exists user_
+(
+    id_               bigint primary key,
+    created_date      timestamp not null default localtimestamp,
+    modified_date     timestamp not null default localtimestamp,
+    dob               date      not null,
+    passwd            varchar   not null,
+    passwd_enc_method varchar   not null,
+    screenname        varchar   not null,
+    status_           smallint  not null default 0,
+    username          varchar   not null,
+    org_id            bigint    not null,
+    org_treepath      varchar   not null
+);
@@ -0,0 +1,2 @@
+alter table user_ drop column if exists version_;
+alter table test_rec drop column if exists version_;
@@ -0,0 +1,3 @@
+-- optimistic locking counter, see `AsyncRepository::VERSION_COLUMN`
+alter table test_rec add column if not exists version_ integer not null default 0;
+alter table user_ add column if not exists version_ integer not null default 0;
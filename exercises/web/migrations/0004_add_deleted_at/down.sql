@@ -0,0 +1,2 @@
+alter table user_ drop column if exists deleted_at;
+alter table test_rec drop column if exists deleted_at;
@@ -0,0 +1,3 @@
+-- soft delete stamp, see `AsyncRepository::DELETED_COLUMN`
+alter table test_rec add column if not exists deleted_at timestamp;
+alter table user_ add column if not exists deleted_at timestamp;
@@ -0,0 +1,3 @@
+alter table user_ drop constraint if exists user_org_treepath_chk;
+drop index if exists user_org_id_idx;
+drop index if exists user_org_treepath_idx;
@@ -0,0 +1,6 @@
+-- materialized path queries, see `persistence::org_tree`.
+-- `varchar_pattern_ops` lets `org_treepath like '/1/5/%'` use the index under any collation.
+create index if not exists user_org_treepath_idx on user_ (org_treepath varchar_pattern_ops);
+create index if not exists user_org_id_idx on user_ (org_id);
+alter table user_ drop constraint if exists user_org_treepath_chk;
+alter table user_ add constraint user_org_treepath_chk check (org_treepath ~ '^(/[0-9]+)+/$');
@@ -0,0 +1 @@
+drop index if exists user_username_idx;
@@ -0,0 +1,3 @@
+-- login looks users up by name, see `services::credential_service`.
+-- a soft deleted user gives up the name.
+create unique index if not exists user_username_idx on user_ (username) where deleted_at is null;
@@ -0,0 +1,11 @@
+-- refresh tokens handed out by `services::auth_service`, one row per token id.
+-- a token is live while `revoked_at` is null and `expires_at` is ahead.
+create table if not exists refresh_token
+(
+    jti        varchar primary key,
+    user_id    bigint    not null references user_ (id_) on delete cascade,
+    issued_at  timestamp not null default localtimestamp,
+    expires_at timestamp not null,
+    revoked_at timestamp
+);
+create index if not exists refresh_token_user_id_idx on refresh_token (user_id);
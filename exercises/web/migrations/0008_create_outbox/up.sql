@@ -0,0 +1,20 @@
+-- domain events, written in the transaction of the change they tell about and
+-- delivered by `services::outbox_dispatcher`. an event is pending while both
+-- `delivered_at` and `dead_at` are null; a failed attempt pushes
+-- `next_attempt_at` back, the last one allowed stamps `dead_at`.
+create table if not exists outbox
+(
+    id_             bigserial primary key,
+    aggregate       varchar   not null,
+    aggregate_id    bigint    not null,
+    event_type      varchar   not null,
+    payload         jsonb     not null,
+    created_at      timestamp not null default localtimestamp,
+    attempts        integer   not null default 0,
+    next_attempt_at timestamp not null default localtimestamp,
+    last_error      varchar,
+    delivered_at    timestamp,
+    dead_at         timestamp
+);
+create index if not exists outbox_pending_idx on outbox (next_attempt_at, id_)
+    where delivered_at is null and dead_at is null;
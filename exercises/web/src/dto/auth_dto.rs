@@ -0,0 +1,29 @@
+use crate::models::user_status::UserStatus;
+use macros::{ApiSchema, Validate};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Validate, ApiSchema)]
+pub struct LoginRequest {
+    #[validate(required)]
+    pub username: String,
+    #[validate(required)]
+    pub passwd: String,
+}
+
+/// The body of refresh and logout.
+#[derive(Debug, Clone, PartialEq, Deserialize, Validate, ApiSchema)]
+pub struct RefreshRequest {
+    #[validate(required)]
+    pub refresh_token: String,
+    /// on logout, revoke every refresh token of the user
+    #[serde(default)]
+    pub everywhere: bool,
+}
+
+/// Who the access token is for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct PrincipalDto {
+    pub user_id: i64,
+    pub username: String,
+    pub status: UserStatus,
+}
@@ -0,0 +1,10 @@
+use macros::ApiSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// `status` is `up` or `down`; `checks` says how each dependency is doing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ApiSchema)]
+pub struct HealthStatus {
+    pub status: String,
+    pub checks: BTreeMap<String, String>,
+}
@@ -1 +1,5 @@
+pub mod auth_dto;
+pub mod health_dto;
+pub mod sample_rec_dto;
 pub mod test_dto;
+pub mod user_dto;
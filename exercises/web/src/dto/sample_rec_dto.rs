@@ -0,0 +1,39 @@
+use crate::models::sample_rec::SampleRecord;
+use chrono::NaiveDateTime;
+use macros::{ApiSchema, Dto, Validate};
+use serde::{Deserialize, Serialize};
+
+/// A `SampleRecord` as the API shows it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Dto, ApiSchema)]
+#[dto(from = "SampleRecord")]
+pub struct SampleRecordDto {
+    pub id: i64,
+    pub name: String,
+    pub available: bool,
+    pub created_at: NaiveDateTime,
+    pub version: i32,
+}
+
+/// The body of create.
+#[derive(Debug, Clone, PartialEq, Deserialize, Validate, Dto, ApiSchema)]
+#[dto(into = "SampleRecord")]
+pub struct NewSampleRecord {
+    #[validate(range(min = 1))]
+    pub id: i64,
+    #[validate(required, length(max = 255))]
+    pub name: String,
+    pub available: bool,
+}
+
+/// The body of update; `version` is the one read last, the id comes from the path.
+#[derive(Debug, Clone, PartialEq, Deserialize, Validate, Dto, ApiSchema)]
+#[dto(into = "SampleRecord")]
+pub struct SampleRecordUpdate {
+    #[serde(default)]
+    pub id: i64,
+    #[validate(required, length(max = 255))]
+    pub name: String,
+    pub available: bool,
+    #[serde(default)]
+    pub version: i32,
+}
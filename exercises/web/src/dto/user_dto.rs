@@ -0,0 +1,75 @@
+use crate::models::user::User;
+use chrono::{NaiveDate, NaiveDateTime};
+use macros::{ApiSchema, Dto, Validate};
+use serde::{Deserialize, Serialize};
+
+/// A `User` as the API shows it, never with the password.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Dto, ApiSchema)]
+#[dto(from = "User")]
+pub struct UserDto {
+    pub id: i64,
+    pub created_date: NaiveDateTime,
+    pub modified_date: NaiveDateTime,
+    pub dob: NaiveDate,
+    pub screenname: String,
+    pub status: i16,
+    pub username: String,
+    pub org_id: i64,
+    pub org_treepath: String,
+    pub version: i32,
+}
+
+/// The body of create, `passwd` in plain text; it is stored hashed.
+#[derive(Debug, Clone, PartialEq, Deserialize, Validate, Dto, ApiSchema)]
+#[dto(into = "User")]
+pub struct NewUser {
+    #[validate(range(min = 1))]
+    pub id: i64,
+    #[validate(date(min = "1900-01-01", max = "today"))]
+    pub dob: NaiveDate,
+    #[validate(required, length(min = 6, max = 128))]
+    pub passwd: String,
+    #[validate(required, length(max = 128))]
+    pub screenname: String,
+    #[validate(range(min = 0, max = 2))]
+    pub status: i16,
+    #[validate(required, regex = "^[A-Za-z0-9_.-]{3,64}$")]
+    pub username: String,
+    #[validate(range(min = 1))]
+    pub org_id: i64,
+    #[validate(required, regex = "^(/[0-9]+)+/$")]
+    pub org_treepath: String,
+}
+
+/// The body of update, the password is changed on its own route. `version`
+/// is the one read last, the id comes from the path.
+#[derive(Debug, Clone, PartialEq, Deserialize, Validate, Dto, ApiSchema)]
+#[dto(into = "User")]
+pub struct UserUpdate {
+    #[serde(default)]
+    pub id: i64,
+    #[validate(date(min = "1900-01-01", max = "today"))]
+    pub dob: NaiveDate,
+    #[validate(required, length(max = 128))]
+    pub screenname: String,
+    #[validate(range(min = 0, max = 2))]
+    pub status: i16,
+    #[validate(required, regex = "^[A-Za-z0-9_.-]{3,64}$")]
+    pub username: String,
+    #[validate(range(min = 1))]
+    pub org_id: i64,
+    #[validate(required, regex = "^(/[0-9]+)+/$")]
+    pub org_treepath: String,
+    #[serde(default)]
+    pub version: i32,
+}
+
+/// The body of a password change, see `credential_service::change_password`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Validate, ApiSchema)]
+pub struct PasswdChange {
+    pub version: i32,
+    #[validate(required)]
+    pub current: String,
+    #[validate(required, length(min = 6, max = 128))]
+    pub new: String,
+}
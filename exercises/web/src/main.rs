@@ -6,6 +6,8 @@ use crate::utils::boot::boot;
 pub(crate) mod dto;
 pub(crate) mod models;
 pub(crate) mod persistence;
+pub(crate) mod presentation;
+pub(crate) mod services;
 pub(crate) mod utils;
 
 fn main() {
@@ -0,0 +1,73 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utils::error::app_error::AppResult;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventType {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl EventType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventType::Created => "created",
+            EventType::Updated => "updated",
+            EventType::Deleted => "deleted",
+        }
+    }
+
+    pub fn from_name(val: &str) -> Option<Self> {
+        match val {
+            "created" => Some(EventType::Created),
+            "updated" => Some(EventType::Updated),
+            "deleted" => Some(EventType::Deleted),
+            _ => None,
+        }
+    }
+}
+
+/// A change to one entity, e.g. `sample_record` 42 `updated`, with the entity
+/// as the API shows it after the change, or just its id once deleted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DomainEvent {
+    pub aggregate: String,
+    pub aggregate_id: i64,
+    pub event_type: EventType,
+    pub payload: Value,
+}
+
+impl DomainEvent {
+    pub fn new(
+        aggregate: &str,
+        aggregate_id: i64,
+        event_type: EventType,
+        payload: &impl Serialize,
+    ) -> AppResult<Self> {
+        Ok(Self {
+            aggregate: aggregate.to_string(),
+            aggregate_id,
+            event_type,
+            payload: serde_json::to_value(payload)?,
+        })
+    }
+}
+
+/// A `DomainEvent` as stored in the outbox and handed to the sinks.
+///
+/// Delivery is at least once, so a sink may see the same event again; `id`
+/// tells them apart and grows in the order the events were written.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutboxEvent {
+    pub id: i64,
+    #[serde(flatten)]
+    pub event: DomainEvent,
+    pub created_at: NaiveDateTime,
+    /// failed deliveries so far
+    pub attempts: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
@@ -1,2 +1,5 @@
+pub mod domain_event;
+pub mod org_path;
 pub mod sample_rec;
 pub mod user;
+pub mod user_status;
@@ -0,0 +1,88 @@
+use anyhow::anyhow;
+use std::fmt::{Display, Formatter};
+use utils::error::app_error::AppResult;
+
+/// Materialized path of an organisation, as kept in `User::org_treepath`:
+/// the org ids from the root down to the org itself, e.g. `/1/5/9/` is org 9
+/// under 5 under 1. A path is a prefix of the paths of everything below it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OrgPath(String);
+
+impl OrgPath {
+    pub fn parse(path: &str) -> AppResult<Self> {
+        let invalid = || anyhow!("invalid org treepath {path}, expected e.g. /1/5/");
+        let inner = path
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+            .filter(|inner| !inner.is_empty())
+            .ok_or_else(invalid)?;
+        for id in inner.split('/') {
+            if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            id.parse::<i64>().map_err(|_| invalid())?;
+        }
+        Ok(Self(path.to_string()))
+    }
+
+    pub fn root(org_id: i64) -> Self {
+        Self(format!("/{org_id}/"))
+    }
+
+    pub fn child(&self, org_id: i64) -> Self {
+        Self(format!("{}{}/", self.0, org_id))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Root first, the org itself last.
+    pub fn ids(&self) -> Vec<i64> {
+        self.0
+            .trim_matches('/')
+            .split('/')
+            .map(|id| id.parse().expect("checked by parse"))
+            .collect()
+    }
+
+    pub fn org_id(&self) -> i64 {
+        *self.ids().last().expect("never empty")
+    }
+
+    pub fn depth(&self) -> usize {
+        self.ids().len()
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        let inner = &self.0[..self.0.len() - 1];
+        let cut = inner.rfind('/')?;
+        match cut {
+            0 => None,
+            _ => Some(Self(self.0[..=cut].to_string())),
+        }
+    }
+
+    /// The paths above this one, root first.
+    pub fn ancestors(&self) -> Vec<Self> {
+        let mut ancestors = vec![];
+        let mut next = self.parent();
+        while let Some(path) = next {
+            next = path.parent();
+            ancestors.push(path);
+        }
+        ancestors.reverse();
+        ancestors
+    }
+
+    /// Whether `other` is this org or anywhere below it.
+    pub fn contains(&self, other: &OrgPath) -> bool {
+        other.0.starts_with(&self.0)
+    }
+}
+
+impl Display for OrgPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
@@ -12,6 +12,9 @@ table! {
         name -> VarChar,
         available -> Bool,
         created_at -> Timestamp,
+        #[sql_name="version_"]
+        version -> Integer,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -41,6 +44,14 @@ record! {
         id : i64,
         name : String,
         available : bool,
+        #[diesel(skip_insertion)]
+        #[serde(default)]
         created_at : NaiveDateTime,
+        #[diesel(skip_insertion)]
+        #[serde(default)]
+        version : i32,
+        #[diesel(skip_insertion)]
+        #[serde(default)]
+        deleted_at : Option<NaiveDateTime>,
     }
 }
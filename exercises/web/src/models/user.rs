@@ -1,5 +1,6 @@
+use crate::models::user_status::UserStatus;
 use chrono::{NaiveDate, NaiveDateTime};
-use diesel::{table, Identifiable, Insertable, Queryable};
+use diesel::{table, AsChangeset, Identifiable, Insertable, Queryable};
 use macros::record;
 
 table! {
@@ -22,10 +23,14 @@ table! {
 
         org_id -> BigInt,
         org_treepath -> VarChar,
+
+        #[sql_name="version_"]
+        version -> Integer,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 // #[derive(Queryable, Serialize, Identifiable, Insertable, AsChangeset, Debug)]
-#[derive(Queryable, Identifiable, Insertable)]
+#[derive(Queryable, Identifiable, Insertable, AsChangeset)]
 #[record]
 // #[table_name = "user_"]
 #[diesel(table_name=users)]
@@ -34,7 +39,12 @@ table! {
 pub struct User {
     // #[column_name = "id_"]
     id: i64,
+    // maintained by persistence, the column defaults fill them on insert
+    #[diesel(skip_insertion)]
+    #[serde(default)]
     created_date: NaiveDateTime,
+    #[diesel(skip_insertion)]
+    #[serde(default)]
     modified_date: NaiveDateTime,
     dob: NaiveDate,
     passwd: String,
@@ -45,4 +55,16 @@ pub struct User {
     username: String,
     org_id: i64,
     org_treepath: String,
+    #[diesel(skip_insertion)]
+    #[serde(default)]
+    version: i32,
+    #[diesel(skip_insertion)]
+    #[serde(default)]
+    deleted_at: Option<NaiveDateTime>,
+}
+
+impl User {
+    pub fn user_status(&self) -> UserStatus {
+        UserStatus::from_code(self.status)
+    }
 }
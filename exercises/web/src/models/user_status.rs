@@ -0,0 +1,37 @@
+use macros::ApiSchema;
+use serde::{Deserialize, Serialize};
+
+/// What `User::status` allows, each one granting everything the ones below it do.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ApiSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum UserStatus {
+    /// can not log in, the default of the column
+    Disabled,
+    Active,
+    Admin,
+}
+
+impl UserStatus {
+    pub fn code(&self) -> i16 {
+        match self {
+            UserStatus::Disabled => 0,
+            UserStatus::Active => 1,
+            UserStatus::Admin => 2,
+        }
+    }
+
+    /// Codes this build does not know are treated as disabled.
+    pub fn from_code(code: i16) -> Self {
+        match code {
+            1 => UserStatus::Active,
+            2 => UserStatus::Admin,
+            _ => UserStatus::Disabled,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        *self > UserStatus::Disabled
+    }
+}
@@ -0,0 +1,335 @@
+use crate::persistence::common::{
+    create_async_conn_pool, create_conn_pool, AsyncDbConnection, AsyncDbConnectionPool,
+    DbConnection, DbConnectionPool,
+};
+use crate::persistence::db_config::DbConfig;
+use crate::persistence::tx::{is_serialization_failure, Tx};
+use diesel::{Connection, PgConnection};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use tokio::sync::OnceCell;
+use tokio_postgres::IsolationLevel;
+use tracing::{info, warn};
+use utils::error::app_error::AppResult;
+
+static GLOBAL_DB: OnceLock<Db> = OnceLock::new();
+
+/// Handle to one database, owning its sync and async pools and those of its
+/// read replicas.
+///
+/// Cloning is cheap and every clone shares the same pools. Each pool is
+/// created on first use, so a handle for a sync caller never needs a runtime.
+///
+/// `conn`/`async_conn` always go to the primary. `read_conn`/`async_read_conn`
+/// take the replicas in turn, skip one that failed to connect for
+/// `DbConfig::replica_retry`, and fall back to the primary when none is left.
+/// A replica is waited for `DbConfig::replica_timeout` at most, not the
+/// primary's `connect_timeout`.
+#[derive(Clone)]
+pub struct Db {
+    inner: Arc<DbInner>,
+    force_primary: bool,
+}
+
+struct DbInner {
+    config: DbConfig,
+    pool: OnceLock<DbConnectionPool>,
+    async_pool: OnceCell<AsyncDbConnectionPool>,
+    replicas: Vec<Replica>,
+    next_replica: AtomicUsize,
+    /// callers waiting for a connection, of the sync and the async pool
+    waiting: AtomicUsize,
+    async_waiting: AtomicUsize,
+}
+
+/// Counts a caller in `waiting` until dropped, the checkout done or given up.
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl<'a> Waiting<'a> {
+    fn enter(waiting: &'a AtomicUsize) -> Self {
+        waiting.fetch_add(1, Ordering::Relaxed);
+        Self(waiting)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The state of one pool, see `Db::pool_stats`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolStats {
+    /// `address:port`
+    pub server: String,
+    /// `sync` for r2d2, `async` for bb8
+    pub kind: &'static str,
+    pub connections: u32,
+    pub idle: u32,
+    pub max_size: u32,
+    pub waiting: usize,
+    /// checkouts that gave up at `DbConfig::connect_timeout`, async only
+    pub timed_out: u64,
+}
+
+struct Replica {
+    db: Db,
+    down_until: Mutex<Option<Instant>>,
+}
+
+impl Replica {
+    fn is_up(&self) -> bool {
+        let down_until = self
+            .down_until
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        down_until.is_none_or(|until| Instant::now() >= until)
+    }
+
+    fn mark_down(&self, err: &anyhow::Error) {
+        let config = self.db.config();
+        warn!(
+            "Replica {}:{} is down for {:?}: {}",
+            config.address, config.port, config.replica_retry, err
+        );
+        *self
+            .down_until
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = Some(Instant::now() + config.replica_retry);
+    }
+}
+
+impl Db {
+    /// A primary on `config` with the replicas listed in `config.replicas`.
+    pub fn new(config: DbConfig) -> Self {
+        let replicas = config.replica_configs();
+        Self::with_replicas(config, replicas)
+    }
+
+    /// Replicas configured one by one, e.g. with other credentials; their
+    /// `connect_timeout` is cut to `config.replica_timeout`.
+    pub fn with_replicas(config: DbConfig, replicas: Vec<DbConfig>) -> Self {
+        let replicas = replicas
+            .into_iter()
+            .map(|mut replica| {
+                replica.connect_timeout = replica.connect_timeout.min(config.replica_timeout);
+                Replica {
+                    db: Self::with_replicas(replica, vec![]),
+                    down_until: Mutex::new(None),
+                }
+            })
+            .collect();
+        Self {
+            inner: Arc::new(DbInner {
+                config,
+                pool: OnceLock::new(),
+                async_pool: OnceCell::new(),
+                replicas,
+                next_replica: AtomicUsize::new(0),
+                waiting: AtomicUsize::new(0),
+                async_waiting: AtomicUsize::new(0),
+            }),
+            force_primary: false,
+        }
+    }
+
+    /// The process wide handle on `DbConfig::global()`, backing
+    /// `get_connection`/`get_async_connection` for code without a handle.
+    pub fn global() -> AppResult<&'static Db> {
+        if let Some(db) = GLOBAL_DB.get() {
+            return Ok(db);
+        }
+        let db = Db::new(DbConfig::global()?.clone());
+        Ok(GLOBAL_DB.get_or_init(|| db))
+    }
+
+    pub fn config(&self) -> &DbConfig {
+        &self.inner.config
+    }
+
+    pub fn pool(&self) -> AppResult<&DbConnectionPool> {
+        if let Some(pool) = self.inner.pool.get() {
+            return Ok(pool);
+        }
+        let pool = create_conn_pool(&self.inner.config)?;
+        Ok(self.inner.pool.get_or_init(|| pool))
+    }
+
+    pub async fn async_pool(&self) -> AppResult<&AsyncDbConnectionPool> {
+        self.inner
+            .async_pool
+            .get_or_try_init(|| create_async_conn_pool(&self.inner.config))
+            .await
+    }
+
+    pub fn conn(&self) -> AppResult<DbConnection> {
+        let pool = self.pool()?;
+        let _waiting = Waiting::enter(&self.inner.waiting);
+        Ok(pool.get()?)
+    }
+
+    pub async fn async_conn(&self) -> AppResult<AsyncDbConnection> {
+        let pool = self.async_pool().await?;
+        let _waiting = Waiting::enter(&self.inner.async_waiting);
+        Ok(pool.get_owned().await?)
+    }
+
+    /// The pools created so far, the primary's first, then the replicas'.
+    pub fn pool_stats(&self) -> Vec<PoolStats> {
+        let inner = &self.inner;
+        let server = format!("{}:{}", inner.config.address, inner.config.port);
+        let mut stats = vec![];
+        if let Some(pool) = inner.pool.get() {
+            let state = pool.state();
+            stats.push(PoolStats {
+                server: server.clone(),
+                kind: "sync",
+                connections: state.connections,
+                idle: state.idle_connections,
+                max_size: pool.max_size(),
+                waiting: inner.waiting.load(Ordering::Relaxed),
+                timed_out: 0,
+            });
+        }
+        if let Some(pool) = inner.async_pool.get() {
+            let state = pool.state();
+            stats.push(PoolStats {
+                server,
+                kind: "async",
+                connections: state.connections,
+                idle: state.idle_connections,
+                max_size: inner.config.max_pool_size,
+                waiting: inner.async_waiting.load(Ordering::Relaxed),
+                timed_out: state.statistics.get_timed_out,
+            });
+        }
+        for replica in &inner.replicas {
+            stats.extend(replica.db.pool_stats());
+        }
+        stats
+    }
+
+    /// Drops the pools, replicas' included, if this is the last handle; their
+    /// idle connections are closed at once, the ones still checked out when
+    /// they come back. Returns false, closing nothing, while other clones live.
+    pub fn close(self) -> bool {
+        match Arc::try_unwrap(self.inner) {
+            Ok(inner) => {
+                if let Some(pool) = inner.async_pool.get() {
+                    let state = pool.state();
+                    info!(
+                        "Closing pool of {}:{}, {} connections, {} idle",
+                        inner.config.address,
+                        inner.config.port,
+                        state.connections,
+                        state.idle_connections
+                    );
+                }
+                drop(inner);
+                true
+            }
+            Err(inner) => {
+                warn!(
+                    "Pools of {}:{} are still in use by {} handles",
+                    inner.config.address,
+                    inner.config.port,
+                    Arc::strong_count(&inner) - 1
+                );
+                false
+            }
+        }
+    }
+
+    // ----- read routing -----
+
+    /// The same pools, but reads stay on the primary, e.g. to read your own writes.
+    pub fn primary(&self) -> Db {
+        Self {
+            inner: self.inner.clone(),
+            force_primary: true,
+        }
+    }
+
+    pub fn read_conn(&self) -> AppResult<DbConnection> {
+        for replica in self.replicas_in_turn() {
+            match replica.db.conn() {
+                Ok(conn) => return Ok(conn),
+                Err(err) => replica.mark_down(&err),
+            }
+        }
+        self.conn()
+    }
+
+    pub async fn async_read_conn(&self) -> AppResult<AsyncDbConnection> {
+        for replica in self.replicas_in_turn() {
+            match replica.db.async_conn().await {
+                Ok(conn) => return Ok(conn),
+                Err(err) => replica.mark_down(&err),
+            }
+        }
+        self.async_conn().await
+    }
+
+    /// Replicas currently in the rotation.
+    pub fn replicas_up(&self) -> usize {
+        self.inner.replicas.iter().filter(|r| r.is_up()).count()
+    }
+
+    fn replicas_in_turn(&self) -> Vec<&Replica> {
+        let replicas = &self.inner.replicas;
+        if self.force_primary || replicas.is_empty() {
+            return vec![];
+        }
+        let start = self.inner.next_replica.fetch_add(1, Ordering::Relaxed);
+        (0..replicas.len())
+            .map(|i| &replicas[(start + i) % replicas.len()])
+            .filter(|replica| replica.is_up())
+            .collect()
+    }
+
+    // ----- units of work, see `persistence::tx` -----
+
+    pub async fn begin(&self) -> AppResult<Tx> {
+        self.begin_with(IsolationLevel::ReadCommitted).await
+    }
+
+    pub async fn begin_serializable(&self) -> AppResult<Tx> {
+        self.begin_with(IsolationLevel::Serializable).await
+    }
+
+    pub async fn begin_with(&self, isolation: IsolationLevel) -> AppResult<Tx> {
+        Tx::begin_on(self.async_conn().await?, isolation).await
+    }
+
+    /// Sync unit of work: every `*_with` persistence call made on `conn` inside
+    /// `f` commits or rolls back together. A nested `conn.transaction(..)` inside
+    /// `f` becomes a savepoint.
+    pub fn with_tx<T, F>(&self, mut f: F) -> AppResult<T>
+    where
+        F: FnMut(&mut PgConnection) -> AppResult<T>,
+    {
+        let mut conn = self.conn()?;
+        conn.transaction(|conn| f(conn))
+    }
+
+    /// `with_tx` at serializable isolation, retried up to `max_attempts` times
+    /// in total on serialization failure.
+    pub fn with_serializable_tx<T, F>(&self, max_attempts: u32, mut f: F) -> AppResult<T>
+    where
+        F: FnMut(&mut PgConnection) -> AppResult<T>,
+    {
+        let mut conn = self.conn()?;
+        let mut attempt = 1;
+        loop {
+            match conn.build_transaction().serializable().run(|conn| f(conn)) {
+                Err(err) if attempt < max_attempts && is_serialization_failure(&err) => {
+                    warn!("Serialization failure, retry {}/{}", attempt, max_attempts);
+                    attempt += 1;
+                }
+                rs => return rs,
+            }
+        }
+    }
+}
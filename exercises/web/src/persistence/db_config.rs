@@ -0,0 +1,334 @@
+use anyhow::anyhow;
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::X509;
+use postgres_openssl::MakeTlsConnector;
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+use utils::error::app_error::AppResult;
+
+static DB_CONFIG: OnceLock<DbConfig> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SslMode {
+    Disable,
+    #[default]
+    Prefer,
+    Require,
+    /// encrypted and the server certificate must chain to `ssl_root_cert`
+    /// (or the system roots) and match the host name
+    VerifyFull,
+}
+
+impl SslMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SslMode::Disable => "disable",
+            SslMode::Prefer => "prefer",
+            SslMode::Require => "require",
+            SslMode::VerifyFull => "verify-full",
+        }
+    }
+}
+
+impl FromStr for SslMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "disable" => Ok(SslMode::Disable),
+            "prefer" => Ok(SslMode::Prefer),
+            "require" => Ok(SslMode::Require),
+            "verify-full" => Ok(SslMode::VerifyFull),
+            _ => Err(anyhow!("unknown ssl mode: {s}")),
+        }
+    }
+}
+
+/// Settings for both connection pools, read from `.env.{ENV}` where the
+/// process environment wins over the file.
+///
+/// | variable                  | default        |
+/// |---------------------------|----------------|
+/// | `DB_ADDRESS`              | required       |
+/// | `DB_PORT`                 | `5432`         |
+/// | `DB_NAME`                 | required       |
+/// | `DB_USERNAME`             | required       |
+/// | `DB_PASSWORD`             | required       |
+/// | `DB_SSL_MODE`             | `prefer`       |
+/// | `DB_SSL_ROOT_CERT`        | system roots   |
+/// | `DB_CONNECT_TIMEOUT_SECS` | `30`           |
+/// | `DB_IDLE_TIMEOUT_SECS`    | `600`, 0 = off |
+/// | `DB_STATEMENT_TIMEOUT_MS` | `0` = off      |
+/// | `DB_APPLICATION_NAME`     | `web`          |
+/// | `DB_SCHEMA`               | server default |
+/// | `DB_REPLICAS`             | none           |
+/// | `DB_REPLICA_RETRY_SECS`   | `30`           |
+/// | `DB_REPLICA_TIMEOUT_MS`   | `1000`         |
+/// | `DB_MIN_POOL_SIZE`        | `1`            |
+/// | `DB_MAX_POOL_SIZE`        | `5`            |
+#[derive(Debug, Clone)]
+pub struct DbConfig {
+    pub address: String,
+    pub port: u16,
+    pub name: String,
+    pub username: String,
+    pub password: String,
+    pub ssl_mode: SslMode,
+    /// PEM bundle of the CAs trusted for `verify-full`
+    pub ssl_root_cert: Option<PathBuf>,
+    pub connect_timeout: Duration,
+    pub idle_timeout: Option<Duration>,
+    pub statement_timeout: Option<Duration>,
+    pub application_name: String,
+    /// put first on the `search_path` of every pooled connection
+    pub schema: Option<String>,
+    /// read replicas as `host[:port]`, reached with the primary's credentials
+    pub replicas: Vec<(String, u16)>,
+    /// how long a replica that failed to connect is left out of the rotation
+    pub replica_retry: Duration,
+    /// how long a read waits for a replica before it goes to the primary,
+    /// in place of the replica's own `connect_timeout` when that is longer
+    pub replica_timeout: Duration,
+    pub min_pool_size: u32,
+    pub max_pool_size: u32,
+}
+
+impl DbConfig {
+    /// Loaded on first use and shared by the process wide pools.
+    pub fn global() -> AppResult<&'static DbConfig> {
+        if let Some(config) = DB_CONFIG.get() {
+            return Ok(config);
+        }
+        let config = Self::load()?;
+        Ok(DB_CONFIG.get_or_init(|| config))
+    }
+
+    pub fn load() -> AppResult<Self> {
+        Self::from_lookup(env_lookup())
+    }
+
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> AppResult<Self> {
+        let required = |key: &str| lookup(key).ok_or_else(|| anyhow!("{key} is not configured"));
+        let parsed = |key: &str, default: &str| parse_var::<u64>(&lookup, key, default);
+        let non_zero = |val: Duration| (!val.is_zero()).then_some(val);
+
+        let config = Self {
+            address: required("DB_ADDRESS")?,
+            port: parsed("DB_PORT", "5432")?.try_into()?,
+            name: required("DB_NAME")?,
+            username: required("DB_USERNAME")?,
+            password: required("DB_PASSWORD")?,
+            ssl_mode: lookup("DB_SSL_MODE")
+                .map(|val| val.parse::<SslMode>())
+                .transpose()?
+                .unwrap_or_default(),
+            ssl_root_cert: lookup("DB_SSL_ROOT_CERT").map(PathBuf::from),
+            connect_timeout: Duration::from_secs(parsed("DB_CONNECT_TIMEOUT_SECS", "30")?),
+            idle_timeout: non_zero(Duration::from_secs(parsed("DB_IDLE_TIMEOUT_SECS", "600")?)),
+            statement_timeout: non_zero(Duration::from_millis(parsed(
+                "DB_STATEMENT_TIMEOUT_MS",
+                "0",
+            )?)),
+            application_name: lookup("DB_APPLICATION_NAME").unwrap_or_else(|| "web".to_string()),
+            schema: lookup("DB_SCHEMA")
+                .filter(|val| !val.is_empty())
+                .map(check_schema)
+                .transpose()?,
+            replicas: lookup("DB_REPLICAS")
+                .map(|val| parse_hosts(&val))
+                .transpose()?
+                .unwrap_or_default(),
+            replica_retry: Duration::from_secs(parsed("DB_REPLICA_RETRY_SECS", "30")?),
+            replica_timeout: Duration::from_millis(parsed("DB_REPLICA_TIMEOUT_MS", "1000")?),
+            min_pool_size: parsed("DB_MIN_POOL_SIZE", "1")?.try_into()?,
+            max_pool_size: parsed("DB_MAX_POOL_SIZE", "5")?.try_into()?,
+        };
+        if config.max_pool_size == 0 || config.min_pool_size > config.max_pool_size {
+            return Err(anyhow!(
+                "DB_MAX_POOL_SIZE ({}) must be positive and not below DB_MIN_POOL_SIZE ({})",
+                config.max_pool_size,
+                config.min_pool_size
+            ));
+        }
+        if config.connect_timeout.is_zero() {
+            return Err(anyhow!("DB_CONNECT_TIMEOUT_SECS must be positive"));
+        }
+        if config.replica_timeout.is_zero() {
+            return Err(anyhow!("DB_REPLICA_TIMEOUT_MS must be positive"));
+        }
+        Ok(config)
+    }
+
+    /// One config per entry of `replicas`, otherwise the same as this one.
+    pub fn replica_configs(&self) -> Vec<DbConfig> {
+        self.replicas
+            .iter()
+            .map(|(address, port)| DbConfig {
+                address: address.clone(),
+                port: *port,
+                replicas: vec![],
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// libpq keyword/value connection string for the diesel pool.
+    pub fn conn_string(&self) -> String {
+        let mut parts = vec![
+            format!("host={}", quote(&self.address)),
+            format!("port={}", self.port),
+            format!("dbname={}", quote(&self.name)),
+            format!("user={}", quote(&self.username)),
+            format!("password={}", quote(&self.password)),
+            format!("sslmode={}", self.ssl_mode.as_str()),
+            format!("application_name={}", quote(&self.application_name)),
+        ];
+        if let Some(root_cert) = &self.ssl_root_cert {
+            parts.push(format!(
+                "sslrootcert={}",
+                quote(&root_cert.to_string_lossy())
+            ));
+        }
+        if !self.connect_timeout.is_zero() {
+            // libpq only takes whole seconds
+            parts.push(format!(
+                "connect_timeout={}",
+                self.connect_timeout.as_secs().max(1)
+            ));
+        }
+        if let Some(options) = self.options() {
+            parts.push(format!("options={}", quote(&options)));
+        }
+        parts.join(" ")
+    }
+
+    /// The same settings for tokio-postgres. It has no `verify-full` mode of its
+    /// own, certificate checks are left to the connector from `tls_connector`.
+    pub fn pg_config(&self) -> tokio_postgres::Config {
+        let mut pg_config = tokio_postgres::Config::new();
+        pg_config
+            .host(&self.address)
+            .port(self.port)
+            .dbname(&self.name)
+            .user(&self.username)
+            .password(&self.password)
+            .application_name(&self.application_name)
+            .ssl_mode(match self.ssl_mode {
+                SslMode::Disable => tokio_postgres::config::SslMode::Disable,
+                SslMode::Prefer => tokio_postgres::config::SslMode::Prefer,
+                SslMode::Require | SslMode::VerifyFull => tokio_postgres::config::SslMode::Require,
+            });
+        if !self.connect_timeout.is_zero() {
+            pg_config.connect_timeout(self.connect_timeout);
+        }
+        if let Some(options) = self.options() {
+            pg_config.options(options);
+        }
+        pg_config
+    }
+
+    /// Server settings passed at connect time.
+    fn options(&self) -> Option<String> {
+        let mut options = vec![];
+        if let Some(timeout) = self.statement_timeout {
+            options.push(format!("-c statement_timeout={}", timeout.as_millis()));
+        }
+        if let Some(schema) = &self.schema {
+            options.push(format!("-c search_path={schema}"));
+        }
+        (!options.is_empty()).then(|| options.join(" "))
+    }
+
+    /// OpenSSL connector for the async pool. Only `verify-full` checks the
+    /// certificate chain and host name, `prefer` and `require` just encrypt,
+    /// as libpq does without a root certificate. With `disable` the root
+    /// certificate is not read, the connector is never used.
+    pub fn tls_connector(&self) -> AppResult<MakeTlsConnector> {
+        let mut builder = SslConnector::builder(SslMethod::tls_client())?;
+        let root_cert = self
+            .ssl_root_cert
+            .as_ref()
+            .filter(|_| self.ssl_mode != SslMode::Disable);
+        if let Some(root_cert) = root_cert {
+            // like libpq's sslrootcert: trust this bundle only, not the system roots
+            let mut store = X509StoreBuilder::new()?;
+            for cert in X509::stack_from_pem(&std::fs::read(root_cert)?)? {
+                store.add_cert(cert)?;
+            }
+            builder.set_cert_store(store.build());
+        }
+        let verify = self.ssl_mode == SslMode::VerifyFull;
+        builder.set_verify(if verify {
+            SslVerifyMode::PEER
+        } else {
+            SslVerifyMode::NONE
+        });
+        let mut connector = MakeTlsConnector::new(builder.build());
+        connector.set_callback(move |connect_config, _domain| {
+            connect_config.set_verify_hostname(verify);
+            Ok(())
+        });
+        Ok(connector)
+    }
+}
+
+/// The environment name picking the `.env.{name}` file, `ENV` first, then `RUN_ENV`.
+pub fn run_env() -> String {
+    env::var("ENV")
+        .or_else(|_| env::var("RUN_ENV"))
+        .unwrap_or_else(|_| "dev".to_string())
+}
+
+/// The process environment after `.env.{run_env()}` is loaded into it, as the
+/// `from_lookup` of every config takes it; variables set already are kept.
+pub fn env_lookup() -> impl Fn(&str) -> Option<String> {
+    dotenvy::from_filename(format!(".env.{}", run_env())).ok();
+    |key| env::var(key).ok()
+}
+
+/// The number in `key`, `default` when it is not set.
+pub fn parse_var<T>(
+    lookup: impl Fn(&str) -> Option<String>,
+    key: &str,
+    default: &str,
+) -> AppResult<T>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    let val = lookup(key).unwrap_or_else(|| default.to_string());
+    val.parse::<T>()
+        .map_err(|err| anyhow!("{key}={val} is not a number: {err}"))
+}
+
+/// `host1,host2:5433`, the port defaults to 5432.
+fn parse_hosts(val: &str) -> AppResult<Vec<(String, u16)>> {
+    val.split(',')
+        .map(str::trim)
+        .filter(|host| !host.is_empty())
+        .map(|host| match host.rsplit_once(':') {
+            Some((address, port)) => Ok((
+                address.to_string(),
+                port.parse::<u16>()
+                    .map_err(|err| anyhow!("DB_REPLICAS: bad port in {host}: {err}"))?,
+            )),
+            None => Ok((host.to_string(), 5432)),
+        })
+        .collect()
+}
+
+fn check_schema(val: String) -> AppResult<String> {
+    if val.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Ok(val)
+    } else {
+        Err(anyhow!("DB_SCHEMA={val} must be alphanumeric or _"))
+    }
+}
+
+fn quote(val: &str) -> String {
+    format!("'{}'", val.replace('\\', "\\\\").replace('\'', "\\'"))
+}
@@ -0,0 +1,193 @@
+use anyhow::anyhow;
+use chrono::NaiveDateTime;
+use tokio_postgres::Client;
+use tracing::info;
+use utils::error::app_error::AppResult;
+
+/// Versioned schema change, embedded from `migrations/<version>_<name>/{up,down}.sql`.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+macro_rules! migration {
+    ($version:literal, $dir:literal) => {
+        Migration {
+            version: $version,
+            name: $dir,
+            up: include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/migrations/",
+                $dir,
+                "/up.sql"
+            )),
+            down: include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/migrations/",
+                $dir,
+                "/down.sql"
+            )),
+        }
+    };
+}
+
+/// In version order; append new ones at the end.
+pub static MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_create_test_rec"),
+    migration!(2, "0002_create_user"),
+    migration!(3, "0003_add_version"),
+    migration!(4, "0004_add_deleted_at"),
+    migration!(5, "0005_org_treepath_index"),
+    migration!(6, "0006_user_username_index"),
+    migration!(7, "0007_create_refresh_token"),
+    migration!(8, "0008_create_outbox"),
+];
+
+const HISTORY_TABLE: &str = "schema_migrations";
+/// serializes runners against the same database
+const LOCK_KEY: i64 = 0x0077_6562_5f6d_6967;
+
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: &'static str,
+    pub applied_at: Option<NaiveDateTime>,
+}
+
+/// Applies every pending migration, each in its own transaction, and returns
+/// the versions applied.
+pub async fn migrate_up(client: &mut Client) -> AppResult<Vec<i64>> {
+    lock(client).await?;
+    let rs = apply_pending(client).await;
+    unlock(client).await?;
+    rs
+}
+
+/// Reverts the `steps` most recently applied migrations, newest first.
+pub async fn migrate_down(client: &mut Client, steps: usize) -> AppResult<Vec<i64>> {
+    lock(client).await?;
+    let rs = revert_last(client, steps).await;
+    unlock(client).await?;
+    rs
+}
+
+/// Read only, for probes on a role that may not create tables: without the
+/// history table every migration is pending.
+pub async fn status(client: &Client) -> AppResult<Vec<MigrationStatus>> {
+    let exists: bool = client
+        .query_one("select to_regclass($1) is not null", &[&HISTORY_TABLE])
+        .await?
+        .get(0);
+    let rows = match exists {
+        true => {
+            client
+                .query(
+                    &format!("select version, applied_at from {HISTORY_TABLE}"),
+                    &[],
+                )
+                .await?
+        }
+        false => vec![],
+    };
+    Ok(MIGRATIONS
+        .iter()
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            name: migration.name,
+            applied_at: rows
+                .iter()
+                .find(|row| row.get::<_, i64>("version") == migration.version)
+                .map(|row| row.get("applied_at")),
+        })
+        .collect())
+}
+
+/// Versions of this build not applied yet, see `status`.
+pub async fn pending(client: &Client) -> AppResult<Vec<i64>> {
+    Ok(status(client)
+        .await?
+        .into_iter()
+        .filter(|s| s.applied_at.is_none())
+        .map(|s| s.version)
+        .collect())
+}
+
+async fn ensure_history_table(client: &Client) -> AppResult<()> {
+    client
+        .batch_execute(&format!(
+            "create table if not exists {HISTORY_TABLE} (
+                version    bigint primary key,
+                name       varchar   not null,
+                applied_at timestamp not null default localtimestamp
+            )"
+        ))
+        .await?;
+    Ok(())
+}
+
+async fn applied_versions(client: &Client) -> AppResult<Vec<i64>> {
+    ensure_history_table(client).await?;
+    let rows = client
+        .query(
+            &format!("select version from {HISTORY_TABLE} order by version"),
+            &[],
+        )
+        .await?;
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+async fn apply_pending(client: &mut Client) -> AppResult<Vec<i64>> {
+    let applied = applied_versions(client).await?;
+    let mut done = vec![];
+    for migration in MIGRATIONS.iter().filter(|m| !applied.contains(&m.version)) {
+        info!("Migrate up {}", migration.name);
+        let tx = client.transaction().await?;
+        tx.batch_execute(migration.up).await?;
+        tx.execute(
+            &format!("insert into {HISTORY_TABLE} (version, name) values ($1, $2)"),
+            &[&migration.version, &migration.name],
+        )
+        .await?;
+        tx.commit().await?;
+        done.push(migration.version);
+    }
+    Ok(done)
+}
+
+async fn revert_last(client: &mut Client, steps: usize) -> AppResult<Vec<i64>> {
+    let applied = applied_versions(client).await?;
+    let mut done = vec![];
+    for version in applied.iter().rev().take(steps) {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|m| m.version == *version)
+            .ok_or_else(|| anyhow!("applied migration {version} is unknown to this build"))?;
+        info!("Migrate down {}", migration.name);
+        let tx = client.transaction().await?;
+        tx.batch_execute(migration.down).await?;
+        tx.execute(
+            &format!("delete from {HISTORY_TABLE} where version = $1"),
+            &[&migration.version],
+        )
+        .await?;
+        tx.commit().await?;
+        done.push(migration.version);
+    }
+    Ok(done)
+}
+
+async fn lock(client: &Client) -> AppResult<()> {
+    client
+        .execute("select pg_advisory_lock($1)", &[&LOCK_KEY])
+        .await?;
+    Ok(())
+}
+
+async fn unlock(client: &Client) -> AppResult<()> {
+    client
+        .execute("select pg_advisory_unlock($1)", &[&LOCK_KEY])
+        .await?;
+    Ok(())
+}
@@ -1,4 +1,16 @@
 pub mod common;
+pub mod db;
+pub mod db_config;
+pub mod migration;
+pub mod notification;
+pub mod org_tree;
+pub mod outbox_persistence;
+pub mod page;
+pub mod query;
+pub mod refresh_token_persistence;
+pub mod repository;
 pub mod sample_rec_persistence;
 pub mod sample_rec_persistence_async;
+pub mod tx;
 pub mod user_persistence;
+pub mod user_persistence_async;
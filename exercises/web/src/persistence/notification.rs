@@ -0,0 +1,238 @@
+use crate::persistence::db::Db;
+use crate::persistence::db_config::DbConfig;
+use anyhow::anyhow;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::poll_fn;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
+use tokio_postgres::{AsyncMessage, GenericClient};
+use tokio_stream::Stream;
+use tracing::{info, warn};
+use utils::error::app_error::AppResult;
+
+// LISTEN/NOTIFY. A subscription holds a connection of its own, outside the
+// pools: a pooled connection drops the notifications it receives, and would
+// hand its LISTEN on to the next caller. Always on the primary, replicas do
+// not see a NOTIFY.
+
+/// Notifications a subscription holds before the connection is read no
+/// further; the server keeps queueing them meanwhile.
+const BUFFER: usize = 64;
+const RECONNECT_MIN: Duration = Duration::from_millis(100);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+/// A connection lost without a word, e.g. behind a dropped route, sends no
+/// error either; a query now and then finds it out.
+const HEARTBEAT: Duration = Duration::from_secs(30);
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longer channel names are cut short by the server, NAMEDATALEN - 1.
+const MAX_CHANNEL_LEN: usize = 63;
+
+/// An item of a `Subscription`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notification<T> {
+    /// One NOTIFY on the channel; `pid` is the backend that sent it.
+    Message { pid: i32, payload: T },
+    /// The connection was lost and is listening again. Whatever was sent in
+    /// between is lost, so re-read what is kept from the channel, e.g. a cache.
+    Resubscribed,
+}
+
+/// The notifications of one channel, payloads decoded from JSON into `T`.
+///
+/// A payload that does not decode is yielded as an `Err`, the stream goes on.
+/// The connection comes back by itself once lost, see `Notification::Resubscribed`,
+/// so the stream only ends when dropped; dropping it closes the connection.
+pub struct Subscription<T> {
+    channel: String,
+    rx: mpsc::Receiver<AppResult<Notification<String>>>,
+    task: JoinHandle<()>,
+    _payload: PhantomData<fn() -> T>,
+}
+
+impl<T> Subscription<T> {
+    pub fn channel(&self) -> &str {
+        &self.channel
+    }
+}
+
+impl<T: DeserializeOwned> Stream for Subscription<T> {
+    type Item = AppResult<Notification<T>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let channel = self.channel.clone();
+        self.rx.poll_recv(cx).map(|item| {
+            item.map(|item| match item? {
+                Notification::Message { pid, payload } => Ok(Notification::Message {
+                    pid,
+                    payload: serde_json::from_str(&payload).map_err(|err| {
+                        anyhow!("payload on {channel} does not decode: {err}: {payload}")
+                    })?,
+                }),
+                Notification::Resubscribed => Ok(Notification::Resubscribed),
+            })
+        })
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Listens on `channel` of the primary of `db`; returns once listening, so
+/// every notification sent afterwards is received.
+pub async fn subscribe<T: DeserializeOwned>(db: &Db, channel: &str) -> AppResult<Subscription<T>> {
+    if channel.is_empty() {
+        return Err(anyhow!("channel name must not be empty"));
+    }
+    if channel.len() > MAX_CHANNEL_LEN {
+        return Err(anyhow!(
+            "channel name {channel} is longer than {MAX_CHANNEL_LEN} bytes"
+        ));
+    }
+    let (tx, rx) = mpsc::channel(BUFFER);
+    let (ready_tx, ready_rx) = oneshot::channel();
+    let task = tokio::spawn(listen(
+        db.config().clone(),
+        channel.to_string(),
+        tx,
+        ready_tx,
+    ));
+    match ready_rx.await {
+        Ok(Ok(())) => Ok(Subscription {
+            channel: channel.to_string(),
+            rx,
+            task,
+            _payload: PhantomData,
+        }),
+        Ok(Err(err)) => Err(err),
+        Err(_) => Err(anyhow!("listener of {channel} stopped")),
+    }
+}
+
+/// Sends `payload` as JSON to the listeners of `channel`.
+pub async fn notify(db: &Db, channel: &str, payload: &impl Serialize) -> AppResult<()> {
+    let conn = db.async_conn().await?;
+    notify_with(&*conn, channel, payload).await
+}
+
+/// On a `Tx` the notification is sent on commit, and not at all on rollback.
+pub async fn notify_with<C: GenericClient + Sync>(
+    client: &C,
+    channel: &str,
+    payload: &impl Serialize,
+) -> AppResult<()> {
+    let payload = serde_json::to_string(payload)?;
+    client
+        .execute("select pg_notify($1, $2)", &[&channel, &payload])
+        .await?;
+    Ok(())
+}
+
+/// The first connection fails `subscribe`, later ones are retried for as
+/// long as the subscription lives.
+async fn listen(
+    config: DbConfig,
+    channel: String,
+    tx: mpsc::Sender<AppResult<Notification<String>>>,
+    ready: oneshot::Sender<AppResult<()>>,
+) {
+    let mut ready = Some(ready);
+    let mut retry_in = RECONNECT_MIN;
+    loop {
+        let err = match listen_once(&config, &channel, &tx, &mut ready, &mut retry_in).await {
+            // the subscription is gone
+            Ok(()) => return,
+            Err(err) => err,
+        };
+        if let Some(ready) = ready.take() {
+            let _ = ready.send(Err(err));
+            return;
+        }
+        warn!(
+            "Listener of {} lost, reconnecting in {:?}: {:#}",
+            channel, retry_in, err
+        );
+        sleep(retry_in).await;
+        retry_in = (retry_in * 2).min(RECONNECT_MAX);
+    }
+}
+
+/// Connects, listens and forwards notifications until the connection is
+/// lost or misses a heartbeat; `Ok` once nobody reads them anymore.
+async fn listen_once(
+    config: &DbConfig,
+    channel: &str,
+    tx: &mpsc::Sender<AppResult<Notification<String>>>,
+    ready: &mut Option<oneshot::Sender<AppResult<()>>>,
+    retry_in: &mut Duration,
+) -> AppResult<()> {
+    let (client, mut connection) = config.pg_config().connect(config.tls_connector()?).await?;
+    // the connection carries the answers to the client too, read it from the start
+    let forward = async {
+        loop {
+            match poll_fn(|cx| connection.poll_message(cx)).await {
+                Some(Ok(AsyncMessage::Notification(message))) if message.channel() == channel => {
+                    let item = Notification::Message {
+                        pid: message.process_id(),
+                        payload: message.payload().to_string(),
+                    };
+                    if tx.send(Ok(item)).await.is_err() {
+                        return Ok(());
+                    }
+                }
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(err.into()),
+                None => return Err(anyhow!("connection closed")),
+            }
+        }
+    };
+    let subscribe = async {
+        let quoted = channel.replace('"', "\"\"");
+        client
+            .batch_execute(&format!("listen \"{quoted}\""))
+            .await?;
+        let delivered = match ready.take() {
+            Some(ready) => ready.send(Ok(())).is_ok(),
+            None => {
+                info!("Listening on {} again", channel);
+                *retry_in = RECONNECT_MIN;
+                tx.send(Ok(Notification::Resubscribed)).await.is_ok()
+            }
+        };
+        if !delivered {
+            return Ok(());
+        }
+        loop {
+            sleep(HEARTBEAT).await;
+            let answer = client.simple_query("select 1");
+            tokio::pin!(answer);
+            loop {
+                match timeout(HEARTBEAT_TIMEOUT, &mut answer).await {
+                    Ok(rs) => {
+                        rs?;
+                        break;
+                    }
+                    // the connection is not read while the subscriber is behind
+                    Err(_) if tx.capacity() == 0 => {}
+                    Err(_) => {
+                        return Err(anyhow!(
+                            "no answer to the heartbeat in {HEARTBEAT_TIMEOUT:?}"
+                        ))
+                    }
+                }
+            }
+        }
+    };
+    tokio::select! {
+        rs = forward => rs,
+        rs = subscribe => rs,
+    }
+}
@@ -0,0 +1,182 @@
+use crate::models::org_path::OrgPath;
+use crate::models::user::User;
+use crate::persistence::db::Db;
+use crate::persistence::repository::AsyncRepository;
+use crate::persistence::user_persistence_async::UserRepository;
+use crate::utils::metrics::timed;
+use anyhow::anyhow;
+use tokio_postgres::GenericClient;
+use utils::error::app_error::AppResult;
+
+// Subtree queries are prefix matches on `org_treepath`, served by the
+// `varchar_pattern_ops` index of migration 0005. Paths are checked by
+// `OrgPath::parse` and hold no `like` wildcards.
+
+fn subtree_pattern(path: &OrgPath) -> String {
+    format!("{path}%")
+}
+
+/// Users in the org of `path` and every org below it, by path then id.
+pub async fn find_subtree(
+    db: &Db,
+    path: &OrgPath,
+    page_no: u32,
+    page_size: u32,
+) -> AppResult<Vec<User>> {
+    let conn = db.async_read_conn().await?;
+    let query = find_subtree_with(&*conn, path, page_no, page_size);
+    timed(UserRepository::TABLE_NAME, "find_subtree", query).await
+}
+
+/// Users in the orgs above `path`, root first.
+pub async fn find_ancestors(db: &Db, path: &OrgPath) -> AppResult<Vec<User>> {
+    let conn = db.async_read_conn().await?;
+    timed(
+        UserRepository::TABLE_NAME,
+        "find_ancestors",
+        find_ancestors_with(&*conn, path),
+    )
+    .await
+}
+
+/// The orgs below `path` that have users, by path.
+pub async fn descendants(db: &Db, path: &OrgPath) -> AppResult<Vec<OrgPath>> {
+    let conn = db.async_read_conn().await?;
+    timed(
+        UserRepository::TABLE_NAME,
+        "descendants",
+        descendants_with(&*conn, path),
+    )
+    .await
+}
+
+/// Moves the org of `path`, with everything below it, under `new_parent`,
+/// or to the top with `None`. Returns the new path of the org.
+///
+/// Every treepath is rewritten in one transaction; the rows are stamped and
+/// their version bumped like any other update.
+pub async fn move_subtree(
+    db: &Db,
+    path: &OrgPath,
+    new_parent: Option<&OrgPath>,
+) -> AppResult<OrgPath> {
+    let tx = db.begin().await?;
+    let query = move_subtree_with(tx.client(), path, new_parent);
+    let moved = timed(UserRepository::TABLE_NAME, "move_subtree", query).await?;
+    tx.commit().await?;
+    Ok(moved)
+}
+
+// ----- the same operations on a caller supplied client, e.g. `Tx::client()` -----
+
+pub async fn find_subtree_with<C: GenericClient + Sync>(
+    client: &C,
+    path: &OrgPath,
+    page_no: u32,
+    page_size: u32,
+) -> AppResult<Vec<User>> {
+    let sql = format!(
+        "select {} from {} where org_treepath like $1 and deleted_at is null \
+         order by org_treepath, id_ limit $2 offset $3",
+        UserRepository::COLUMNS.join(", "),
+        UserRepository::TABLE_NAME
+    );
+    let offset = page_no as i64 * page_size as i64;
+    let rows = client
+        .query(
+            &sql,
+            &[&subtree_pattern(path), &(page_size as i64), &offset],
+        )
+        .await?;
+    rows.iter().map(UserRepository::from_row).collect()
+}
+
+pub async fn find_ancestors_with<C: GenericClient + Sync>(
+    client: &C,
+    path: &OrgPath,
+) -> AppResult<Vec<User>> {
+    let ancestors = path
+        .ancestors()
+        .iter()
+        .map(|ancestor| ancestor.to_string())
+        .collect::<Vec<String>>();
+    let sql = format!(
+        "select {} from {} where org_treepath = any($1) and deleted_at is null \
+         order by length(org_treepath), id_",
+        UserRepository::COLUMNS.join(", "),
+        UserRepository::TABLE_NAME
+    );
+    let rows = client.query(&sql, &[&ancestors]).await?;
+    rows.iter().map(UserRepository::from_row).collect()
+}
+
+pub async fn descendants_with<C: GenericClient + Sync>(
+    client: &C,
+    path: &OrgPath,
+) -> AppResult<Vec<OrgPath>> {
+    let sql = format!(
+        "select distinct org_treepath from {} \
+         where org_treepath like $1 and org_treepath <> $2 and deleted_at is null \
+         order by org_treepath",
+        UserRepository::TABLE_NAME
+    );
+    let rows = client
+        .query(&sql, &[&subtree_pattern(path), &path.as_str()])
+        .await?;
+    rows.iter()
+        .map(|row| OrgPath::parse(row.try_get(0)?))
+        .collect()
+}
+
+/// Run it on a `Tx`, the subtree is locked for the checks and the rewrite.
+pub async fn move_subtree_with<C: GenericClient + Sync>(
+    client: &C,
+    path: &OrgPath,
+    new_parent: Option<&OrgPath>,
+) -> AppResult<OrgPath> {
+    let moved = match new_parent {
+        Some(parent) => parent.child(path.org_id()),
+        None => OrgPath::root(path.org_id()),
+    };
+    if new_parent.is_some_and(|parent| path.contains(parent)) {
+        return Err(anyhow!("can not move {path} under itself"));
+    }
+    if moved == *path {
+        return Ok(moved);
+    }
+    let table = UserRepository::TABLE_NAME;
+    let locked = client
+        .query(
+            &format!("select id_ from {table} where org_treepath like $1 for update"),
+            &[&subtree_pattern(path)],
+        )
+        .await?;
+    if locked.is_empty() {
+        return Err(anyhow!("no users in org {path}"));
+    }
+    let taken = client
+        .query_opt(
+            &format!("select 1 from {table} where org_treepath like $1 limit 1"),
+            &[&subtree_pattern(&moved)],
+        )
+        .await?;
+    if taken.is_some() {
+        return Err(anyhow!("org {moved} already has users"));
+    }
+    // soft deleted users move too, a restore must find a valid path
+    client
+        .execute(
+            &format!(
+                "update {table} set org_treepath = $2 || substr(org_treepath, $3), \
+                 modified_date = localtimestamp, version_ = version_ + 1 \
+                 where org_treepath like $1"
+            ),
+            &[
+                &subtree_pattern(path),
+                &moved.as_str(),
+                &(path.as_str().len() as i32 + 1),
+            ],
+        )
+        .await?;
+    Ok(moved)
+}
@@ -0,0 +1,201 @@
+use crate::models::domain_event::{DomainEvent, EventType, OutboxEvent};
+use crate::persistence::db::Db;
+use crate::utils::metrics::timed;
+use anyhow::anyhow;
+use std::time::Duration;
+use tokio_postgres::{GenericClient, Row};
+use utils::error::app_error::AppResult;
+
+// The transactional outbox: `insert_with` runs on the `Tx` of the change the
+// event tells about, so both are committed or neither; the dispatcher of
+// `services::outbox_dispatcher` leases and marks them. Always on the primary.
+
+const TABLE: &str = "outbox";
+const COLUMNS: &str =
+    "id_, aggregate, aggregate_id, event_type, payload, created_at, attempts, last_error";
+
+fn from_row(row: &Row) -> AppResult<OutboxEvent> {
+    let event_type: String = row.try_get("event_type")?;
+    Ok(OutboxEvent {
+        id: row.try_get("id_")?,
+        event: DomainEvent {
+            aggregate: row.try_get("aggregate")?,
+            aggregate_id: row.try_get("aggregate_id")?,
+            event_type: EventType::from_name(&event_type)
+                .ok_or_else(|| anyhow!("unknown event type {event_type}"))?,
+            payload: row.try_get("payload")?,
+        },
+        created_at: row.try_get("created_at")?,
+        attempts: row.try_get("attempts")?,
+        last_error: row.try_get("last_error")?,
+    })
+}
+
+/// Dead events, newest first, see `mark_failed_with`.
+pub async fn find_dead(db: &Db, limit: u32) -> AppResult<Vec<OutboxEvent>> {
+    let conn = db.async_conn().await?;
+    timed(TABLE, "find_dead", async {
+        let sql = format!(
+            "select {COLUMNS} from {TABLE} where dead_at is not null order by id_ desc limit $1"
+        );
+        conn.query(&sql, &[&(limit as i64)])
+            .await?
+            .iter()
+            .map(from_row)
+            .collect()
+    })
+    .await
+}
+
+/// Makes a dead event pending again with its attempts reset, e.g. once the
+/// sink that failed it is fixed.
+pub async fn requeue(db: &Db, id: i64) -> AppResult<bool> {
+    let conn = db.async_conn().await?;
+    timed(TABLE, "requeue", async {
+        let count = conn
+            .execute(
+                "update outbox set dead_at = null, attempts = 0, next_attempt_at = localtimestamp \
+                 where id_ = $1 and dead_at is not null",
+                &[&id],
+            )
+            .await?;
+        Ok(count > 0)
+    })
+    .await
+}
+
+/// Events neither delivered nor dead, due or not.
+pub async fn count_pending(db: &Db) -> AppResult<i64> {
+    let conn = db.async_conn().await?;
+    timed(TABLE, "count_pending", async {
+        let row = conn
+            .query_one(
+                "select count(*) from outbox where delivered_at is null and dead_at is null",
+                &[],
+            )
+            .await?;
+        Ok(row.try_get(0)?)
+    })
+    .await
+}
+
+// ----- on a caller supplied client, e.g. `Tx::client()` -----
+
+/// Returns the id of the event.
+pub async fn insert_with<C: GenericClient + Sync>(
+    client: &C,
+    event: &DomainEvent,
+) -> AppResult<i64> {
+    timed(TABLE, "insert", async {
+        let row = client
+            .query_one(
+                "insert into outbox (aggregate, aggregate_id, event_type, payload) \
+                 values ($1, $2, $3, $4) returning id_",
+                &[
+                    &event.aggregate,
+                    &event.aggregate_id,
+                    &event.event_type.name(),
+                    &event.payload,
+                ],
+            )
+            .await?;
+        Ok(row.try_get(0)?)
+    })
+    .await
+}
+
+/// Leases up to `limit` pending events that are due, oldest first, skipping
+/// the ones another dispatcher is claiming: they are not due again before
+/// `lease` is over. One statement, nothing stays locked while the events are
+/// delivered; mark each of them, or `release_with` the ones not tried.
+pub async fn claim_with<C: GenericClient + Sync>(
+    client: &C,
+    limit: u32,
+    lease: Duration,
+) -> AppResult<Vec<OutboxEvent>> {
+    timed(TABLE, "claim", async {
+        let sql = format!(
+            "update {TABLE} set next_attempt_at = localtimestamp + $2 * interval '1 second' \
+             where id_ in (select id_ from {TABLE} \
+             where delivered_at is null and dead_at is null and next_attempt_at <= localtimestamp \
+             order by id_ limit $1 for update skip locked) \
+             returning {COLUMNS}"
+        );
+        let mut events = client
+            .query(&sql, &[&(limit as i64), &lease.as_secs_f64()])
+            .await?
+            .iter()
+            .map(from_row)
+            .collect::<AppResult<Vec<_>>>()?;
+        events.sort_by_key(|event| event.id);
+        Ok(events)
+    })
+    .await
+}
+
+/// Ends the lease of claimed events that were not tried, they are due at once.
+pub async fn release_with<C: GenericClient + Sync>(client: &C, ids: &[i64]) -> AppResult<u64> {
+    timed(TABLE, "release", async {
+        let count = client
+            .execute(
+                "update outbox set next_attempt_at = localtimestamp \
+                 where id_ = any($1) and delivered_at is null and dead_at is null",
+                &[&ids],
+            )
+            .await?;
+        Ok(count)
+    })
+    .await
+}
+
+/// Like `mark_failed_with`, a no-op on an event another dispatcher settled
+/// after this one's lease ran out.
+pub async fn mark_delivered_with<C: GenericClient + Sync>(client: &C, id: i64) -> AppResult<()> {
+    timed(TABLE, "mark_delivered", async {
+        client
+            .execute(
+                "update outbox set delivered_at = localtimestamp \
+                 where id_ = $1 and delivered_at is null and dead_at is null",
+                &[&id],
+            )
+            .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Counts a failed attempt: the event is due again after `retry_in`, or dead
+/// when there is none.
+pub async fn mark_failed_with<C: GenericClient + Sync>(
+    client: &C,
+    id: i64,
+    error: &str,
+    retry_in: Option<Duration>,
+) -> AppResult<()> {
+    timed(TABLE, "mark_failed", async {
+        match retry_in {
+            Some(retry_in) => {
+                client
+                    .execute(
+                        "update outbox set attempts = attempts + 1, last_error = $2, \
+                         next_attempt_at = localtimestamp + $3 * interval '1 second' \
+                         where id_ = $1 and delivered_at is null and dead_at is null",
+                        &[&id, &error, &retry_in.as_secs_f64()],
+                    )
+                    .await?
+            }
+            None => {
+                client
+                    .execute(
+                        "update outbox set attempts = attempts + 1, last_error = $2, \
+                         dead_at = localtimestamp \
+                         where id_ = $1 and delivered_at is null and dead_at is null",
+                        &[&id, &error],
+                    )
+                    .await?
+            }
+        };
+        Ok(())
+    })
+    .await
+}
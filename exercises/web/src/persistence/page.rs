@@ -0,0 +1,94 @@
+use anyhow::anyhow;
+use macros::ApiSchema;
+use serde::{Deserialize, Serialize};
+use utils::error::app_error::AppResult;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// One keyset page; pass `next_cursor` back to get the rows after it.
+/// `next_cursor` is `None` on the last page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+/// Key types that can be carried inside a cursor.
+pub trait CursorKey: Sized {
+    fn to_cursor_value(&self) -> String;
+    fn from_cursor_value(val: &str) -> AppResult<Self>;
+}
+
+impl CursorKey for i64 {
+    fn to_cursor_value(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_cursor_value(val: &str) -> AppResult<Self> {
+        Ok(val.parse::<i64>()?)
+    }
+}
+
+impl CursorKey for String {
+    fn to_cursor_value(&self) -> String {
+        self.clone()
+    }
+
+    fn from_cursor_value(val: &str) -> AppResult<Self> {
+        Ok(val.to_string())
+    }
+}
+
+/// Encodes the last key of a page together with its order, so a cursor
+/// can not be replayed against the opposite order.
+pub fn encode_cursor<K: CursorKey>(last_key: &K, order: SortOrder) -> String {
+    let raw = format!("{}:{}", order_tag(order), last_key.to_cursor_value());
+    raw.bytes().map(|b| format!("{b:02x}")).collect()
+}
+
+pub fn decode_cursor<K: CursorKey>(cursor: &str, order: SortOrder) -> AppResult<K> {
+    let invalid = || anyhow!("invalid cursor: {cursor}");
+    if !cursor.len().is_multiple_of(2) || !cursor.is_ascii() {
+        return Err(invalid());
+    }
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| invalid())?;
+    let raw = String::from_utf8(bytes).map_err(|_| invalid())?;
+    let (tag, key) = raw.split_once(':').ok_or_else(invalid)?;
+    if tag != order_tag(order) {
+        return Err(anyhow!("cursor was issued for the other sort order"));
+    }
+    K::from_cursor_value(key).map_err(|_| invalid())
+}
+
+fn order_tag(order: SortOrder) -> &'static str {
+    match order {
+        SortOrder::Asc => "a",
+        SortOrder::Desc => "d",
+    }
+}
+
+/// Trims the look-ahead row fetched by the keyset queries and builds the page.
+pub fn to_page<T, K: CursorKey>(
+    mut items: Vec<T>,
+    page_size: u32,
+    order: SortOrder,
+    key: impl Fn(&T) -> &K,
+) -> Page<T> {
+    let has_more = items.len() > page_size as usize;
+    items.truncate(page_size as usize);
+    let next_cursor = match (has_more, items.last()) {
+        (true, Some(last)) => Some(encode_cursor(key(last), order)),
+        _ => None,
+    };
+    Page { items, next_cursor }
+}
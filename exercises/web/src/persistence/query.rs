@@ -0,0 +1,580 @@
+use crate::persistence::page::SortOrder;
+use anyhow::{anyhow, Context};
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::dsl::sql;
+use diesel::expression::BoxableExpression;
+use diesel::pg::Pg;
+use diesel::{sql_types, BoolExpressionMethods};
+use serde::Serialize;
+use serde_json::{Map, Value as JsonValue};
+use std::fmt::Debug;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::Row;
+use utils::error::app_error::AppResult;
+
+/// A boolean condition for a diesel query on `QS`, e.g. `into_boxed().filter(..)`.
+pub type Condition<QS> = Box<dyn BoxableExpression<QS, Pg, SqlType = sql_types::Bool>>;
+
+/// Column types a `Field` can have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Bool,
+    SmallInt,
+    Int,
+    BigInt,
+    Text,
+    Timestamp,
+    Date,
+}
+
+/// A column an entity lets callers filter, sort and project on, known by its
+/// API name; nothing else ever reaches the SQL text.
+pub trait Field: Copy + Eq + Debug + Send + Sync + 'static {
+    const ALL: &'static [Self];
+
+    /// the name in the entity's JSON
+    fn name(self) -> &'static str;
+    fn column(self) -> &'static str;
+    fn kind(self) -> Kind;
+
+    fn parse(name: &str) -> AppResult<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|field| field.name() == name)
+            .ok_or_else(|| anyhow!("unknown field {name}"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    SmallInt(i16),
+    Int(i32),
+    BigInt(i64),
+    Text(String),
+    Timestamp(NaiveDateTime),
+    Date(NaiveDate),
+}
+
+impl From<bool> for Value {
+    fn from(val: bool) -> Self {
+        Value::Bool(val)
+    }
+}
+
+impl From<i16> for Value {
+    fn from(val: i16) -> Self {
+        Value::SmallInt(val)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(val: i32) -> Self {
+        Value::Int(val)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(val: i64) -> Self {
+        Value::BigInt(val)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(val: &str) -> Self {
+        Value::Text(val.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(val: String) -> Self {
+        Value::Text(val)
+    }
+}
+
+impl From<NaiveDateTime> for Value {
+    fn from(val: NaiveDateTime) -> Self {
+        Value::Timestamp(val)
+    }
+}
+
+impl From<NaiveDate> for Value {
+    fn from(val: NaiveDate) -> Self {
+        Value::Date(val)
+    }
+}
+
+impl Value {
+    /// Reads a query string value as a value for a column of `kind`.
+    pub fn parse(kind: Kind, text: &str) -> AppResult<Value> {
+        let val = match kind {
+            Kind::Bool => text.parse().map(Value::Bool).ok(),
+            Kind::SmallInt => text.parse().map(Value::SmallInt).ok(),
+            Kind::Int => text.parse().map(Value::Int).ok(),
+            Kind::BigInt => text.parse().map(Value::BigInt).ok(),
+            Kind::Text => Some(Value::Text(text.to_string())),
+            Kind::Timestamp => text.parse().map(Value::Timestamp).ok(),
+            Kind::Date => text.parse().map(Value::Date).ok(),
+        };
+        val.ok_or_else(|| anyhow!("{text} is not a valid {kind:?}"))
+    }
+
+    /// The same value typed for a column of `kind`, e.g. an `i32` literal
+    /// compared with a `SmallInt` column. tokio-postgres needs the exact type.
+    fn coerce(self, kind: Kind) -> AppResult<Value> {
+        let int = match self {
+            Value::SmallInt(val) => Some(val as i64),
+            Value::Int(val) => Some(val as i64),
+            Value::BigInt(val) => Some(val),
+            _ => None,
+        };
+        let out_of_range = |val: i64| anyhow!("{val} is out of range for {kind:?}");
+        match (self, kind, int) {
+            (_, Kind::SmallInt, Some(val)) => Ok(Value::SmallInt(
+                i16::try_from(val).map_err(|_| out_of_range(val))?,
+            )),
+            (_, Kind::Int, Some(val)) => Ok(Value::Int(
+                i32::try_from(val).map_err(|_| out_of_range(val))?,
+            )),
+            (_, Kind::BigInt, Some(val)) => Ok(Value::BigInt(val)),
+            (val @ Value::Bool(_), Kind::Bool, _)
+            | (val @ Value::Text(_), Kind::Text, _)
+            | (val @ Value::Timestamp(_), Kind::Timestamp, _)
+            | (val @ Value::Date(_), Kind::Date, _) => Ok(val),
+            (val, kind, _) => Err(anyhow!("{val:?} can not be compared with a {kind:?}")),
+        }
+    }
+
+    fn as_param(&self) -> &(dyn ToSql + Sync) {
+        match self {
+            Value::Bool(val) => val,
+            Value::SmallInt(val) => val,
+            Value::Int(val) => val,
+            Value::BigInt(val) => val,
+            Value::Text(val) => val,
+            Value::Timestamp(val) => val,
+            Value::Date(val) => val,
+        }
+    }
+
+    /// `prefix` followed by this value as a bind parameter.
+    fn bind<QS>(&self, prefix: &str) -> Condition<QS> {
+        let prefix = sql::<sql_types::Bool>(prefix);
+        match self.clone() {
+            Value::Bool(val) => Box::new(prefix.bind::<sql_types::Bool, _>(val)),
+            Value::SmallInt(val) => Box::new(prefix.bind::<sql_types::SmallInt, _>(val)),
+            Value::Int(val) => Box::new(prefix.bind::<sql_types::Integer, _>(val)),
+            Value::BigInt(val) => Box::new(prefix.bind::<sql_types::BigInt, _>(val)),
+            Value::Text(val) => Box::new(prefix.bind::<sql_types::Text, _>(val)),
+            Value::Timestamp(val) => Box::new(prefix.bind::<sql_types::Timestamp, _>(val)),
+            Value::Date(val) => Box::new(prefix.bind::<sql_types::Date, _>(val)),
+        }
+    }
+}
+
+/// Typed filter over the fields `F` of one entity.
+///
+/// Values are checked against the field types when the filter is compiled;
+/// they are always sent as bind parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter<F> {
+    Eq(F, Value),
+    Ne(F, Value),
+    Lt(F, Value),
+    Gt(F, Value),
+    /// SQL `like` pattern, `%` and `_` are wildcards
+    Like(F, String),
+    In(F, Vec<Value>),
+    /// both ends included
+    Between(F, Value, Value),
+    IsNull(F),
+    And(Vec<Filter<F>>),
+    Or(Vec<Filter<F>>),
+}
+
+impl<F: Field> Filter<F> {
+    pub fn eq(field: F, val: impl Into<Value>) -> Self {
+        Filter::Eq(field, val.into())
+    }
+
+    pub fn ne(field: F, val: impl Into<Value>) -> Self {
+        Filter::Ne(field, val.into())
+    }
+
+    pub fn lt(field: F, val: impl Into<Value>) -> Self {
+        Filter::Lt(field, val.into())
+    }
+
+    pub fn gt(field: F, val: impl Into<Value>) -> Self {
+        Filter::Gt(field, val.into())
+    }
+
+    pub fn like(field: F, pattern: impl Into<String>) -> Self {
+        Filter::Like(field, pattern.into())
+    }
+
+    pub fn is_in<V: Into<Value>>(field: F, vals: impl IntoIterator<Item = V>) -> Self {
+        Filter::In(field, vals.into_iter().map(Into::into).collect())
+    }
+
+    pub fn between(field: F, low: impl Into<Value>, high: impl Into<Value>) -> Self {
+        Filter::Between(field, low.into(), high.into())
+    }
+
+    pub fn is_null(field: F) -> Self {
+        Filter::IsNull(field)
+    }
+
+    pub fn and(self, other: Self) -> Self {
+        match self {
+            Filter::And(mut all) => {
+                all.push(other);
+                Filter::And(all)
+            }
+            first => Filter::And(vec![first, other]),
+        }
+    }
+
+    pub fn or(self, other: Self) -> Self {
+        match self {
+            Filter::Or(mut any) => {
+                any.push(other);
+                Filter::Or(any)
+            }
+            first => Filter::Or(vec![first, other]),
+        }
+    }
+
+    /// Reads the query string form: conditions separated by `,` must all hold,
+    /// alternatives within one condition are separated by `|`. A condition is
+    /// `field:op:value` with op one of `eq ne lt gt like in between is_null`;
+    /// `in` takes `a;b;c`, `between` takes `low;high` and `is_null` no value,
+    /// e.g. `available:eq:true,name:like:a%|id:in:1;2`.
+    pub fn parse(text: &str) -> AppResult<Self> {
+        let all = text
+            .split(',')
+            .filter(|cond| !cond.is_empty())
+            .map(|cond| {
+                let any = cond
+                    .split('|')
+                    .map(Self::parse_one)
+                    .collect::<AppResult<Vec<Self>>>()?;
+                Ok(match <[Self; 1]>::try_from(any) {
+                    Ok([one]) => one,
+                    Err(any) => Filter::Or(any),
+                })
+            })
+            .collect::<AppResult<Vec<Self>>>()?;
+        Ok(match <[Self; 1]>::try_from(all) {
+            Ok([one]) => one,
+            Err(all) => Filter::And(all),
+        })
+    }
+
+    fn parse_one(text: &str) -> AppResult<Self> {
+        let mut parts = text.splitn(3, ':');
+        let (name, op, arg) = (parts.next().unwrap_or(""), parts.next(), parts.next());
+        let field = F::parse(name)?;
+        let val = |text: &str| Value::parse(field.kind(), text);
+        let vals = |text: &str| text.split(';').map(val).collect::<AppResult<Vec<_>>>();
+        let filter = match (op, arg) {
+            (Some("is_null"), None) => Filter::IsNull(field),
+            (Some("eq"), Some(arg)) => Filter::Eq(field, val(arg)?),
+            (Some("ne"), Some(arg)) => Filter::Ne(field, val(arg)?),
+            (Some("lt"), Some(arg)) => Filter::Lt(field, val(arg)?),
+            (Some("gt"), Some(arg)) => Filter::Gt(field, val(arg)?),
+            (Some("like"), Some(arg)) => Filter::Like(field, arg.to_string()),
+            (Some("in"), Some(arg)) => Filter::In(field, vals(arg)?),
+            (Some("between"), Some(arg)) => match <[Value; 2]>::try_from(vals(arg)?) {
+                Ok([low, high]) => Filter::Between(field, low, high),
+                Err(_) => return Err(anyhow!("between takes low;high in {text}")),
+            },
+            _ => return Err(anyhow!("invalid condition {text}")),
+        };
+        Ok(filter)
+    }
+
+    /// Down to comparisons, with every value typed for its column.
+    fn lower(&self) -> AppResult<Cond> {
+        let cmp = |field: F, op: &'static str, val: &Value| -> AppResult<Cond> {
+            let val = val
+                .clone()
+                .coerce(field.kind())
+                .with_context(|| format!("filter on {}", field.name()))?;
+            Ok(Cond::Cmp(field.column(), op, val))
+        };
+        Ok(match self {
+            Filter::Eq(field, val) => cmp(*field, "=", val)?,
+            Filter::Ne(field, val) => cmp(*field, "<>", val)?,
+            Filter::Lt(field, val) => cmp(*field, "<", val)?,
+            Filter::Gt(field, val) => cmp(*field, ">", val)?,
+            Filter::Like(field, pattern) => cmp(*field, "like", &Value::Text(pattern.clone()))?,
+            Filter::In(field, vals) => Cond::Or(
+                vals.iter()
+                    .map(|val| cmp(*field, "=", val))
+                    .collect::<AppResult<Vec<Cond>>>()?,
+            ),
+            Filter::Between(field, low, high) => {
+                Cond::And(vec![cmp(*field, ">=", low)?, cmp(*field, "<=", high)?])
+            }
+            Filter::IsNull(field) => Cond::IsNull(field.column()),
+            Filter::And(all) => Cond::And(all.iter().map(Self::lower).collect::<AppResult<_>>()?),
+            Filter::Or(any) => Cond::Or(any.iter().map(Self::lower).collect::<AppResult<_>>()?),
+        })
+    }
+}
+
+/// A checked filter, columns resolved and values typed.
+enum Cond {
+    Cmp(&'static str, &'static str, Value),
+    IsNull(&'static str),
+    And(Vec<Cond>),
+    Or(Vec<Cond>),
+}
+
+impl Cond {
+    /// SQL with `$n` placeholders numbered after those already in `params`.
+    fn to_sql(&self, params: &mut Vec<Value>) -> String {
+        let join = |conds: &[Cond], sep: &str, empty: &str, params: &mut Vec<Value>| {
+            if conds.is_empty() {
+                return empty.to_string();
+            }
+            let parts = conds
+                .iter()
+                .map(|cond| cond.to_sql(params))
+                .collect::<Vec<String>>();
+            format!("({})", parts.join(sep))
+        };
+        match self {
+            Cond::Cmp(column, op, val) => {
+                params.push(val.clone());
+                format!("{} {} ${}", column, op, params.len())
+            }
+            Cond::IsNull(column) => format!("{column} is null"),
+            Cond::And(all) => join(all, " and ", "true", params),
+            Cond::Or(any) => join(any, " or ", "false", params),
+        }
+    }
+
+    fn to_diesel<QS: 'static>(&self) -> Condition<QS> {
+        let fold = |conds: &[Cond], and: bool| {
+            conds
+                .iter()
+                .map(Cond::to_diesel)
+                .reduce(|left: Condition<QS>, right| match and {
+                    true => Box::new(left.and(right)),
+                    false => Box::new(left.or(right)),
+                })
+                .unwrap_or_else(|| {
+                    Box::new(sql::<sql_types::Bool>(if and { "true" } else { "false" }))
+                })
+        };
+        match self {
+            Cond::Cmp(column, op, val) => val.bind(&format!("{column} {op} ")),
+            Cond::IsNull(column) => Box::new(sql::<sql_types::Bool>(&format!("{column} is null"))),
+            Cond::And(all) => fold(all, true),
+            Cond::Or(any) => fold(any, false),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort<F> {
+    pub field: F,
+    pub order: SortOrder,
+}
+
+impl<F: Field> Sort<F> {
+    /// Reads `-created_at,name`: fields separated by `,`, a leading `-` sorts descending.
+    pub fn parse_list(text: &str) -> AppResult<Vec<Self>> {
+        text.split(',')
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                let (name, order) = match part.strip_prefix('-') {
+                    Some(name) => (name, SortOrder::Desc),
+                    None => (part, SortOrder::Asc),
+                };
+                Ok(Sort {
+                    field: F::parse(name)?,
+                    order,
+                })
+            })
+            .collect()
+    }
+}
+
+/// One page of the rows matching `filter`, ordered by `sort` and then by the
+/// key, with only `fields` when projected. No `sort` means newest key first,
+/// like `AsyncRepository::find`; no `fields` means every field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListQuery<F> {
+    pub filter: Option<Filter<F>>,
+    pub sort: Vec<Sort<F>>,
+    pub fields: Vec<F>,
+    pub page_no: u32,
+    pub page_size: u32,
+}
+
+impl<F: Field> ListQuery<F> {
+    pub fn new(page_no: u32, page_size: u32) -> Self {
+        Self {
+            filter: None,
+            sort: vec![],
+            fields: vec![],
+            page_no,
+            page_size,
+        }
+    }
+
+    /// Reads the query string forms, see `Filter::parse` and `Sort::parse_list`;
+    /// `fields` is a `,` separated list of field names.
+    pub fn parse(
+        filter: Option<&str>,
+        sort: Option<&str>,
+        fields: Option<&str>,
+        page_no: u32,
+        page_size: u32,
+    ) -> AppResult<Self> {
+        Ok(Self {
+            filter: filter.map(Filter::parse).transpose()?,
+            sort: sort.map(Sort::parse_list).transpose()?.unwrap_or_default(),
+            fields: fields
+                .map(|fields| {
+                    fields
+                        .split(',')
+                        .filter(|name| !name.is_empty())
+                        .map(F::parse)
+                        .collect::<AppResult<Vec<F>>>()
+                })
+                .transpose()?
+                .unwrap_or_default(),
+            page_no,
+            page_size,
+        })
+    }
+
+    pub fn filter(mut self, filter: Filter<F>) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn sort(mut self, field: F, order: SortOrder) -> Self {
+        self.sort.push(Sort { field, order });
+        self
+    }
+
+    pub fn select(mut self, fields: &[F]) -> Self {
+        self.fields = fields.to_vec();
+        self
+    }
+
+    /// Checks the filter values against the field types.
+    pub fn check(&self) -> AppResult<()> {
+        self.filter.as_ref().map(Filter::lower).transpose()?;
+        Ok(())
+    }
+
+    /// The projected fields, every field when none is asked for.
+    pub fn fields(&self) -> &[F] {
+        match self.fields.is_empty() {
+            true => F::ALL,
+            false => &self.fields,
+        }
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.page_no as i64 * self.page_size as i64
+    }
+
+    /// The filter with `$n` placeholders, its values pushed onto `params`.
+    pub fn where_sql(&self, params: &mut Vec<Value>) -> AppResult<Option<String>> {
+        Ok(self
+            .filter
+            .as_ref()
+            .map(Filter::lower)
+            .transpose()?
+            .map(|cond| cond.to_sql(params)))
+    }
+
+    pub fn diesel_filter<QS: 'static>(&self) -> AppResult<Option<Condition<QS>>> {
+        Ok(self
+            .filter
+            .as_ref()
+            .map(Filter::lower)
+            .transpose()?
+            .map(|cond| cond.to_diesel()))
+    }
+
+    /// The `order by` list, ending with `key_column` so pages never overlap.
+    pub fn order_sql(&self, key_column: &str) -> String {
+        let dir = |order: SortOrder| match order {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        };
+        let mut parts = self
+            .sort
+            .iter()
+            .map(|sort| format!("{} {}", sort.field.column(), dir(sort.order)))
+            .collect::<Vec<String>>();
+        if !self
+            .sort
+            .iter()
+            .any(|sort| sort.field.column() == key_column)
+        {
+            let order = match self.sort.is_empty() {
+                true => SortOrder::Desc,
+                false => SortOrder::Asc,
+            };
+            parts.push(format!("{} {}", key_column, dir(order)));
+        }
+        parts.join(", ")
+    }
+}
+
+pub fn params(vals: &[Value]) -> Vec<&(dyn ToSql + Sync)> {
+    vals.iter().map(Value::as_param).collect()
+}
+
+/// A row selected with the columns of `fields`, keyed by field name.
+pub fn project_row<F: Field>(row: &Row, fields: &[F]) -> AppResult<Map<String, JsonValue>> {
+    fields
+        .iter()
+        .map(|field| {
+            let column = field.column();
+            let val = match field.kind() {
+                Kind::Bool => serde_json::to_value(row.try_get::<_, Option<bool>>(column)?)?,
+                Kind::SmallInt => serde_json::to_value(row.try_get::<_, Option<i16>>(column)?)?,
+                Kind::Int => serde_json::to_value(row.try_get::<_, Option<i32>>(column)?)?,
+                Kind::BigInt => serde_json::to_value(row.try_get::<_, Option<i64>>(column)?)?,
+                Kind::Text => serde_json::to_value(row.try_get::<_, Option<String>>(column)?)?,
+                Kind::Timestamp => {
+                    serde_json::to_value(row.try_get::<_, Option<NaiveDateTime>>(column)?)?
+                }
+                Kind::Date => serde_json::to_value(row.try_get::<_, Option<NaiveDate>>(column)?)?,
+            };
+            Ok((field.name().to_string(), val))
+        })
+        .collect()
+}
+
+/// A loaded entity cut down to `fields`, for the sync path that can not select
+/// columns dynamically.
+pub fn project<T: Serialize, F: Field>(val: &T, fields: &[F]) -> AppResult<Map<String, JsonValue>> {
+    let JsonValue::Object(mut all) = serde_json::to_value(val)? else {
+        return Err(anyhow!(
+            "{} is not serialized as an object",
+            std::any::type_name::<T>()
+        ));
+    };
+    Ok(fields
+        .iter()
+        .map(|field| {
+            let name = field.name();
+            (
+                name.to_string(),
+                all.remove(name).unwrap_or(JsonValue::Null),
+            )
+        })
+        .collect())
+}
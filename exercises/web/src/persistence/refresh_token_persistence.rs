@@ -0,0 +1,78 @@
+use crate::persistence::db::Db;
+use crate::utils::metrics::timed;
+use std::time::Duration;
+use tokio_postgres::GenericClient;
+use utils::error::app_error::AppResult;
+
+// The revocation state of the refresh tokens of `services::auth_service`,
+// the tokens themselves are never stored. Always on the primary, a replica
+// lagging behind a revoke would let the token through.
+
+const TABLE: &str = "refresh_token";
+
+/// Expiry is kept on the database clock, like the other timestamps.
+pub async fn insert(db: &Db, jti: &str, user_id: i64, ttl: Duration) -> AppResult<()> {
+    let conn = db.async_conn().await?;
+    timed(TABLE, "insert", insert_with(&*conn, jti, user_id, ttl)).await
+}
+
+/// Revokes the token if it is live, returning whose it was. A token can be
+/// consumed once, so a stolen one used after its owner, or the other way
+/// round, is refused.
+pub async fn consume(db: &Db, jti: &str) -> AppResult<Option<i64>> {
+    let conn = db.async_conn().await?;
+    timed(TABLE, "consume", consume_with(&*conn, jti)).await
+}
+
+pub async fn revoke(db: &Db, jti: &str) -> AppResult<bool> {
+    Ok(consume(db, jti).await?.is_some())
+}
+
+/// Logs the user out everywhere.
+pub async fn revoke_all(db: &Db, user_id: i64) -> AppResult<u64> {
+    let conn = db.async_conn().await?;
+    timed(TABLE, "revoke_all", revoke_all_with(&*conn, user_id)).await
+}
+
+// ----- the same operations on a caller supplied client, e.g. `Tx::client()` -----
+
+pub async fn insert_with<C: GenericClient + Sync>(
+    client: &C,
+    jti: &str,
+    user_id: i64,
+    ttl: Duration,
+) -> AppResult<()> {
+    client
+        .execute(
+            "insert into refresh_token (jti, user_id, expires_at) \
+             values ($1, $2, localtimestamp + $3 * interval '1 second')",
+            &[&jti, &user_id, &ttl.as_secs_f64()],
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn consume_with<C: GenericClient + Sync>(
+    client: &C,
+    jti: &str,
+) -> AppResult<Option<i64>> {
+    let row = client
+        .query_opt(
+            "update refresh_token set revoked_at = localtimestamp \
+             where jti = $1 and revoked_at is null and expires_at > localtimestamp \
+             returning user_id",
+            &[&jti],
+        )
+        .await?;
+    Ok(row.map(|row| row.try_get(0)).transpose()?)
+}
+
+pub async fn revoke_all_with<C: GenericClient + Sync>(client: &C, user_id: i64) -> AppResult<u64> {
+    Ok(client
+        .execute(
+            "update refresh_token set revoked_at = localtimestamp \
+             where user_id = $1 and revoked_at is null",
+            &[&user_id],
+        )
+        .await?)
+}
@@ -0,0 +1,722 @@
+use crate::persistence::db::Db;
+use crate::persistence::page::{decode_cursor, to_page, CursorKey, Page, SortOrder};
+use crate::persistence::query::{params, project_row, Field, ListQuery, Value};
+use crate::utils::metrics::timed;
+use anyhow::anyhow;
+use serde_json::{Map, Value as JsonValue};
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use tokio::pin;
+use tokio_postgres::binary_copy::BinaryCopyInWriter;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::types::Type;
+use tokio_postgres::{GenericClient, Row};
+use tokio_stream::{Stream, StreamExt};
+use utils::error::app_error::AppResult;
+
+pub type SqlParam<'a> = &'a (dyn ToSql + Sync);
+/// Rows decoded one by one as they arrive; the stream owns its pooled
+/// connection and hands it back when dropped.
+pub type EntityStream<T> = Pin<Box<dyn Stream<Item = AppResult<T>> + Send + 'static>>;
+
+/// Shared async CRUD over one table.
+///
+/// An entity opts in by declaring its table, key column, column list and the
+/// mapping between a row and the struct; all the SQL lives in the default methods.
+/// The plain methods check a connection out of the given `Db`, reads from a
+/// replica when there is one; the `*_with` ones run on the client they are handed.
+pub trait AsyncRepository<T, Id>
+where
+    T: Send + Sync,
+    Id: ToSql + Send + Sync,
+{
+    const TABLE_NAME: &'static str;
+    const KEY_COLUMN: &'static str;
+    /// every column, key included, in the same order as `to_params`
+    const COLUMNS: &'static [&'static str];
+    /// `find` returns the newest rows first by this column
+    const ORDER_COLUMN: &'static str = Self::KEY_COLUMN;
+    /// opt-in optimistic locking: `update` only matches the row while it still
+    /// has the entity's version, bumps it, and fails with `VersionConflict` otherwise
+    const VERSION_COLUMN: Option<&'static str> = None;
+    /// opt-in audit columns stamped by the database. Inserts leave them, and the
+    /// version, to the column defaults; whatever `to_params` holds for them is ignored.
+    const CREATED_COLUMN: Option<&'static str> = None;
+    const MODIFIED_COLUMN: Option<&'static str> = None;
+    /// opt-in soft delete: `delete` stamps this nullable column instead of removing
+    /// the row, reads and updates skip stamped rows, `restore` clears the stamp
+    /// and `purge` removes the row for good.
+    const DELETED_COLUMN: Option<&'static str> = None;
+
+    fn from_row(row: &Row) -> AppResult<T>;
+    fn key(val: &T) -> &Id;
+    fn to_params(val: &T) -> Vec<SqlParam<'_>>;
+
+    fn find(
+        db: &Db,
+        page_no: u32,
+        page_size: u32,
+    ) -> impl Future<Output = AppResult<Vec<T>>> + Send {
+        async move {
+            let conn = db.async_read_conn().await?;
+            Self::find_with(&*conn, page_no, page_size).await
+        }
+    }
+
+    /// Keyset page ordered by the key column, see `persistence::page`.
+    fn find_page(
+        db: &Db,
+        cursor: Option<&str>,
+        page_size: u32,
+        order: SortOrder,
+    ) -> impl Future<Output = AppResult<Page<T>>> + Send
+    where
+        Id: CursorKey,
+    {
+        async move {
+            let conn = db.async_read_conn().await?;
+            Self::find_page_with(&*conn, cursor, page_size, order).await
+        }
+    }
+
+    /// Every row ordered by the key column, without collecting them first.
+    /// A row that fails to decode is yielded as an `Err`, the stream goes on.
+    fn stream(db: &Db, order: SortOrder) -> impl Future<Output = AppResult<EntityStream<T>>> + Send
+    where
+        Self: 'static,
+        T: 'static,
+    {
+        async move {
+            let conn = db.async_read_conn().await?;
+            let dir = match order {
+                SortOrder::Asc => "asc",
+                SortOrder::Desc => "desc",
+            };
+            let sql = format!(
+                "select {} from {}{} order by {} {}",
+                Self::COLUMNS.join(", "),
+                Self::TABLE_NAME,
+                where_live::<T, Id, Self>(None),
+                Self::KEY_COLUMN,
+                dir
+            );
+            let rows = timed(
+                Self::TABLE_NAME,
+                "stream",
+                conn.query_raw(&sql, Vec::<i64>::new()),
+            )
+            .await?;
+            let stream = rows.map(move |row| {
+                // keep the connection checked out for as long as rows are read
+                let _conn = &conn;
+                Self::from_row(&row?)
+            });
+            Ok(Box::pin(stream) as EntityStream<T>)
+        }
+    }
+
+    fn find_by_id(db: &Db, _id: &Id) -> impl Future<Output = AppResult<Option<T>>> + Send {
+        async move {
+            let conn = db.async_read_conn().await?;
+            Self::find_by_id_with(&*conn, _id).await
+        }
+    }
+
+    fn insert(db: &Db, val: &T) -> impl Future<Output = AppResult<u64>> + Send {
+        async move {
+            let conn = db.async_conn().await?;
+            Self::insert_with(&*conn, val).await
+        }
+    }
+
+    fn insert_batch(db: &Db, vals: &[T]) -> impl Future<Output = AppResult<u64>> + Send {
+        async move {
+            let tx = db.begin().await?;
+            let count = Self::insert_batch_with(tx.client(), vals).await?;
+            tx.commit().await?;
+            Ok(count)
+        }
+    }
+
+    /// Bulk load through `COPY ... FROM STDIN BINARY` in one transaction,
+    /// returns the number of rows written. Nothing is kept when a row fails.
+    fn copy_in(db: &Db, vals: &[T]) -> impl Future<Output = AppResult<u64>> + Send {
+        async move {
+            let tx = db.begin().await?;
+            let count = Self::copy_in_with(tx.client(), vals).await?;
+            tx.commit().await?;
+            Ok(count)
+        }
+    }
+
+    fn update(db: &Db, val: &T) -> impl Future<Output = AppResult<u64>> + Send {
+        async move {
+            let conn = db.async_conn().await?;
+            Self::update_with(&*conn, val).await
+        }
+    }
+
+    fn delete(db: &Db, _id: &Id) -> impl Future<Output = AppResult<u64>> + Send {
+        async move {
+            let conn = db.async_conn().await?;
+            Self::delete_with(&*conn, _id).await
+        }
+    }
+
+    fn restore(db: &Db, _id: &Id) -> impl Future<Output = AppResult<u64>> + Send {
+        async move {
+            let conn = db.async_conn().await?;
+            Self::restore_with(&*conn, _id).await
+        }
+    }
+
+    fn purge(db: &Db, _id: &Id) -> impl Future<Output = AppResult<u64>> + Send {
+        async move {
+            let conn = db.async_conn().await?;
+            Self::purge_with(&*conn, _id).await
+        }
+    }
+
+    // ----- the same operations on a caller supplied client, e.g. `Tx::client()` -----
+
+    fn find_with<C: GenericClient + Sync>(
+        client: &C,
+        page_no: u32,
+        page_size: u32,
+    ) -> impl Future<Output = AppResult<Vec<T>>> + Send {
+        timed(Self::TABLE_NAME, "find", async move {
+            let offset_val = page_no as i64 * page_size as i64;
+            let page_size = page_size as i64;
+            let sql = format!(
+                "select {} from {}{} order by {} desc limit $2 offset $1",
+                Self::COLUMNS.join(", "),
+                Self::TABLE_NAME,
+                where_live::<T, Id, Self>(None),
+                Self::ORDER_COLUMN
+            );
+            let rows = client.query_raw(&sql, [offset_val, page_size]).await?;
+            pin!(rows);
+            let mut result: Vec<T> = vec![];
+            while let Some(row) = rows.next().await {
+                result.push(Self::from_row(&row?)?);
+            }
+            Ok(result)
+        })
+    }
+
+    fn find_page_with<C: GenericClient + Sync>(
+        client: &C,
+        cursor: Option<&str>,
+        page_size: u32,
+        order: SortOrder,
+    ) -> impl Future<Output = AppResult<Page<T>>> + Send
+    where
+        Id: CursorKey,
+    {
+        timed(Self::TABLE_NAME, "find_page", async move {
+            let (cmp, dir) = match order {
+                SortOrder::Asc => (">", "asc"),
+                SortOrder::Desc => ("<", "desc"),
+            };
+            let limit = page_size as i64 + 1;
+            let after = cursor
+                .map(|cursor| decode_cursor::<Id>(cursor, order))
+                .transpose()?;
+            let cols = Self::COLUMNS.join(", ");
+            let rows = match &after {
+                Some(after) => {
+                    let sql = format!(
+                        "select {} from {}{} order by {} {} limit $2",
+                        cols,
+                        Self::TABLE_NAME,
+                        where_live::<T, Id, Self>(Some(format!("{} {} $1", Self::KEY_COLUMN, cmp))),
+                        Self::KEY_COLUMN,
+                        dir
+                    );
+                    client.query(&sql, &[after, &limit]).await?
+                }
+                None => {
+                    let sql = format!(
+                        "select {} from {}{} order by {} {} limit $1",
+                        cols,
+                        Self::TABLE_NAME,
+                        where_live::<T, Id, Self>(None),
+                        Self::KEY_COLUMN,
+                        dir
+                    );
+                    client.query(&sql, &[&limit]).await?
+                }
+            };
+            let items = rows
+                .iter()
+                .map(Self::from_row)
+                .collect::<AppResult<Vec<T>>>()?;
+            Ok(to_page(items, page_size, order, Self::key))
+        })
+    }
+
+    fn find_by_id_with<C: GenericClient + Sync>(
+        client: &C,
+        _id: &Id,
+    ) -> impl Future<Output = AppResult<Option<T>>> + Send {
+        timed(Self::TABLE_NAME, "find_by_id", async move {
+            let sql = format!(
+                "select {} from {}{}",
+                Self::COLUMNS.join(", "),
+                Self::TABLE_NAME,
+                where_live::<T, Id, Self>(Some(format!("{} = $1", Self::KEY_COLUMN)))
+            );
+            match client.query_opt(&sql, &[_id]).await? {
+                Some(row) => Ok(Some(Self::from_row(&row)?)),
+                None => Ok(None),
+            }
+        })
+    }
+
+    /// Whether the key is taken, by a soft deleted row too.
+    fn exists_with<C: GenericClient + Sync>(
+        client: &C,
+        _id: &Id,
+    ) -> impl Future<Output = AppResult<bool>> + Send {
+        timed(Self::TABLE_NAME, "exists", async move {
+            let sql = format!(
+                "select exists(select 1 from {} where {} = $1)",
+                Self::TABLE_NAME,
+                Self::KEY_COLUMN
+            );
+            Ok(client.query_one(&sql, &[_id]).await?.try_get(0)?)
+        })
+    }
+
+    fn insert_with<C: GenericClient + Sync>(
+        client: &C,
+        val: &T,
+    ) -> impl Future<Output = AppResult<u64>> + Send {
+        timed(Self::TABLE_NAME, "insert", async move {
+            let (sql, idx) = insert_sql::<T, Id, Self>();
+            Ok(client
+                .execute(&sql, &pick(&Self::to_params(val), &idx))
+                .await?)
+        })
+    }
+
+    /// Does not open a transaction itself, run it on a `Tx` to make the batch atomic.
+    fn insert_batch_with<C: GenericClient + Sync>(
+        client: &C,
+        vals: &[T],
+    ) -> impl Future<Output = AppResult<u64>> + Send {
+        timed(Self::TABLE_NAME, "insert_batch", async move {
+            let (sql, idx) = insert_sql::<T, Id, Self>();
+            let stmt = client.prepare(&sql).await?;
+            let mut count = 0;
+            for val in vals {
+                count += client
+                    .execute(&stmt, &pick(&Self::to_params(val), &idx))
+                    .await?;
+            }
+            Ok(count)
+        })
+    }
+
+    /// Fails with a `CopyRowError` naming the first row that could not be
+    /// written; the COPY is aborted, run it on a `Tx` to discard earlier rows too.
+    fn copy_in_with<C: GenericClient + Sync>(
+        client: &C,
+        vals: &[T],
+    ) -> impl Future<Output = AppResult<u64>> + Send {
+        timed(Self::TABLE_NAME, "copy_in", async move {
+            let idx = insert_columns::<T, Id, Self>();
+            let cols = idx
+                .iter()
+                .map(|i| Self::COLUMNS[*i])
+                .collect::<Vec<&str>>()
+                .join(", ");
+            // let the server tell the binary types of the columns
+            let probe = client
+                .prepare(&format!(
+                    "select {} from {} limit 0",
+                    cols,
+                    Self::TABLE_NAME
+                ))
+                .await?;
+            let types = probe
+                .columns()
+                .iter()
+                .map(|col| col.type_().clone())
+                .collect::<Vec<Type>>();
+            let sql = format!("copy {} ({}) from stdin binary", Self::TABLE_NAME, cols);
+            let sink = client.client().copy_in(&sql).await?;
+            let writer = BinaryCopyInWriter::new(sink, &types);
+            pin!(writer);
+            for (row, val) in vals.iter().enumerate() {
+                writer
+                    .as_mut()
+                    .write(&pick(&Self::to_params(val), &idx))
+                    .await
+                    .map_err(|err| CopyRowError::new(Self::TABLE_NAME, row, err))?;
+            }
+            writer
+                .finish()
+                .await
+                .map_err(|err| CopyRowError::from_copy_error(Self::TABLE_NAME, err))
+                .map_err(anyhow::Error::from)
+        })
+    }
+
+    /// Returns 0 when there is no such row, or it is soft deleted.
+    fn update_with<C: GenericClient + Sync>(
+        client: &C,
+        val: &T,
+    ) -> impl Future<Output = AppResult<u64>> + Send {
+        timed(Self::TABLE_NAME, "update", async move {
+            let (sql, idx) = update_sql::<T, Id, Self>()?;
+            let count = client
+                .execute(&sql, &pick(&Self::to_params(val), &idx))
+                .await?;
+            if count == 0 && Self::VERSION_COLUMN.is_some() {
+                let exists = format!(
+                    "select 1 from {}{}",
+                    Self::TABLE_NAME,
+                    where_live::<T, Id, Self>(Some(format!("{} = $1", Self::KEY_COLUMN)))
+                );
+                let key = Self::key(val);
+                if client.query_opt(&exists, &[key]).await?.is_some() {
+                    return Err(VersionConflict::new(Self::TABLE_NAME, key).into());
+                }
+            }
+            Ok(count)
+        })
+    }
+
+    /// A soft delete with `DELETED_COLUMN`, returns 0 when the row is already deleted.
+    fn delete_with<C: GenericClient + Sync>(
+        client: &C,
+        _id: &Id,
+    ) -> impl Future<Output = AppResult<u64>> + Send {
+        timed(Self::TABLE_NAME, "delete", async move {
+            let Some(deleted) = Self::DELETED_COLUMN else {
+                return Self::purge_with(client, _id).await;
+            };
+            let sql = format!(
+                "update {} set {} = localtimestamp where {} = $1 and {} is null",
+                Self::TABLE_NAME,
+                deleted,
+                Self::KEY_COLUMN,
+                deleted
+            );
+            Ok(client.execute(&sql, &[_id]).await?)
+        })
+    }
+
+    /// Brings back a soft deleted row, returns 0 when it is not deleted.
+    fn restore_with<C: GenericClient + Sync>(
+        client: &C,
+        _id: &Id,
+    ) -> impl Future<Output = AppResult<u64>> + Send {
+        timed(Self::TABLE_NAME, "restore", async move {
+            let deleted = Self::DELETED_COLUMN
+                .ok_or_else(|| anyhow!("{} has no soft delete", Self::TABLE_NAME))?;
+            let sql = format!(
+                "update {} set {} = null where {} = $1 and {} is not null",
+                Self::TABLE_NAME,
+                deleted,
+                Self::KEY_COLUMN,
+                deleted
+            );
+            Ok(client.execute(&sql, &[_id]).await?)
+        })
+    }
+
+    /// Removes the row whether soft deleted or not.
+    fn purge_with<C: GenericClient + Sync>(
+        client: &C,
+        _id: &Id,
+    ) -> impl Future<Output = AppResult<u64>> + Send {
+        timed(Self::TABLE_NAME, "purge", async move {
+            let sql = format!(
+                "delete from {} where {} = $1",
+                Self::TABLE_NAME,
+                Self::KEY_COLUMN
+            );
+            Ok(client.execute(&sql, &[_id]).await?)
+        })
+    }
+}
+
+/// Opt-in listing by a `ListQuery` over the fields an entity exposes.
+pub trait QueryRepository<T, Id>: AsyncRepository<T, Id>
+where
+    T: Send + Sync,
+    Id: ToSql + Send + Sync,
+{
+    type Field: Field;
+
+    fn find_by(
+        db: &Db,
+        query: &ListQuery<Self::Field>,
+    ) -> impl Future<Output = AppResult<Vec<T>>> + Send {
+        async move {
+            let conn = db.async_read_conn().await?;
+            Self::find_by_with(&*conn, query).await
+        }
+    }
+
+    /// Only the fields the query projects, keyed by field name.
+    fn select(
+        db: &Db,
+        query: &ListQuery<Self::Field>,
+    ) -> impl Future<Output = AppResult<Vec<Map<String, JsonValue>>>> + Send {
+        async move {
+            let conn = db.async_read_conn().await?;
+            Self::select_with(&*conn, query).await
+        }
+    }
+
+    fn find_by_with<C: GenericClient + Sync>(
+        client: &C,
+        query: &ListQuery<Self::Field>,
+    ) -> impl Future<Output = AppResult<Vec<T>>> + Send {
+        timed(Self::TABLE_NAME, "find_by", async move {
+            let (sql, vals) = list_sql::<T, Id, Self>(query, &Self::COLUMNS.join(", "))?;
+            let rows = client.query(&sql, &params(&vals)).await?;
+            rows.iter().map(Self::from_row).collect()
+        })
+    }
+
+    fn select_with<C: GenericClient + Sync>(
+        client: &C,
+        query: &ListQuery<Self::Field>,
+    ) -> impl Future<Output = AppResult<Vec<Map<String, JsonValue>>>> + Send {
+        timed(Self::TABLE_NAME, "select", async move {
+            let fields = query.fields();
+            let columns = fields
+                .iter()
+                .map(|field| field.column())
+                .collect::<Vec<&str>>()
+                .join(", ");
+            let (sql, vals) = list_sql::<T, Id, Self>(query, &columns)?;
+            let rows = client.query(&sql, &params(&vals)).await?;
+            rows.iter().map(|row| project_row(row, fields)).collect()
+        })
+    }
+}
+
+/// A bulk load that stopped at `row`, the index into the slice given to `copy_in`.
+/// `row` is `None` when the server rejected the data without naming a line.
+#[derive(Debug)]
+pub struct CopyRowError {
+    pub table: &'static str,
+    pub row: Option<usize>,
+    source: tokio_postgres::Error,
+}
+
+impl CopyRowError {
+    fn new(table: &'static str, row: usize, source: tokio_postgres::Error) -> Self {
+        Self {
+            table,
+            row: Some(row),
+            source,
+        }
+    }
+
+    /// The server reports the failing line as `COPY <table>, line <n>` (1-based).
+    fn from_copy_error(table: &'static str, source: tokio_postgres::Error) -> Self {
+        let row = source
+            .as_db_error()
+            .and_then(|db_err| db_err.where_())
+            .and_then(|where_| where_.split("line ").nth(1))
+            .and_then(|line| {
+                line.chars()
+                    .take_while(|c| c.is_ascii_digit())
+                    .collect::<String>()
+                    .parse::<usize>()
+                    .ok()
+            })
+            .map(|line| line.saturating_sub(1));
+        Self { table, row, source }
+    }
+}
+
+impl Display for CopyRowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.row {
+            Some(row) => write!(
+                f,
+                "copy into {} failed at row {}: {}",
+                self.table, row, self.source
+            ),
+            None => write!(f, "copy into {} failed: {}", self.table, self.source),
+        }
+    }
+}
+
+impl std::error::Error for CopyRowError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// An update made with a version the row no longer has, i.e. someone else
+/// updated it since the entity was read.
+#[derive(Debug)]
+pub struct VersionConflict {
+    pub table: &'static str,
+    pub key: String,
+}
+
+impl VersionConflict {
+    pub fn new(table: &'static str, key: &dyn std::fmt::Debug) -> Self {
+        Self {
+            table,
+            key: format!("{key:?}"),
+        }
+    }
+}
+
+impl Display for VersionConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} was changed by someone else, reload and retry",
+            self.table, self.key
+        )
+    }
+}
+
+impl std::error::Error for VersionConflict {}
+
+fn is_managed<T, Id, R>(col: &str) -> bool
+where
+    T: Send + Sync,
+    Id: ToSql + Send + Sync,
+    R: AsyncRepository<T, Id> + ?Sized,
+{
+    [
+        R::VERSION_COLUMN,
+        R::CREATED_COLUMN,
+        R::MODIFIED_COLUMN,
+        R::DELETED_COLUMN,
+    ]
+    .contains(&Some(col))
+}
+
+/// ` where <cond>` that also leaves out soft deleted rows, empty when there is nothing to filter.
+fn where_live<T, Id, R>(cond: Option<String>) -> String
+where
+    T: Send + Sync,
+    Id: ToSql + Send + Sync,
+    R: AsyncRepository<T, Id> + ?Sized,
+{
+    let conds = cond
+        .into_iter()
+        .chain(R::DELETED_COLUMN.map(|deleted| format!("{deleted} is null")))
+        .collect::<Vec<String>>();
+    if conds.is_empty() {
+        return String::new();
+    }
+    format!(" where {}", conds.join(" and "))
+}
+
+/// Indexes into `COLUMNS` of the columns an insert writes.
+fn insert_columns<T, Id, R>() -> Vec<usize>
+where
+    T: Send + Sync,
+    Id: ToSql + Send + Sync,
+    R: AsyncRepository<T, Id> + ?Sized,
+{
+    (0..R::COLUMNS.len())
+        .filter(|i| !is_managed::<T, Id, R>(R::COLUMNS[*i]))
+        .collect()
+}
+
+fn list_sql<T, Id, R>(query: &ListQuery<R::Field>, columns: &str) -> AppResult<(String, Vec<Value>)>
+where
+    T: Send + Sync,
+    Id: ToSql + Send + Sync,
+    R: QueryRepository<T, Id> + ?Sized,
+{
+    let mut vals = vec![];
+    let cond = query.where_sql(&mut vals)?;
+    let sql = format!(
+        "select {} from {}{} order by {} limit {} offset {}",
+        columns,
+        R::TABLE_NAME,
+        where_live::<T, Id, R>(cond),
+        query.order_sql(R::KEY_COLUMN),
+        query.page_size,
+        query.offset()
+    );
+    Ok((sql, vals))
+}
+
+fn pick<'a>(params: &[SqlParam<'a>], idx: &[usize]) -> Vec<SqlParam<'a>> {
+    idx.iter().map(|i| params[*i]).collect()
+}
+
+/// The statement and the indexes into `to_params` of its `$n` parameters.
+fn insert_sql<T, Id, R>() -> (String, Vec<usize>)
+where
+    T: Send + Sync,
+    Id: ToSql + Send + Sync,
+    R: AsyncRepository<T, Id> + ?Sized,
+{
+    let idx = insert_columns::<T, Id, R>();
+    let cols = idx.iter().map(|i| R::COLUMNS[*i]).collect::<Vec<&str>>();
+    let params = (1..=idx.len())
+        .map(|n| format!("${n}"))
+        .collect::<Vec<String>>();
+    let sql = format!(
+        "insert into {} ({}) values ({})",
+        R::TABLE_NAME,
+        cols.join(", "),
+        params.join(", ")
+    );
+    (sql, idx)
+}
+
+/// Like `insert_sql`; stamps the modified column, checks and bumps the version
+/// and leaves soft deleted rows alone.
+fn update_sql<T, Id, R>() -> AppResult<(String, Vec<usize>)>
+where
+    T: Send + Sync,
+    Id: ToSql + Send + Sync,
+    R: AsyncRepository<T, Id> + ?Sized,
+{
+    let position = |col: &str| {
+        R::COLUMNS
+            .iter()
+            .position(|c| *c == col)
+            .ok_or_else(|| anyhow!("{} is not a column of {}", col, R::TABLE_NAME))
+    };
+    let key_idx = position(R::KEY_COLUMN)?;
+    let mut idx = insert_columns::<T, Id, R>()
+        .into_iter()
+        .filter(|i| *i != key_idx)
+        .collect::<Vec<usize>>();
+    let mut sets = idx
+        .iter()
+        .enumerate()
+        .map(|(n, i)| format!("{} = ${}", R::COLUMNS[*i], n + 1))
+        .collect::<Vec<String>>();
+    if let Some(modified) = R::MODIFIED_COLUMN {
+        sets.push(format!("{modified} = localtimestamp"));
+    }
+    idx.push(key_idx);
+    let mut filter = format!("{} = ${}", R::KEY_COLUMN, idx.len());
+    if let Some(version) = R::VERSION_COLUMN {
+        sets.push(format!("{version} = {version} + 1"));
+        idx.push(position(version)?);
+        filter.push_str(&format!(" and {} = ${}", version, idx.len()));
+    }
+    if let Some(deleted) = R::DELETED_COLUMN {
+        filter.push_str(&format!(" and {deleted} is null"));
+    }
+    let sql = format!(
+        "update {} set {} where {}",
+        R::TABLE_NAME,
+        sets.join(", "),
+        filter
+    );
+    Ok((sql, idx))
+}
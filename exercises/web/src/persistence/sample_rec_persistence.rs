@@ -1,29 +1,151 @@
-use crate::models::sample_rec::sample_recs::{dsl::sample_recs, id};
+use crate::models::sample_rec::sample_recs::{
+    available, deleted_at, dsl::sample_recs, id, name, version,
+};
 use crate::models::sample_rec::SampleRecord;
-use crate::persistence::common::get_connection;
+use crate::persistence::db::Db;
+use crate::persistence::page::{decode_cursor, to_page, Page, SortOrder};
+use crate::persistence::query::{project, ListQuery};
+use crate::persistence::repository::{AsyncRepository, VersionConflict};
+use crate::persistence::sample_rec_persistence_async::{SampleRecordField, SampleRecordRepository};
 use anyhow::anyhow;
-use diesel::dsl::insert_into;
+use chrono::NaiveDateTime;
+use diesel::dsl::{insert_into, now, sql};
 use diesel::result::Error;
+use diesel::sql_types::Text;
 use diesel::OptionalExtension;
-use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl};
+use diesel::{
+    Connection, ExpressionMethods, NullableExpressionMethods, PgConnection, QueryDsl, RunQueryDsl,
+};
+use serde_json::{Map, Value};
 use utils::error::app_error::AppResult;
 
-pub fn find(page_no: u32, page_size: u32) -> AppResult<Vec<SampleRecord>> {
-    let mut conn = get_connection()?;
-    let offset_val = page_no * page_size;
+pub fn find(db: &Db, page_no: u32, page_size: u32) -> AppResult<Vec<SampleRecord>> {
+    let mut conn = db.read_conn()?;
+    find_with(&mut conn, page_no, page_size)
+}
+pub fn find_page(
+    db: &Db,
+    cursor: Option<&str>,
+    page_size: u32,
+    order: SortOrder,
+) -> AppResult<Page<SampleRecord>> {
+    let mut conn = db.read_conn()?;
+    find_page_with(&mut conn, cursor, page_size, order)
+}
+/// See `persistence::query`.
+pub fn find_by(db: &Db, query: &ListQuery<SampleRecordField>) -> AppResult<Vec<SampleRecord>> {
+    let mut conn = db.read_conn()?;
+    find_by_with(&mut conn, query)
+}
+pub fn select(db: &Db, query: &ListQuery<SampleRecordField>) -> AppResult<Vec<Map<String, Value>>> {
+    let mut conn = db.read_conn()?;
+    select_with(&mut conn, query)
+}
+pub fn find_by_id(db: &Db, _id: i64) -> AppResult<Option<SampleRecord>> {
+    let mut conn = db.read_conn()?;
+    find_by_id_with(&mut conn, _id)
+}
+pub fn insert(db: &Db, val: &SampleRecord) -> AppResult<()> {
+    let mut conn = db.conn()?;
+    insert_with(&mut conn, val)
+}
+pub fn insert_batch(db: &Db, vals: &[SampleRecord]) -> AppResult<()> {
+    let mut conn = db.conn()?;
+    conn.transaction::<(), Error, _>(|connection| {
+        insert_into(sample_recs).values(vals).execute(connection)?;
+        Ok(())
+    })?;
+    Ok(())
+}
+pub fn update(db: &Db, val: &SampleRecord) -> AppResult<()> {
+    let mut conn = db.conn()?;
+    update_with(&mut conn, val)
+}
+pub fn delete(db: &Db, _id: i64) -> AppResult<()> {
+    let mut conn = db.conn()?;
+    delete_with(&mut conn, _id)
+}
+pub fn restore(db: &Db, _id: i64) -> AppResult<()> {
+    let mut conn = db.conn()?;
+    restore_with(&mut conn, _id)
+}
+pub fn purge(db: &Db, _id: i64) -> AppResult<()> {
+    let mut conn = db.conn()?;
+    purge_with(&mut conn, _id)
+}
+
+// ----- the same operations on a caller supplied connection, e.g. inside `tx::with_tx` -----
+
+pub fn find_with(
+    conn: &mut PgConnection,
+    page_no: u32,
+    page_size: u32,
+) -> AppResult<Vec<SampleRecord>> {
+    let offset_val = page_no as i64 * page_size as i64;
     let rs = sample_recs
+        .filter(deleted_at.is_null())
         .order(id.desc())
-        .offset(offset_val as i64)
+        .offset(offset_val)
         .limit(page_size as i64)
-        .load::<SampleRecord>(&mut conn)?;
+        .load::<SampleRecord>(conn)?;
 
     Ok(rs)
 }
-pub fn find_by_id(_id: i64) -> AppResult<Option<SampleRecord>> {
-    let mut conn = get_connection()?;
+pub fn find_page_with(
+    conn: &mut PgConnection,
+    cursor: Option<&str>,
+    page_size: u32,
+    order: SortOrder,
+) -> AppResult<Page<SampleRecord>> {
+    let mut query = sample_recs.filter(deleted_at.is_null()).into_boxed();
+    if let Some(cursor) = cursor {
+        let after = decode_cursor::<i64>(cursor, order)?;
+        query = match order {
+            SortOrder::Asc => query.filter(id.gt(after)),
+            SortOrder::Desc => query.filter(id.lt(after)),
+        };
+    }
+    query = match order {
+        SortOrder::Asc => query.order(id.asc()),
+        SortOrder::Desc => query.order(id.desc()),
+    };
+    let rs = query
+        .limit(page_size as i64 + 1)
+        .load::<SampleRecord>(conn)?;
+    Ok(to_page(rs, page_size, order, |rec| rec.id()))
+}
+pub fn find_by_with(
+    conn: &mut PgConnection,
+    query: &ListQuery<SampleRecordField>,
+) -> AppResult<Vec<SampleRecord>> {
+    let mut rs = sample_recs.filter(deleted_at.is_null()).into_boxed();
+    if let Some(filter) = query.diesel_filter()? {
+        rs = rs.filter(filter);
+    }
+    let rs = rs
+        .order(sql::<Text>(
+            &query.order_sql(SampleRecordRepository::KEY_COLUMN),
+        ))
+        .offset(query.offset())
+        .limit(query.page_size as i64)
+        .load::<SampleRecord>(conn)?;
+    Ok(rs)
+}
+/// Loads whole rows and cuts them down, diesel selects a fixed column list.
+pub fn select_with(
+    conn: &mut PgConnection,
+    query: &ListQuery<SampleRecordField>,
+) -> AppResult<Vec<Map<String, Value>>> {
+    find_by_with(conn, query)?
+        .iter()
+        .map(|rec| project(rec, query.fields()))
+        .collect()
+}
+pub fn find_by_id_with(conn: &mut PgConnection, _id: i64) -> AppResult<Option<SampleRecord>> {
     let rs = sample_recs
         .filter(id.eq(_id))
-        .first::<SampleRecord>(&mut conn)
+        .filter(deleted_at.is_null())
+        .first::<SampleRecord>(conn)
         .optional();
     match rs {
         Ok(Some(rs)) => Ok(Some(rs)),  // found
@@ -31,29 +153,51 @@ pub fn find_by_id(_id: i64) -> AppResult<Option<SampleRecord>> {
         Err(err) => Err(anyhow!(err)), // system error
     }
 }
-pub fn insert(val: &SampleRecord) -> AppResult<()> {
-    let mut conn = get_connection()?;
-    insert_into(sample_recs).values(val).execute(&mut conn)?;
+pub fn insert_with(conn: &mut PgConnection, val: &SampleRecord) -> AppResult<()> {
+    insert_into(sample_recs).values(val).execute(conn)?;
     Ok(())
 }
-pub fn insert_batch(vals: &[SampleRecord]) -> AppResult<()> {
-    let mut conn = get_connection()?;
-    conn.transaction::<(), Error, _>(|connection| {
-        insert_into(sample_recs).values(vals).execute(connection)?;
-        Ok(())
-    })?;
-    let trans = ();
+pub fn insert_batch_with(conn: &mut PgConnection, vals: &[SampleRecord]) -> AppResult<()> {
+    insert_into(sample_recs).values(vals).execute(conn)?;
+    Ok(())
+}
+/// Checks and bumps `version`, see `AsyncRepository::VERSION_COLUMN`.
+pub fn update_with(conn: &mut PgConnection, val: &SampleRecord) -> AppResult<()> {
+    let rs = diesel::update(
+        sample_recs
+            .filter(id.eq(val.id()))
+            .filter(version.eq(val.version()))
+            .filter(deleted_at.is_null()),
+    )
+    .set((
+        name.eq(val.name()),
+        available.eq(val.available()),
+        version.eq(version + 1),
+    ))
+    .execute(conn)?;
+    if rs == 0 && find_by_id_with(conn, *val.id())?.is_some() {
+        return Err(VersionConflict::new(SampleRecordRepository::TABLE_NAME, val.id()).into());
+    }
+    Ok(())
+}
+/// A soft delete, see `AsyncRepository::DELETED_COLUMN`.
+pub fn delete_with(conn: &mut PgConnection, _id: i64) -> AppResult<()> {
+    diesel::update(sample_recs.filter(id.eq(_id)).filter(deleted_at.is_null()))
+        .set(deleted_at.eq(now.nullable()))
+        .execute(conn)?;
     Ok(())
 }
-pub fn update(val: &SampleRecord) -> AppResult<()> {
-    let mut conn = get_connection()?;
-    let upd = diesel::update(sample_recs.filter(id.eq(val.id())))
-        .set(val)
-        .execute(&mut conn)?;
+pub fn restore_with(conn: &mut PgConnection, _id: i64) -> AppResult<()> {
+    diesel::update(
+        sample_recs
+            .filter(id.eq(_id))
+            .filter(deleted_at.is_not_null()),
+    )
+    .set(deleted_at.eq(None::<NaiveDateTime>))
+    .execute(conn)?;
     Ok(())
 }
-pub fn delete(_id: i64) -> AppResult<()> {
-    let mut conn = get_connection()?;
-    let rs = diesel::delete(sample_recs.filter(id.eq(_id))).execute(&mut conn)?;
+pub fn purge_with(conn: &mut PgConnection, _id: i64) -> AppResult<()> {
+    diesel::delete(sample_recs.filter(id.eq(_id))).execute(conn)?;
     Ok(())
 }
@@ -1,96 +1,170 @@
 use crate::models::sample_rec::SampleRecord;
-use crate::persistence::common::get_async_connection;
-use chrono::NaiveDateTime;
-use tokio::pin;
-use tokio_postgres::types::ToSql;
-use tokio_stream::StreamExt;
+use crate::persistence::db::Db;
+use crate::persistence::page::{Page, SortOrder};
+use crate::persistence::query::{Field, Kind, ListQuery};
+use crate::persistence::repository::{AsyncRepository, QueryRepository, SqlParam};
+use serde_json::{Map, Value};
+use tokio_postgres::Row;
+use tokio_stream::Stream;
 use utils::error::app_error::AppResult;
 
-pub async fn find(page_no: u32, page_size: u32) -> AppResult<Vec<SampleRecord>> {
-    let conn = get_async_connection().await?;
-    let mut result: Vec<SampleRecord> = vec![];
+pub struct SampleRecordRepository;
 
-    let offset_val = (page_no * page_size) as i64;
-    let page_size = page_size as i64;
-    let sql = "select id_, name_, available, created_at from test_rec order by created_at desc limit $2 offset $1";
-    let rows = conn.query_raw(sql, &[offset_val, page_size]).await?;
-    pin!(rows);
-    while let Some(row) = rows.next().await {
-        if let Ok(row) = row {
-            let rec = SampleRecord::new(
-                row.get::<_, _>("id_"),
-                row.get::<_, _>("name_"),
-                row.get::<_, _>("available"),
-                row.get::<_, _>("created_at"),
-            );
-            result.push(rec);
+impl AsyncRepository<SampleRecord, i64> for SampleRecordRepository {
+    const TABLE_NAME: &'static str = "test_rec";
+    const KEY_COLUMN: &'static str = "id_";
+    const COLUMNS: &'static [&'static str] = &[
+        "id_",
+        "name_",
+        "available",
+        "created_at",
+        "version_",
+        "deleted_at",
+    ];
+    const VERSION_COLUMN: Option<&'static str> = Some("version_");
+    const CREATED_COLUMN: Option<&'static str> = Some("created_at");
+    const DELETED_COLUMN: Option<&'static str> = Some("deleted_at");
+
+    fn from_row(row: &Row) -> AppResult<SampleRecord> {
+        Ok(SampleRecord::new(
+            row.try_get("id_")?,
+            row.try_get("name_")?,
+            row.try_get("available")?,
+            row.try_get("created_at")?,
+            row.try_get("version_")?,
+            row.try_get("deleted_at")?,
+        ))
+    }
+
+    fn key(val: &SampleRecord) -> &i64 {
+        val.id()
+    }
+
+    fn to_params(val: &SampleRecord) -> Vec<SqlParam<'_>> {
+        vec![
+            &val.id,
+            &val.name,
+            &val.available,
+            &val.created_at,
+            &val.version,
+            &val.deleted_at,
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleRecordField {
+    Id,
+    Name,
+    Available,
+    CreatedAt,
+    Version,
+}
+
+impl Field for SampleRecordField {
+    const ALL: &'static [Self] = &[
+        Self::Id,
+        Self::Name,
+        Self::Available,
+        Self::CreatedAt,
+        Self::Version,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Name => "name",
+            Self::Available => "available",
+            Self::CreatedAt => "created_at",
+            Self::Version => "version",
+        }
+    }
+
+    fn column(self) -> &'static str {
+        match self {
+            Self::Id => "id_",
+            Self::Name => "name_",
+            Self::Available => "available",
+            Self::CreatedAt => "created_at",
+            Self::Version => "version_",
         }
     }
-    Ok(result)
-}
-pub async fn find_by_id(_id: i64) -> AppResult<Option<SampleRecord>> {
-    let conn = get_async_connection().await?;
-    let sql = "select id_, name_, available, created_at from test_rec where id_ = $1";
-    let row = conn.query_opt(sql, &[&_id]).await?;
-    match row {
-        Some(row) => Ok(Some(SampleRecord::new(
-            row.get("id_"),
-            row.get("name_"),
-            row.get("available"),
-            row.get::<_, NaiveDateTime>("created_at"),
-        ))),
-        None => Ok(None),
+
+    fn kind(self) -> Kind {
+        match self {
+            Self::Id => Kind::BigInt,
+            Self::Name => Kind::Text,
+            Self::Available => Kind::Bool,
+            Self::CreatedAt => Kind::Timestamp,
+            Self::Version => Kind::Int,
+        }
     }
 }
-pub async fn insert(val: &SampleRecord) -> AppResult<()> {
-    let conn = get_async_connection().await?;
-    let sql = "insert into test_rec (id_, name_, available, created_at) values ($1, $2, $3, $4)";
-    let _ = conn
-        .execute(sql, &[&val.id, &val.name, &val.available, &val.created_at])
-        .await?;
+
+impl QueryRepository<SampleRecord, i64> for SampleRecordRepository {
+    type Field = SampleRecordField;
+}
+
+pub async fn find(db: &Db, page_no: u32, page_size: u32) -> AppResult<Vec<SampleRecord>> {
+    SampleRecordRepository::find(db, page_no, page_size).await
+}
+pub async fn find_page(
+    db: &Db,
+    cursor: Option<&str>,
+    page_size: u32,
+    order: SortOrder,
+) -> AppResult<Page<SampleRecord>> {
+    SampleRecordRepository::find_page(db, cursor, page_size, order).await
+}
+/// See `AsyncRepository::stream`.
+pub async fn stream_all(
+    db: &Db,
+    order: SortOrder,
+) -> AppResult<impl Stream<Item = AppResult<SampleRecord>>> {
+    SampleRecordRepository::stream(db, order).await
+}
+/// See `persistence::query`.
+pub async fn find_by(
+    db: &Db,
+    query: &ListQuery<SampleRecordField>,
+) -> AppResult<Vec<SampleRecord>> {
+    SampleRecordRepository::find_by(db, query).await
+}
+pub async fn select(
+    db: &Db,
+    query: &ListQuery<SampleRecordField>,
+) -> AppResult<Vec<Map<String, Value>>> {
+    SampleRecordRepository::select(db, query).await
+}
+pub async fn find_by_id(db: &Db, _id: i64) -> AppResult<Option<SampleRecord>> {
+    SampleRecordRepository::find_by_id(db, &_id).await
+}
+pub async fn insert(db: &Db, val: &SampleRecord) -> AppResult<()> {
+    SampleRecordRepository::insert(db, val).await?;
     Ok(())
 }
-pub async fn insert_batch(vals: &[SampleRecord]) -> AppResult<()> {
-    let mut conn = get_async_connection().await?;
-    let tx = conn.transaction().await?;
-    // let mut sql_batch: Vec<String> = vec![];
-    let sql: &str =
-        "insert into test_rec (id_, name_, available, created_at) values ($1, $2, $3, $4)";
-    let stmt = tx.prepare(sql).await?;
-    for (idx, val) in vals.iter().enumerate() {
-        let params: &[&(dyn ToSql + Sync)] = &[&val.id, &val.name, &val.available, &val.created_at];
-        let exec = tx
-            .execute(
-                &stmt,
-                &[&val.id, &val.name, &val.available, &val.created_at],
-            )
-            .await?;
-        // let sql = format!(
-        //     "insert into test_rec (id_, name_, available, created_at) values (${}::INT8, ${}::TEXT, ${}::BOOL, ${}::TIMESTAMP)",
-        //     idx * params.len() + 1,
-        //     idx * params.len() + 2,
-        //     idx * params.len() + 3,
-        //     idx * params.len() + 4
-        // );
-        // let portal = tx.bind(&stmt, params).await?;
-        // sql_batch.push(String::from(sql));
-    }
-    // let exec = tx.batch_execute(sql_batch.join(";").as_str()).await;
-    let exec = tx.commit().await?;
+pub async fn insert_batch(db: &Db, vals: &[SampleRecord]) -> AppResult<()> {
+    SampleRecordRepository::insert_batch(db, vals).await?;
     Ok(())
 }
-pub async fn update(val: &SampleRecord) -> AppResult<()> {
-    let conn = get_async_connection().await?;
-    let sql = "update test_rec set name_=$2, available = $3  where id_ = $1 ";
-    let exec = conn
-        .execute(sql, &[&val.name, &val.available, &val.created_at])
-        .await?;
+/// Fast path for large loads, see `AsyncRepository::copy_in`.
+pub async fn bulk_insert(db: &Db, vals: &[SampleRecord]) -> AppResult<u64> {
+    SampleRecordRepository::copy_in(db, vals).await
+}
+pub async fn update(db: &Db, val: &SampleRecord) -> AppResult<()> {
+    SampleRecordRepository::update(db, val).await?;
     Ok(())
 }
-pub async fn delete(_id: i64) -> AppResult<()> {
-    let conn = get_async_connection().await?;
-    let sql = "delete from test_rec where id_=$1";
-    let _ = conn.execute(sql, &[&_id]).await?;
-
+/// A soft delete, see `AsyncRepository::DELETED_COLUMN`.
+pub async fn delete(db: &Db, _id: i64) -> AppResult<()> {
+    SampleRecordRepository::delete(db, &_id).await?;
+    Ok(())
+}
+pub async fn restore(db: &Db, _id: i64) -> AppResult<()> {
+    SampleRecordRepository::restore(db, &_id).await?;
+    Ok(())
+}
+pub async fn purge(db: &Db, _id: i64) -> AppResult<()> {
+    SampleRecordRepository::purge(db, &_id).await?;
     Ok(())
 }
@@ -0,0 +1,142 @@
+use crate::persistence::common::AsyncDbConnection;
+use anyhow::anyhow;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use std::future::Future;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::{Client, IsolationLevel};
+use tracing::warn;
+use utils::error::app_error::AppResult;
+
+/// Unit of work over one pooled async connection, started by `Db::begin`.
+///
+/// Pass `tx.client()` to the `*_with` repository functions so every statement
+/// runs in the same transaction; nothing is visible to others until `commit`.
+/// A `Tx` dropped without `commit` or `rollback` is rolled back.
+pub struct Tx {
+    conn: Option<AsyncDbConnection>,
+}
+
+impl Tx {
+    pub async fn begin_on(conn: AsyncDbConnection, isolation: IsolationLevel) -> AppResult<Self> {
+        let level = match isolation {
+            IsolationLevel::ReadUncommitted => "read uncommitted",
+            IsolationLevel::ReadCommitted => "read committed",
+            IsolationLevel::RepeatableRead => "repeatable read",
+            IsolationLevel::Serializable => "serializable",
+            _ => return Err(anyhow!("unsupported isolation level {:?}", isolation)),
+        };
+        conn.batch_execute(&format!("begin isolation level {level}"))
+            .await?;
+        Ok(Self { conn: Some(conn) })
+    }
+
+    pub fn client(&self) -> &Client {
+        self.conn.as_ref().expect("transaction already finished")
+    }
+
+    pub async fn savepoint(&self, name: &str) -> AppResult<()> {
+        self.execute_named("savepoint", name).await
+    }
+
+    /// Undoes everything after `savepoint(name)`, the transaction stays usable.
+    pub async fn rollback_to(&self, name: &str) -> AppResult<()> {
+        self.execute_named("rollback to savepoint", name).await
+    }
+
+    pub async fn release(&self, name: &str) -> AppResult<()> {
+        self.execute_named("release savepoint", name).await
+    }
+
+    pub async fn commit(mut self) -> AppResult<()> {
+        let conn = self.conn.take().expect("transaction already finished");
+        conn.batch_execute("commit").await?;
+        Ok(())
+    }
+
+    pub async fn rollback(mut self) -> AppResult<()> {
+        let conn = self.conn.take().expect("transaction already finished");
+        conn.batch_execute("rollback").await?;
+        Ok(())
+    }
+
+    /// Commits when `rs` is `Ok`, rolls back otherwise and hands `rs` back.
+    /// Unlike a dropped `Tx`, the rollback is done before this returns.
+    pub async fn finish<T>(self, rs: AppResult<T>) -> AppResult<T> {
+        match rs {
+            Ok(val) => {
+                self.commit().await?;
+                Ok(val)
+            }
+            Err(err) => {
+                if let Err(rollback_err) = self.rollback().await {
+                    warn!("Rollback failed: {}", rollback_err);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    async fn execute_named(&self, command: &str, name: &str) -> AppResult<()> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(anyhow!("invalid savepoint name: {name}"));
+        }
+        self.client()
+            .batch_execute(&format!("{command} {name}"))
+            .await?;
+        Ok(())
+    }
+}
+
+impl Drop for Tx {
+    fn drop(&mut self) {
+        // roll back before the connection goes back to the pool
+        if let Some(conn) = self.conn.take() {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn(async move {
+                        if let Err(err) = conn.batch_execute("rollback").await {
+                            warn!("Rollback of dropped transaction failed: {}", err);
+                        }
+                    });
+                }
+                Err(_) => warn!("Transaction dropped outside of a runtime"),
+            }
+        }
+    }
+}
+
+/// True when the error is a serialization failure or deadlock, i.e. the whole
+/// transaction can be safely run again.
+pub fn is_serialization_failure(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<tokio_postgres::Error>() {
+            return matches!(
+                err.code(),
+                Some(&SqlState::T_R_SERIALIZATION_FAILURE) | Some(&SqlState::T_R_DEADLOCK_DETECTED)
+            );
+        }
+        if let Some(DieselError::DatabaseError(kind, _)) = cause.downcast_ref::<DieselError>() {
+            return matches!(kind, DatabaseErrorKind::SerializationFailure);
+        }
+        false
+    })
+}
+
+/// Runs `f` again, up to `max_attempts` times in total, while it fails with a
+/// serialization failure. `f` must begin and commit its own `Tx`.
+pub async fn retry_serializable<T, F, Fut>(max_attempts: u32, mut f: F) -> AppResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = AppResult<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match f().await {
+            Err(err) if attempt < max_attempts && is_serialization_failure(&err) => {
+                warn!("Serialization failure, retry {}/{}", attempt, max_attempts);
+                attempt += 1;
+            }
+            rs => return rs,
+        }
+    }
+}
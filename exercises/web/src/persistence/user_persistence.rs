@@ -1,22 +1,195 @@
+use crate::models::user::users::{
+    deleted_at, dob, dsl::users, id, modified_date, org_id, org_treepath, passwd,
+    passwd_enc_method, screenname, status, username, version,
+};
 use crate::models::user::User;
-use anyhow::anyhow;
+use crate::persistence::db::Db;
+use crate::persistence::page::{decode_cursor, to_page, Page, SortOrder};
+use crate::persistence::query::{project, ListQuery};
+use crate::persistence::repository::{AsyncRepository, VersionConflict};
+use crate::persistence::user_persistence_async::{UserField, UserRepository};
+use chrono::NaiveDateTime;
+use diesel::dsl::insert_into;
+use diesel::dsl::{now, sql};
+use diesel::result::Error;
+use diesel::sql_types::Text;
+use diesel::OptionalExtension;
+use diesel::{
+    Connection, ExpressionMethods, NullableExpressionMethods, PgConnection, QueryDsl, RunQueryDsl,
+};
+use serde_json::{Map, Value};
 use utils::error::app_error::AppResult;
 
-pub fn find(page_no: u32, page_size: u32) -> AppResult<Vec<User>> {
-    let rs: Vec<User> = vec![];
-    Ok(rs)
+pub fn find(db: &Db, page_no: u32, page_size: u32) -> AppResult<Vec<User>> {
+    let mut conn = db.read_conn()?;
+    find_with(&mut conn, page_no, page_size)
+}
+pub fn find_page(
+    db: &Db,
+    cursor: Option<&str>,
+    page_size: u32,
+    order: SortOrder,
+) -> AppResult<Page<User>> {
+    let mut conn = db.read_conn()?;
+    find_page_with(&mut conn, cursor, page_size, order)
+}
+/// See `persistence::query`.
+pub fn find_by(db: &Db, query: &ListQuery<UserField>) -> AppResult<Vec<User>> {
+    let mut conn = db.read_conn()?;
+    find_by_with(&mut conn, query)
+}
+pub fn select(db: &Db, query: &ListQuery<UserField>) -> AppResult<Vec<Map<String, Value>>> {
+    let mut conn = db.read_conn()?;
+    select_with(&mut conn, query)
+}
+pub fn find_by_id(db: &Db, _id: i64) -> AppResult<Option<User>> {
+    let mut conn = db.read_conn()?;
+    find_by_id_with(&mut conn, _id)
+}
+
+pub fn insert(db: &Db, user: &User) -> AppResult<bool> {
+    let mut conn = db.conn()?;
+    insert_with(&mut conn, user)
 }
-pub fn find_by_id(id: u64) -> AppResult<User> {
-    Err(anyhow!("User with id {} not found", id))
+pub fn insert_batch(db: &Db, vals: &[User]) -> AppResult<()> {
+    let mut conn = db.conn()?;
+    conn.transaction::<(), Error, _>(|connection| {
+        insert_into(users).values(vals).execute(connection)?;
+        Ok(())
+    })?;
+    Ok(())
 }
 
-pub fn inssert(user: &User) -> AppResult<bool> {
-    Err(anyhow!("User {} not found", user.id()))
+pub fn update(db: &Db, user: &User) -> AppResult<bool> {
+    let mut conn = db.conn()?;
+    update_with(&mut conn, user)
+}
+pub fn delete(db: &Db, _id: i64) -> AppResult<bool> {
+    let mut conn = db.conn()?;
+    delete_with(&mut conn, _id)
+}
+pub fn restore(db: &Db, _id: i64) -> AppResult<bool> {
+    let mut conn = db.conn()?;
+    restore_with(&mut conn, _id)
 }
+pub fn purge(db: &Db, _id: i64) -> AppResult<bool> {
+    let mut conn = db.conn()?;
+    purge_with(&mut conn, _id)
+}
+
+// ----- the same operations on a caller supplied connection, e.g. inside `tx::with_tx` -----
+
+pub fn find_with(conn: &mut PgConnection, page_no: u32, page_size: u32) -> AppResult<Vec<User>> {
+    let offset_val = page_no as i64 * page_size as i64;
+    let rs = users
+        .filter(deleted_at.is_null())
+        .order(id.desc())
+        .offset(offset_val)
+        .limit(page_size as i64)
+        .load::<User>(conn)?;
 
-pub fn update(user: &User) -> AppResult<bool> {
-    Err(anyhow!("User {} not found", user.id()))
+    Ok(rs)
+}
+pub fn find_page_with(
+    conn: &mut PgConnection,
+    cursor: Option<&str>,
+    page_size: u32,
+    order: SortOrder,
+) -> AppResult<Page<User>> {
+    let mut query = users.filter(deleted_at.is_null()).into_boxed();
+    if let Some(cursor) = cursor {
+        let after = decode_cursor::<i64>(cursor, order)?;
+        query = match order {
+            SortOrder::Asc => query.filter(id.gt(after)),
+            SortOrder::Desc => query.filter(id.lt(after)),
+        };
+    }
+    query = match order {
+        SortOrder::Asc => query.order(id.asc()),
+        SortOrder::Desc => query.order(id.desc()),
+    };
+    let rs = query.limit(page_size as i64 + 1).load::<User>(conn)?;
+    Ok(to_page(rs, page_size, order, |user| user.id()))
+}
+pub fn find_by_with(conn: &mut PgConnection, query: &ListQuery<UserField>) -> AppResult<Vec<User>> {
+    let mut rs = users.filter(deleted_at.is_null()).into_boxed();
+    if let Some(filter) = query.diesel_filter()? {
+        rs = rs.filter(filter);
+    }
+    let rs = rs
+        .order(sql::<Text>(&query.order_sql(UserRepository::KEY_COLUMN)))
+        .offset(query.offset())
+        .limit(query.page_size as i64)
+        .load::<User>(conn)?;
+    Ok(rs)
+}
+/// Loads whole rows and cuts them down, diesel selects a fixed column list.
+pub fn select_with(
+    conn: &mut PgConnection,
+    query: &ListQuery<UserField>,
+) -> AppResult<Vec<Map<String, Value>>> {
+    find_by_with(conn, query)?
+        .iter()
+        .map(|user| project(user, query.fields()))
+        .collect()
+}
+pub fn find_by_id_with(conn: &mut PgConnection, _id: i64) -> AppResult<Option<User>> {
+    let rs = users
+        .filter(id.eq(_id))
+        .filter(deleted_at.is_null())
+        .first::<User>(conn)
+        .optional()?;
+    Ok(rs)
+}
+pub fn insert_with(conn: &mut PgConnection, user: &User) -> AppResult<bool> {
+    let rs = insert_into(users).values(user).execute(conn)?;
+    Ok(rs > 0)
+}
+pub fn insert_batch_with(conn: &mut PgConnection, vals: &[User]) -> AppResult<()> {
+    insert_into(users).values(vals).execute(conn)?;
+    Ok(())
+}
+/// Stamps `modified_date` and checks and bumps `version`, see
+/// `AsyncRepository::VERSION_COLUMN`.
+pub fn update_with(conn: &mut PgConnection, user: &User) -> AppResult<bool> {
+    let rs = diesel::update(
+        users
+            .filter(id.eq(user.id()))
+            .filter(version.eq(user.version()))
+            .filter(deleted_at.is_null()),
+    )
+    .set((
+        dob.eq(user.dob()),
+        passwd.eq(user.passwd()),
+        passwd_enc_method.eq(user.passwd_enc_method()),
+        screenname.eq(user.screenname()),
+        status.eq(user.status()),
+        username.eq(user.username()),
+        org_id.eq(user.org_id()),
+        org_treepath.eq(user.org_treepath()),
+        modified_date.eq(now),
+        version.eq(version + 1),
+    ))
+    .execute(conn)?;
+    if rs == 0 && find_by_id_with(conn, *user.id())?.is_some() {
+        return Err(VersionConflict::new(UserRepository::TABLE_NAME, user.id()).into());
+    }
+    Ok(rs > 0)
+}
+/// A soft delete, see `AsyncRepository::DELETED_COLUMN`.
+pub fn delete_with(conn: &mut PgConnection, _id: i64) -> AppResult<bool> {
+    let rs = diesel::update(users.filter(id.eq(_id)).filter(deleted_at.is_null()))
+        .set(deleted_at.eq(now.nullable()))
+        .execute(conn)?;
+    Ok(rs > 0)
+}
+pub fn restore_with(conn: &mut PgConnection, _id: i64) -> AppResult<bool> {
+    let rs = diesel::update(users.filter(id.eq(_id)).filter(deleted_at.is_not_null()))
+        .set(deleted_at.eq(None::<NaiveDateTime>))
+        .execute(conn)?;
+    Ok(rs > 0)
 }
-pub fn delete(id: u64) -> AppResult<bool> {
-    Err(anyhow!("User {} not found", id))
+pub fn purge_with(conn: &mut PgConnection, _id: i64) -> AppResult<bool> {
+    let rs = diesel::delete(users.filter(id.eq(_id))).execute(conn)?;
+    Ok(rs > 0)
 }
@@ -0,0 +1,202 @@
+use crate::models::user::User;
+use crate::persistence::db::Db;
+use crate::persistence::page::{Page, SortOrder};
+use crate::persistence::query::{Field, Filter, Kind, ListQuery};
+use crate::persistence::repository::{AsyncRepository, QueryRepository, SqlParam};
+use serde_json::{Map, Value};
+use tokio_postgres::Row;
+use tokio_stream::Stream;
+use utils::error::app_error::AppResult;
+
+pub struct UserRepository;
+
+impl AsyncRepository<User, i64> for UserRepository {
+    const TABLE_NAME: &'static str = "user_";
+    const KEY_COLUMN: &'static str = "id_";
+    const COLUMNS: &'static [&'static str] = &[
+        "id_",
+        "created_date",
+        "modified_date",
+        "dob",
+        "passwd",
+        "passwd_enc_method",
+        "screenname",
+        "status_",
+        "username",
+        "org_id",
+        "org_treepath",
+        "version_",
+        "deleted_at",
+    ];
+    const VERSION_COLUMN: Option<&'static str> = Some("version_");
+    const CREATED_COLUMN: Option<&'static str> = Some("created_date");
+    const MODIFIED_COLUMN: Option<&'static str> = Some("modified_date");
+    const DELETED_COLUMN: Option<&'static str> = Some("deleted_at");
+
+    fn from_row(row: &Row) -> AppResult<User> {
+        Ok(User::new(
+            row.try_get("id_")?,
+            row.try_get("created_date")?,
+            row.try_get("modified_date")?,
+            row.try_get("dob")?,
+            row.try_get("passwd")?,
+            row.try_get("passwd_enc_method")?,
+            row.try_get("screenname")?,
+            row.try_get("status_")?,
+            row.try_get("username")?,
+            row.try_get("org_id")?,
+            row.try_get("org_treepath")?,
+            row.try_get("version_")?,
+            row.try_get("deleted_at")?,
+        ))
+    }
+
+    fn key(val: &User) -> &i64 {
+        val.id()
+    }
+
+    fn to_params(val: &User) -> Vec<SqlParam<'_>> {
+        vec![
+            val.id(),
+            val.created_date(),
+            val.modified_date(),
+            val.dob(),
+            val.passwd(),
+            val.passwd_enc_method(),
+            val.screenname(),
+            val.status(),
+            val.username(),
+            val.org_id(),
+            val.org_treepath(),
+            val.version(),
+            val.deleted_at(),
+        ]
+    }
+}
+
+/// The fields listing can filter, sort and project on; the password ones are left out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserField {
+    Id,
+    CreatedDate,
+    ModifiedDate,
+    Dob,
+    Screenname,
+    Status,
+    Username,
+    OrgId,
+    OrgTreepath,
+    Version,
+}
+
+impl Field for UserField {
+    const ALL: &'static [Self] = &[
+        Self::Id,
+        Self::CreatedDate,
+        Self::ModifiedDate,
+        Self::Dob,
+        Self::Screenname,
+        Self::Status,
+        Self::Username,
+        Self::OrgId,
+        Self::OrgTreepath,
+        Self::Version,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::CreatedDate => "created_date",
+            Self::ModifiedDate => "modified_date",
+            Self::Dob => "dob",
+            Self::Screenname => "screenname",
+            Self::Status => "status",
+            Self::Username => "username",
+            Self::OrgId => "org_id",
+            Self::OrgTreepath => "org_treepath",
+            Self::Version => "version",
+        }
+    }
+
+    fn column(self) -> &'static str {
+        match self {
+            Self::Id => "id_",
+            Self::Status => "status_",
+            Self::Version => "version_",
+            other => other.name(),
+        }
+    }
+
+    fn kind(self) -> Kind {
+        match self {
+            Self::Id | Self::OrgId => Kind::BigInt,
+            Self::CreatedDate | Self::ModifiedDate => Kind::Timestamp,
+            Self::Dob => Kind::Date,
+            Self::Screenname | Self::Username | Self::OrgTreepath => Kind::Text,
+            Self::Status => Kind::SmallInt,
+            Self::Version => Kind::Int,
+        }
+    }
+}
+
+impl QueryRepository<User, i64> for UserRepository {
+    type Field = UserField;
+}
+
+pub async fn find(db: &Db, page_no: u32, page_size: u32) -> AppResult<Vec<User>> {
+    UserRepository::find(db, page_no, page_size).await
+}
+pub async fn find_page(
+    db: &Db,
+    cursor: Option<&str>,
+    page_size: u32,
+    order: SortOrder,
+) -> AppResult<Page<User>> {
+    UserRepository::find_page(db, cursor, page_size, order).await
+}
+/// See `AsyncRepository::stream`.
+pub async fn stream_all(
+    db: &Db,
+    order: SortOrder,
+) -> AppResult<impl Stream<Item = AppResult<User>>> {
+    UserRepository::stream(db, order).await
+}
+/// See `persistence::query`.
+pub async fn find_by(db: &Db, query: &ListQuery<UserField>) -> AppResult<Vec<User>> {
+    UserRepository::find_by(db, query).await
+}
+pub async fn select(db: &Db, query: &ListQuery<UserField>) -> AppResult<Vec<Map<String, Value>>> {
+    UserRepository::select(db, query).await
+}
+pub async fn find_by_id(db: &Db, _id: i64) -> AppResult<Option<User>> {
+    UserRepository::find_by_id(db, &_id).await
+}
+/// Usernames are unique among the live users, see migration 0006.
+pub async fn find_by_username(db: &Db, username: &str) -> AppResult<Option<User>> {
+    let query = ListQuery::new(0, 1).filter(Filter::eq(UserField::Username, username));
+    Ok(UserRepository::find_by(db, &query).await?.pop())
+}
+pub async fn insert(db: &Db, user: &User) -> AppResult<bool> {
+    Ok(UserRepository::insert(db, user).await? > 0)
+}
+pub async fn insert_batch(db: &Db, vals: &[User]) -> AppResult<()> {
+    UserRepository::insert_batch(db, vals).await?;
+    Ok(())
+}
+/// Fast path for large loads, see `AsyncRepository::copy_in`.
+pub async fn bulk_insert(db: &Db, vals: &[User]) -> AppResult<u64> {
+    UserRepository::copy_in(db, vals).await
+}
+pub async fn update(db: &Db, user: &User) -> AppResult<bool> {
+    Ok(UserRepository::update(db, user).await? > 0)
+}
+/// A soft delete, see `AsyncRepository::DELETED_COLUMN`.
+pub async fn delete(db: &Db, _id: i64) -> AppResult<bool> {
+    Ok(UserRepository::delete(db, &_id).await? > 0)
+}
+pub async fn restore(db: &Db, _id: i64) -> AppResult<bool> {
+    Ok(UserRepository::restore(db, &_id).await? > 0)
+}
+pub async fn purge(db: &Db, _id: i64) -> AppResult<bool> {
+    Ok(UserRepository::purge(db, &_id).await? > 0)
+}
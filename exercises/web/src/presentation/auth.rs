@@ -0,0 +1,50 @@
+use crate::models::user_status::UserStatus;
+use crate::persistence::user_persistence_async;
+use crate::presentation::common::ApiError;
+use crate::services::error::ServiceError;
+use crate::services::token_service::Principal;
+use crate::utils::app_context::AppContext;
+use axum::extract::{FromRequestParts, Request, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
+use axum::middleware::Next;
+use axum::response::Response;
+
+/// Route layer for everything behind a login: takes the bearer token, checks it
+/// with `TokenService::authenticate` and leaves the `Principal` for handlers.
+/// The status is the one stored on the primary now, not the one in the token,
+/// so a disabled or demoted account loses access at once. The account status
+/// is checked here, roles in the handlers, see `Principal::require`.
+pub async fn require_auth(
+    State(ctx): State<AppContext>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|val| val.to_str().ok())
+        .and_then(|val| val.strip_prefix("Bearer "))
+        .ok_or_else(|| ServiceError::Unauthorized("bearer token required".to_string()))?;
+    let claimed = ctx.tokens.authenticate(token.trim())?;
+    let user = user_persistence_async::find_by_id(&ctx.db.primary(), claimed.user_id)
+        .await?
+        .ok_or_else(|| ServiceError::Unauthorized(format!("{} is gone", claimed.username)))?;
+    let principal = Principal::of(&user);
+    principal.require(UserStatus::Active)?;
+    request.extensions_mut().insert(principal);
+    Ok(next.run(request).await)
+}
+
+/// Available to handlers of routes under `require_auth`.
+impl<S: Send + Sync> FromRequestParts<S> for Principal {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Principal>()
+            .cloned()
+            .ok_or_else(|| ServiceError::Unauthorized("not logged in".to_string()).into())
+    }
+}
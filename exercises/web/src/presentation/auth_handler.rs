@@ -0,0 +1,90 @@
+use crate::dto::auth_dto::{LoginRequest, PrincipalDto, RefreshRequest};
+use crate::presentation::common::{ApiResult, ValidJson};
+use crate::presentation::openapi::{ApiRoutes, Operation};
+use crate::services::auth_service;
+use crate::services::token_service::{Principal, TokenPair};
+use crate::utils::app_context::AppContext;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::Json;
+
+/// `/auth/me` is the only one that needs an access token, see `presentation::router`.
+pub fn routes() -> ApiRoutes {
+    ApiRoutes::new()
+        .route(
+            "/auth/login",
+            post(login),
+            [Operation::post("Log in")
+                .body::<LoginRequest>()
+                .response::<TokenPair>(200, "A new token pair")
+                .error(401, "Unknown user or wrong password")
+                .error(403, "The account is disabled")],
+        )
+        .route(
+            "/auth/refresh",
+            post(refresh),
+            [Operation::post("Trade a refresh token for a new pair")
+                .description("A refresh token is good for one use.")
+                .body::<RefreshRequest>()
+                .response::<TokenPair>(200, "A new token pair")
+                .error(401, "The refresh token is invalid, used or revoked")],
+        )
+        .route(
+            "/auth/logout",
+            post(logout),
+            [Operation::post("Revoke a refresh token")
+                .body::<RefreshRequest>()
+                .empty(204, "Revoked")
+                .error(401, "The refresh token is invalid")],
+        )
+}
+
+pub fn protected_routes() -> ApiRoutes {
+    ApiRoutes::new().route(
+        "/auth/me",
+        get(me),
+        [Operation::get("Who the access token is for")
+            .response::<PrincipalDto>(200, "The principal")],
+    )
+}
+
+async fn login(
+    State(ctx): State<AppContext>,
+    ValidJson(req): ValidJson<LoginRequest>,
+) -> ApiResult<Json<TokenPair>> {
+    let pair = auth_service::login(
+        &ctx.db,
+        &ctx.hasher,
+        &ctx.tokens,
+        &req.username,
+        &req.passwd,
+    )
+    .await?;
+    Ok(Json(pair))
+}
+
+async fn refresh(
+    State(ctx): State<AppContext>,
+    ValidJson(req): ValidJson<RefreshRequest>,
+) -> ApiResult<Json<TokenPair>> {
+    Ok(Json(
+        auth_service::refresh(&ctx.db, &ctx.tokens, &req.refresh_token).await?,
+    ))
+}
+
+async fn logout(
+    State(ctx): State<AppContext>,
+    ValidJson(req): ValidJson<RefreshRequest>,
+) -> ApiResult<StatusCode> {
+    auth_service::logout(&ctx.db, &ctx.tokens, &req.refresh_token, req.everywhere).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn me(principal: Principal) -> Json<PrincipalDto> {
+    Json(PrincipalDto {
+        user_id: principal.user_id,
+        username: principal.username,
+        status: principal.status,
+    })
+}
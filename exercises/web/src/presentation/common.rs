@@ -0,0 +1,149 @@
+use crate::persistence::page::SortOrder;
+use crate::persistence::query::{Field, ListQuery};
+use crate::services::error::ServiceError;
+use axum::extract::{FromRequest, Request};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use macros::ApiSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::error;
+use utils::validation::Validate;
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+/// Wraps the `AppResult` error so handlers can use `?`; business errors keep
+/// their message, everything else becomes an opaque 500.
+#[derive(Debug)]
+pub struct ApiError(anyhow::Error);
+
+/// The body of every error response.
+#[derive(Debug, Clone, Serialize, Deserialize, ApiSchema)]
+pub struct ErrorResponse {
+    pub error: String,
+    /// the failed checks of a 422, the messages by field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<BTreeMap<String, Vec<String>>>,
+}
+
+impl ErrorResponse {
+    fn of(error: String) -> Json<Self> {
+        Json(Self {
+            error,
+            fields: None,
+        })
+    }
+}
+
+impl<E: Into<anyhow::Error>> From<E> for ApiError {
+    fn from(err: E) -> Self {
+        Self(err.into())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self.0.downcast_ref::<ServiceError>() {
+            Some(ServiceError::NotFound(_)) => (StatusCode::NOT_FOUND, self.0.to_string()),
+            Some(ServiceError::InvalidFields(errors)) => {
+                let body = Json(ErrorResponse {
+                    error: self.0.to_string(),
+                    fields: Some(errors.fields().clone()),
+                });
+                return (StatusCode::UNPROCESSABLE_ENTITY, body).into_response();
+            }
+            Some(ServiceError::Validation(_)) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, self.0.to_string())
+            }
+            Some(ServiceError::Conflict(_)) => (StatusCode::CONFLICT, self.0.to_string()),
+            Some(ServiceError::Unauthorized(_)) => {
+                let body = ErrorResponse::of(self.0.to_string());
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    [(header::WWW_AUTHENTICATE, "Bearer")],
+                    body,
+                )
+                    .into_response();
+            }
+            Some(ServiceError::Forbidden(_)) => (StatusCode::FORBIDDEN, self.0.to_string()),
+            None => {
+                error!("Request failed: {:?}", self.0);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "internal server error".to_string(),
+                )
+            }
+        };
+        (status, ErrorResponse::of(message)).into_response()
+    }
+}
+
+/// A JSON body that passed its `#[derive(Validate)]` checks; the failed ones
+/// come back as a 422 with the messages by field.
+#[derive(Debug, Clone)]
+pub struct ValidJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(val) = Json::<T>::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        val.validate().map_err(|errors| {
+            ApiError::from(ServiceError::InvalidFields(errors)).into_response()
+        })?;
+        Ok(Self(val))
+    }
+}
+
+/// A page, optionally with the filter, sort and projection of `persistence::query`,
+/// e.g. `?filter=available:eq:true&sort=-created_at&fields=id,name`.
+#[derive(Debug, Clone, Deserialize, ApiSchema)]
+pub struct ListParams {
+    /// from 0
+    #[serde(default)]
+    pub page_no: u32,
+    /// 20 unless given
+    #[serde(default = "default_page_size")]
+    pub page_size: u32,
+    /// `field:op:value` terms joined by `,`, e.g. `available:eq:true`
+    pub filter: Option<String>,
+    /// fields joined by `,`, descending with a leading `-`
+    pub sort: Option<String>,
+    /// the fields to return, joined by `,`
+    pub fields: Option<String>,
+}
+
+impl ListParams {
+    pub fn is_plain(&self) -> bool {
+        self.filter.is_none() && self.sort.is_none() && self.fields.is_none()
+    }
+
+    pub fn to_query<F: Field>(&self) -> Result<ListQuery<F>, ServiceError> {
+        ListQuery::parse(
+            self.filter.as_deref(),
+            self.sort.as_deref(),
+            self.fields.as_deref(),
+            self.page_no,
+            self.page_size,
+        )
+        .map_err(|err| ServiceError::Validation(format!("{err:#}")))
+    }
+}
+
+fn default_page_size() -> u32 {
+    20
+}
+
+#[derive(Debug, Clone, Default, Deserialize, ApiSchema)]
+pub struct OrderQuery {
+    #[serde(default)]
+    pub order: SortOrder,
+}
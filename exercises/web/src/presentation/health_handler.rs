@@ -0,0 +1,94 @@
+use crate::dto::health_dto::HealthStatus;
+use crate::presentation::openapi::{ApiRoutes, Operation};
+use crate::services::health_service;
+use crate::utils::app_context::AppContext;
+use crate::utils::metrics;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::routing::get;
+use axum::Json;
+use std::collections::BTreeMap;
+
+/// The content type of the Prometheus text format.
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Public, for probes and scrapers without a token.
+pub fn routes() -> ApiRoutes {
+    ApiRoutes::new()
+        .route(
+            "/health/live",
+            get(live),
+            [Operation::get("Whether the process is up")
+                .description("Does not touch the database.")
+                .response::<HealthStatus>(200, "Up")],
+        )
+        .route(
+            "/health/ready",
+            get(ready),
+            [Operation::get("Whether the service can take requests")
+                .description("The primary database answers and has no pending migrations.")
+                .response::<HealthStatus>(200, "Ready")
+                .response::<HealthStatus>(503, "Not ready, see `checks`")],
+        )
+        .route(
+            "/metrics",
+            get(metrics),
+            [Operation::get("Metrics in the Prometheus text format")
+                .description("Request counts and latency by route, pool state and query latency.")
+                .text(200, "The metrics")],
+        )
+}
+
+fn status(up: bool) -> &'static str {
+    match up {
+        true => "up",
+        false => "down",
+    }
+}
+
+async fn live() -> Json<HealthStatus> {
+    Json(HealthStatus {
+        status: status(true).to_string(),
+        checks: BTreeMap::new(),
+    })
+}
+
+async fn ready(State(ctx): State<AppContext>) -> (StatusCode, Json<HealthStatus>) {
+    let readiness = health_service::readiness(&ctx.db).await;
+    let mut checks = BTreeMap::new();
+    checks.insert(
+        "database".to_string(),
+        status(readiness.database).to_string(),
+    );
+    if readiness.database {
+        let migrations = match readiness.pending_migrations.as_slice() {
+            [] => "up to date".to_string(),
+            pending => format!("pending {pending:?}"),
+        };
+        checks.insert("migrations".to_string(), migrations);
+    }
+    if readiness.replicas > 0 {
+        checks.insert(
+            "replicas".to_string(),
+            format!("{} of {} up", readiness.replicas_up, readiness.replicas),
+        );
+    }
+    let code = match readiness.is_ready() {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    let body = HealthStatus {
+        status: status(readiness.is_ready()).to_string(),
+        checks,
+    };
+    (code, Json(body))
+}
+
+async fn metrics(
+    State(ctx): State<AppContext>,
+) -> ([(header::HeaderName, &'static str); 1], String) {
+    (
+        [(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)],
+        metrics::render(&ctx.db),
+    )
+}
@@ -0,0 +1,24 @@
+use crate::utils::metrics::{HTTP_REQUESTS, HTTP_REQUEST_DURATION};
+use axum::extract::{MatchedPath, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::time::Instant;
+
+/// Route label of requests no route matched, so probing random paths does
+/// not add series.
+const UNMATCHED: &str = "unmatched";
+
+/// Counts and times every response into `utils::metrics`, see `presentation::router`.
+pub async fn record_metrics(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED.to_string());
+    let started = Instant::now();
+    let response = next.run(request).await;
+    HTTP_REQUEST_DURATION.observe_duration(&[&method, &route], started.elapsed());
+    HTTP_REQUESTS.inc(&[&method, &route, response.status().as_str()]);
+    response
+}
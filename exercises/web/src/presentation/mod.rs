@@ -1 +1,48 @@
+use crate::presentation::openapi::{ApiRoutes, SWAGGER_UI};
+use crate::utils::app_context::AppContext;
+use axum::middleware::from_fn;
+use axum::response::Html;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::Value;
 
+pub mod auth;
+pub mod auth_handler;
+pub mod common;
+pub mod health_handler;
+pub mod metrics;
+pub mod openapi;
+pub mod sample_rec_handler;
+pub mod user_handler;
+
+/// Every documented route; everything but logging in and out, health and
+/// metrics takes an access token, see `auth::require_auth`.
+pub fn routes() -> ApiRoutes {
+    let protected = ApiRoutes::new()
+        .merge(auth_handler::protected_routes())
+        .merge(sample_rec_handler::routes())
+        .merge(user_handler::routes())
+        .secured();
+    ApiRoutes::new()
+        .merge(auth_handler::routes())
+        .merge(health_handler::routes())
+        .merge(protected)
+}
+
+/// The OpenAPI document of `routes`, as `/openapi.json` serves it.
+pub fn document() -> Value {
+    routes().document()
+}
+
+/// `routes` plus their document at `/openapi.json` and Swagger UI at `/docs`,
+/// every response counted by `metrics::record_metrics`.
+pub fn router(ctx: AppContext) -> Router {
+    let routes = routes();
+    let document = Json(routes.document());
+    routes
+        .into_router(&ctx)
+        .route("/openapi.json", get(move || async move { document }))
+        .route("/docs", get(|| async { Html(SWAGGER_UI) }))
+        .layer(from_fn(metrics::record_metrics))
+        .with_state(ctx)
+}
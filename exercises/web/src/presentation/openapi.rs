@@ -0,0 +1,301 @@
+use crate::presentation::auth::require_auth;
+use crate::presentation::common::ErrorResponse;
+use crate::utils::app_context::AppContext;
+use axum::middleware::from_fn_with_state;
+use axum::routing::MethodRouter;
+use axum::Router;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use utils::openapi::{ApiSchema, Components};
+
+/// The OpenAPI side of one handler, next to the `MethodRouter` it documents,
+/// e.g. `Operation::get("One record").path::<i64>("id").response::<Dto>(200, "The record")`.
+pub struct Operation {
+    method: &'static str,
+    doc: Map<String, Value>,
+    parameters: Vec<Value>,
+    responses: Map<String, Value>,
+    components: Components,
+}
+
+impl Operation {
+    fn new(method: &'static str, summary: &str) -> Self {
+        let mut doc = Map::new();
+        doc.insert("summary".to_string(), json!(summary));
+        Self {
+            method,
+            doc,
+            parameters: vec![],
+            responses: Map::new(),
+            components: Components::new(),
+        }
+    }
+
+    pub fn get(summary: &str) -> Self {
+        Self::new("get", summary)
+    }
+
+    pub fn post(summary: &str) -> Self {
+        Self::new("post", summary)
+    }
+
+    pub fn put(summary: &str) -> Self {
+        Self::new("put", summary)
+    }
+
+    pub fn delete(summary: &str) -> Self {
+        Self::new("delete", summary)
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.doc
+            .insert("description".to_string(), json!(description));
+        self
+    }
+
+    /// A `{name}` of the path.
+    pub fn path<T: ApiSchema>(mut self, name: &str) -> Self {
+        let schema = T::reference(&mut self.components);
+        self.parameters.push(json!({
+            "name": name,
+            "in": "path",
+            "required": true,
+            "schema": schema,
+        }));
+        self
+    }
+
+    /// The fields of `T`, as a `Query<T>` reads them.
+    pub fn query<T: ApiSchema>(mut self) -> Self {
+        let schema = T::schema(&mut self.components);
+        let required = schema["required"].as_array().cloned().unwrap_or_default();
+        if let Some(Value::Object(properties)) = schema.get("properties").cloned() {
+            for (name, mut property) in properties {
+                let description = property
+                    .as_object_mut()
+                    .and_then(|p| p.remove("description"));
+                let mut parameter = json!({
+                    "name": name,
+                    "in": "query",
+                    "required": required.contains(&json!(name)),
+                    "schema": property,
+                });
+                if let Some(description) = description {
+                    parameter["description"] = description;
+                }
+                self.parameters.push(parameter);
+            }
+        }
+        self
+    }
+
+    /// A JSON body, as `ValidJson<T>` reads it: the failed checks are a 422.
+    pub fn body<T: ApiSchema>(mut self) -> Self {
+        let schema = T::reference(&mut self.components);
+        self.doc.insert(
+            "requestBody".to_string(),
+            json!({
+                "required": true,
+                "content": { "application/json": { "schema": schema } },
+            }),
+        );
+        self.error(422, "The body is malformed or fails its checks")
+    }
+
+    pub fn response<T: ApiSchema>(self, status: u16, description: &str) -> Self {
+        self.content::<T>(status, description, "application/json")
+    }
+
+    /// One JSON `T` a line.
+    pub fn ndjson<T: ApiSchema>(self, status: u16, description: &str) -> Self {
+        self.content::<T>(status, description, "application/x-ndjson")
+    }
+
+    /// Plain text, e.g. `/metrics`.
+    pub fn text(self, status: u16, description: &str) -> Self {
+        self.content::<String>(status, description, "text/plain")
+    }
+
+    fn content<T: ApiSchema>(mut self, status: u16, description: &str, media: &str) -> Self {
+        let schema = T::reference(&mut self.components);
+        self.responses.insert(
+            status.to_string(),
+            json!({
+                "description": description,
+                "content": { media: { "schema": schema } },
+            }),
+        );
+        self
+    }
+
+    pub fn empty(mut self, status: u16, description: &str) -> Self {
+        self.responses
+            .insert(status.to_string(), json!({ "description": description }));
+        self
+    }
+
+    /// A failure with the body of `ApiError`.
+    pub fn error(self, status: u16, description: &str) -> Self {
+        self.response::<ErrorResponse>(status, description)
+    }
+
+    fn into_doc(mut self, tag: &str) -> Value {
+        self.doc.insert("tags".to_string(), json!([tag]));
+        if !self.parameters.is_empty() {
+            self.doc
+                .insert("parameters".to_string(), json!(self.parameters));
+        }
+        self.doc
+            .insert("responses".to_string(), Value::Object(self.responses));
+        Value::Object(self.doc)
+    }
+}
+
+/// A router together with the OpenAPI paths of its routes, so the document
+/// can not miss a route added the usual way. Routes made `secured` get
+/// `require_auth` when turned into the router.
+pub struct ApiRoutes {
+    public: Router<AppContext>,
+    secured: Router<AppContext>,
+    paths: BTreeMap<String, Map<String, Value>>,
+    components: Components,
+}
+
+impl Default for ApiRoutes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApiRoutes {
+    pub fn new() -> Self {
+        Self {
+            public: Router::new(),
+            secured: Router::new(),
+            paths: BTreeMap::new(),
+            components: Components::new(),
+        }
+    }
+
+    /// Like `Router::route`, `operations` being the methods of `method_router`;
+    /// every `{param}` of the path must be declared by each of them.
+    pub fn route(
+        mut self,
+        path: &str,
+        method_router: MethodRouter<AppContext>,
+        operations: impl IntoIterator<Item = Operation>,
+    ) -> Self {
+        // tagged by the first segment, e.g. `users` for `/users/{id}`
+        let tag = path.trim_start_matches('/').split('/').next().unwrap_or("");
+        let params = path
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+            .collect::<Vec<_>>();
+        let entry = self.paths.entry(path.to_string()).or_default();
+        for mut operation in operations {
+            for param in &params {
+                let declared = operation
+                    .parameters
+                    .iter()
+                    .any(|p| p["in"] == "path" && p["name"] == *param);
+                assert!(
+                    declared,
+                    "{} {path}: path parameter {param} is not documented",
+                    operation.method
+                );
+            }
+            self.components.append(&mut operation.components);
+            entry.insert(operation.method.to_string(), operation.into_doc(tag));
+        }
+        self.public = self.public.route(path, method_router);
+        self
+    }
+
+    pub fn merge(mut self, mut other: ApiRoutes) -> Self {
+        self.public = self.public.merge(other.public);
+        self.secured = self.secured.merge(other.secured);
+        for (path, operations) in other.paths {
+            self.paths.entry(path).or_default().extend(operations);
+        }
+        self.components.append(&mut other.components);
+        self
+    }
+
+    /// Every route so far takes an access token, see `auth::require_auth`.
+    pub fn secured(mut self) -> Self {
+        let schema = ErrorResponse::reference(&mut self.components);
+        let responses = [
+            ("401", "The access token is missing, invalid or expired"),
+            ("403", "The account may not do this"),
+        ]
+        .map(|(status, description)| {
+            let response = json!({
+                "description": description,
+                "content": { "application/json": { "schema": schema } },
+            });
+            (status.to_string(), response)
+        });
+        for operations in self.paths.values_mut() {
+            for operation in operations.values_mut() {
+                operation["security"] = json!([{ "bearerAuth": [] }]);
+                if let Some(existing) = operation["responses"].as_object_mut() {
+                    for (status, response) in &responses {
+                        existing
+                            .entry(status.clone())
+                            .or_insert_with(|| response.clone());
+                    }
+                }
+            }
+        }
+        self.secured = self.secured.merge(self.public);
+        self.public = Router::new();
+        self
+    }
+
+    /// The OpenAPI 3 document of every route.
+    pub fn document(&self) -> Value {
+        let mut schemas = Map::new();
+        schemas.extend(self.components.clone());
+        json!({
+            "openapi": "3.0.3",
+            "info": {
+                "title": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+            "paths": self.paths,
+            "components": {
+                "schemas": schemas,
+                "securitySchemes": {
+                    "bearerAuth": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" },
+                },
+            },
+        })
+    }
+
+    pub fn into_router(self, ctx: &AppContext) -> Router<AppContext> {
+        let secured = self
+            .secured
+            .route_layer(from_fn_with_state(ctx.clone(), require_auth));
+        self.public.merge(secured)
+    }
+}
+
+/// Swagger UI from its CDN, reading `/openapi.json`.
+pub const SWAGGER_UI: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>API docs</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"##;
@@ -0,0 +1,124 @@
+use crate::dto::sample_rec_dto::{NewSampleRecord, SampleRecordDto, SampleRecordUpdate};
+use crate::presentation::common::{ApiResult, ListParams, OrderQuery, ValidJson};
+use crate::presentation::openapi::{ApiRoutes, Operation};
+use crate::services::sample_rec_service;
+use crate::utils::app_context::AppContext;
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Json;
+use tokio_stream::StreamExt;
+use utils::error::app_error::AppResult;
+
+pub fn routes() -> ApiRoutes {
+    ApiRoutes::new()
+        .route(
+            "/sample-records",
+            get(find).post(create),
+            [
+                Operation::get("List records")
+                    .query::<ListParams>()
+                    .response::<Vec<SampleRecordDto>>(
+                        200,
+                        "A page of records, only the `fields` asked for if any",
+                    ),
+                Operation::post("Create a record")
+                    .body::<NewSampleRecord>()
+                    .response::<SampleRecordDto>(201, "The stored record")
+                    .error(409, "The id is taken"),
+            ],
+        )
+        .route(
+            "/sample-records/export",
+            get(export),
+            [Operation::get("Export every record")
+                .query::<OrderQuery>()
+                .ndjson::<SampleRecordDto>(200, "The records, one a line")],
+        )
+        .route(
+            "/sample-records/{id}",
+            get(find_by_id).put(update).delete(delete),
+            [
+                Operation::get("One record")
+                    .path::<i64>("id")
+                    .response::<SampleRecordDto>(200, "The record")
+                    .error(404, "No such record"),
+                Operation::put("Update a record")
+                    .path::<i64>("id")
+                    .body::<SampleRecordUpdate>()
+                    .response::<SampleRecordDto>(200, "The stored record")
+                    .error(404, "No such record")
+                    .error(409, "The record changed since `version` was read"),
+                Operation::delete("Delete a record")
+                    .path::<i64>("id")
+                    .empty(204, "Deleted")
+                    .error(404, "No such record"),
+            ],
+        )
+}
+
+/// Whole records unless the query filters, sorts or projects, see `ListParams`.
+async fn find(
+    State(ctx): State<AppContext>,
+    Query(params): Query<ListParams>,
+) -> ApiResult<Response> {
+    if params.is_plain() {
+        let vals = sample_rec_service::find(&ctx.db, params.page_no, params.page_size).await?;
+        let vals = vals.iter().map(SampleRecordDto::from).collect::<Vec<_>>();
+        return Ok(Json(vals).into_response());
+    }
+    let vals = sample_rec_service::select(&ctx.db, &params.to_query()?).await?;
+    Ok(Json(vals).into_response())
+}
+
+/// Streams every record as newline delimited JSON, row by row.
+async fn export(
+    State(ctx): State<AppContext>,
+    Query(query): Query<OrderQuery>,
+) -> ApiResult<Response> {
+    let rows = sample_rec_service::stream_all(&ctx.db, query.order).await?;
+    let lines = rows.map(|rec| {
+        let mut line = serde_json::to_vec(&SampleRecordDto::from(&rec?))?;
+        line.push(b'\n');
+        AppResult::Ok(line)
+    });
+    Ok((
+        [(CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response())
+}
+
+async fn find_by_id(
+    State(ctx): State<AppContext>,
+    Path(_id): Path<i64>,
+) -> ApiResult<Json<SampleRecordDto>> {
+    let stored = sample_rec_service::find_by_id(&ctx.db, _id).await?;
+    Ok(Json(SampleRecordDto::from(&stored)))
+}
+
+async fn create(
+    State(ctx): State<AppContext>,
+    ValidJson(val): ValidJson<NewSampleRecord>,
+) -> ApiResult<(StatusCode, Json<SampleRecordDto>)> {
+    let stored = sample_rec_service::create(&ctx.db, &val.into()).await?;
+    Ok((StatusCode::CREATED, Json(SampleRecordDto::from(&stored))))
+}
+
+async fn update(
+    State(ctx): State<AppContext>,
+    Path(_id): Path<i64>,
+    ValidJson(mut val): ValidJson<SampleRecordUpdate>,
+) -> ApiResult<Json<SampleRecordDto>> {
+    val.id = _id;
+    let stored = sample_rec_service::update(&ctx.db, &val.into()).await?;
+    Ok(Json(SampleRecordDto::from(&stored)))
+}
+
+async fn delete(State(ctx): State<AppContext>, Path(_id): Path<i64>) -> ApiResult<StatusCode> {
+    sample_rec_service::delete(&ctx.db, _id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,148 @@
+use crate::dto::user_dto::{NewUser, PasswdChange, UserDto, UserUpdate};
+use crate::models::user_status::UserStatus;
+use crate::presentation::common::{ApiResult, ListParams, ValidJson};
+use crate::presentation::openapi::{ApiRoutes, Operation};
+use crate::services::error::ServiceError;
+use crate::services::token_service::Principal;
+use crate::services::{credential_service, user_service};
+use crate::utils::app_context::AppContext;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, put};
+use axum::Json;
+
+pub fn routes() -> ApiRoutes {
+    ApiRoutes::new()
+        .route(
+            "/users",
+            get(find).post(create),
+            [
+                Operation::get("List users")
+                    .query::<ListParams>()
+                    .response::<Vec<UserDto>>(
+                        200,
+                        "A page of users, only the `fields` asked for if any",
+                    ),
+                Operation::post("Create a user")
+                    .description("Admins only.")
+                    .body::<NewUser>()
+                    .response::<UserDto>(201, "The stored user")
+                    .error(409, "The id or username is taken"),
+            ],
+        )
+        .route(
+            "/users/{id}",
+            get(find_by_id).put(update).delete(delete),
+            [
+                Operation::get("One user")
+                    .path::<i64>("id")
+                    .response::<UserDto>(200, "The user")
+                    .error(404, "No such user"),
+                Operation::put("Update a user")
+                    .description("Admins only; the password is kept.")
+                    .path::<i64>("id")
+                    .body::<UserUpdate>()
+                    .response::<UserDto>(200, "The stored user")
+                    .error(404, "No such user")
+                    .error(
+                        409,
+                        "The user changed since `version` was read, or the username is taken",
+                    ),
+                Operation::delete("Delete a user")
+                    .description("Admins only.")
+                    .path::<i64>("id")
+                    .empty(204, "Deleted")
+                    .error(404, "No such user"),
+            ],
+        )
+        .route(
+            "/users/{id}/passwd",
+            put(change_password),
+            [Operation::put("Change the own password")
+                .path::<i64>("id")
+                .body::<PasswdChange>()
+                .empty(204, "Changed")
+                .error(404, "No such user")
+                .error(409, "The user changed since `version` was read")],
+        )
+}
+
+/// Whole records unless the query filters, sorts or projects, see `ListParams`.
+async fn find(
+    State(ctx): State<AppContext>,
+    Query(params): Query<ListParams>,
+) -> ApiResult<Response> {
+    if params.is_plain() {
+        let vals = user_service::find(&ctx.db, params.page_no, params.page_size).await?;
+        let vals = vals.iter().map(UserDto::from).collect::<Vec<_>>();
+        return Ok(Json(vals).into_response());
+    }
+    let vals = user_service::select(&ctx.db, &params.to_query()?).await?;
+    Ok(Json(vals).into_response())
+}
+
+async fn find_by_id(
+    State(ctx): State<AppContext>,
+    Path(_id): Path<i64>,
+) -> ApiResult<Json<UserDto>> {
+    let stored = user_service::find_by_id(&ctx.db, _id).await?;
+    Ok(Json(UserDto::from(&stored)))
+}
+
+/// Writes are for admins, see `UserStatus`.
+async fn create(
+    State(ctx): State<AppContext>,
+    principal: Principal,
+    ValidJson(user): ValidJson<NewUser>,
+) -> ApiResult<(StatusCode, Json<UserDto>)> {
+    principal.require(UserStatus::Admin)?;
+    let stored = user_service::create(&ctx.db, &ctx.hasher, &user.into()).await?;
+    Ok((StatusCode::CREATED, Json(UserDto::from(&stored))))
+}
+
+async fn update(
+    State(ctx): State<AppContext>,
+    principal: Principal,
+    Path(_id): Path<i64>,
+    ValidJson(mut user): ValidJson<UserUpdate>,
+) -> ApiResult<Json<UserDto>> {
+    principal.require(UserStatus::Admin)?;
+    user.id = _id;
+    let stored = user_service::update(&ctx.db, &user.into()).await?;
+    Ok(Json(UserDto::from(&stored)))
+}
+
+async fn delete(
+    State(ctx): State<AppContext>,
+    principal: Principal,
+    Path(_id): Path<i64>,
+) -> ApiResult<StatusCode> {
+    principal.require(UserStatus::Admin)?;
+    user_service::delete(&ctx.db, _id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Users change their own password only, knowing the current one.
+async fn change_password(
+    State(ctx): State<AppContext>,
+    principal: Principal,
+    Path(_id): Path<i64>,
+    ValidJson(req): ValidJson<PasswdChange>,
+) -> ApiResult<StatusCode> {
+    if principal.user_id != _id {
+        return Err(
+            ServiceError::Forbidden(format!("{} is not user {_id}", principal.username)).into(),
+        );
+    }
+    credential_service::change_password(
+        &ctx.db,
+        &ctx.hasher,
+        _id,
+        req.version,
+        &req.current,
+        &req.new,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
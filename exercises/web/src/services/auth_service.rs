@@ -0,0 +1,81 @@
+use crate::models::user::User;
+use crate::persistence::db::Db;
+use crate::persistence::{refresh_token_persistence, user_persistence_async};
+use crate::services::credential_service;
+use crate::services::error::ServiceError;
+use crate::services::password_hasher::PasswordHasher;
+use crate::services::token_service::{Principal, TokenPair, TokenService, TokenUse};
+use utils::error::app_error::AppResult;
+
+fn check_enabled(user: &User) -> Result<(), ServiceError> {
+    if !user.user_status().is_enabled() {
+        return Err(ServiceError::Forbidden(format!(
+            "user {} is disabled",
+            user.username()
+        )));
+    }
+    Ok(())
+}
+
+/// A short lived access token and a refresh token recorded for `user`.
+async fn issue(db: &Db, tokens: &TokenService, user: &User) -> AppResult<TokenPair> {
+    let principal = Principal::of(user);
+    let access = tokens.claims(&principal, TokenUse::Access)?;
+    let refresh = tokens.claims(&principal, TokenUse::Refresh)?;
+    refresh_token_persistence::insert(db, &refresh.jti, principal.user_id, tokens.refresh_ttl)
+        .await?;
+    Ok(TokenPair {
+        access_token: tokens.sign(&access)?,
+        refresh_token: tokens.sign(&refresh)?,
+        token_type: "Bearer".to_string(),
+        expires_in: tokens.access_ttl.as_secs(),
+    })
+}
+
+/// Disabled users are refused even with the right password.
+pub async fn login(
+    db: &Db,
+    hasher: &PasswordHasher,
+    tokens: &TokenService,
+    username: &str,
+    passwd: &str,
+) -> AppResult<TokenPair> {
+    let user = credential_service::login(db, hasher, username, passwd).await?;
+    check_enabled(&user)?;
+    issue(db, tokens, &user).await
+}
+
+/// Trades a refresh token for a new pair. Each refresh token works once and
+/// the user is read again, so a disabled or deleted one gets no more tokens.
+pub async fn refresh(db: &Db, tokens: &TokenService, refresh_token: &str) -> AppResult<TokenPair> {
+    let claims = tokens.verify(refresh_token, TokenUse::Refresh)?;
+    let Some(user_id) = refresh_token_persistence::consume(db, &claims.jti).await? else {
+        return Err(ServiceError::Unauthorized("refresh token revoked".to_string()).into());
+    };
+    let user = user_persistence_async::find_by_id(&db.primary(), user_id)
+        .await?
+        .ok_or_else(|| ServiceError::Unauthorized(format!("user {user_id} is gone")))?;
+    check_enabled(&user)?;
+    issue(db, tokens, &user).await
+}
+
+/// Revokes the refresh token, or with `everywhere` every refresh token of its
+/// user. Access tokens already handed out stay good until they expire.
+pub async fn logout(
+    db: &Db,
+    tokens: &TokenService,
+    refresh_token: &str,
+    everywhere: bool,
+) -> AppResult<()> {
+    let claims = tokens.verify(refresh_token, TokenUse::Refresh)?;
+    if everywhere {
+        let user_id = claims
+            .sub
+            .parse::<i64>()
+            .map_err(|_| ServiceError::Unauthorized("invalid token: bad subject".to_string()))?;
+        refresh_token_persistence::revoke_all(db, user_id).await?;
+    } else {
+        refresh_token_persistence::revoke(db, &claims.jti).await?;
+    }
+    Ok(())
+}
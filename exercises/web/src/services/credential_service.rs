@@ -0,0 +1,102 @@
+use crate::models::user::User;
+use crate::persistence::db::Db;
+use crate::persistence::user_persistence_async;
+use crate::services::error::{map_conflict, ServiceError};
+use crate::services::password_hasher::PasswordHasher;
+use crate::services::user_service;
+use tokio::task;
+use tracing::warn;
+use utils::error::app_error::AppResult;
+
+fn denied() -> anyhow::Error {
+    ServiceError::Unauthorized("invalid username or password".to_string()).into()
+}
+
+/// `PasswordHasher::hash` off the runtime threads, it takes tens of
+/// milliseconds by design.
+async fn hash(hasher: &PasswordHasher, plain: &str) -> AppResult<(String, String)> {
+    let (hasher, plain) = (*hasher, plain.to_string());
+    task::spawn_blocking(move || hasher.hash(&plain)).await?
+}
+
+/// `PasswordHasher::verify` off the runtime threads, see `hash`.
+async fn verify(hasher: &PasswordHasher, user: &User, plain: &str) -> AppResult<bool> {
+    let hasher = *hasher;
+    let (stored, method, plain) = (
+        user.passwd().to_string(),
+        user.passwd_enc_method().to_string(),
+        plain.to_string(),
+    );
+    task::spawn_blocking(move || hasher.verify(&stored, &method, &plain)).await?
+}
+
+/// Replaces the password of `user` with a hash of `plain`, in memory only.
+pub async fn set_password(hasher: &PasswordHasher, user: &mut User, plain: &str) -> AppResult<()> {
+    if plain.is_empty() {
+        return Err(ServiceError::Validation("passwd must not be empty".to_string()).into());
+    }
+    let (passwd, method) = hash(hasher, plain).await?;
+    user.set_passwd(passwd);
+    user.set_passwd_enc_method(method);
+    Ok(())
+}
+
+/// The live user `username` if `plain` is their password. A password stored
+/// with an outdated method is hashed again with the configured one on the way.
+pub async fn login(
+    db: &Db,
+    hasher: &PasswordHasher,
+    username: &str,
+    plain: &str,
+) -> AppResult<User> {
+    // a replica may not have the user created or rehashed just before
+    let primary = db.primary();
+    let Some(mut user) = user_persistence_async::find_by_username(&primary, username).await? else {
+        // the same work as a wrong password, the timing tells no usernames
+        hash(hasher, plain).await?;
+        return Err(denied());
+    };
+    if !verify(hasher, &user, plain).await? {
+        return Err(denied());
+    }
+    if !hasher.needs_rehash(user.passwd_enc_method()) {
+        return Ok(user);
+    }
+    set_password(hasher, &mut user, plain).await?;
+    // the login stands even if a concurrent update wins, the next one rehashes
+    match user_persistence_async::update(db, &user).await {
+        Ok(true) => user_service::find_by_id(&primary, *user.id()).await,
+        Ok(false) => Err(denied()),
+        Err(err) => {
+            warn!("Rehash of user {} skipped: {:#}", user.id(), err);
+            user_service::find_by_id(&primary, *user.id()).await
+        }
+    }
+}
+
+/// Sets a new password once `current` is verified. `version` must be the one
+/// read last, see `AsyncRepository::VERSION_COLUMN`. Publishes no outbox event,
+/// nothing a `UserDto` shows changes but the version.
+pub async fn change_password(
+    db: &Db,
+    hasher: &PasswordHasher,
+    id: i64,
+    version: i32,
+    current: &str,
+    new: &str,
+) -> AppResult<User> {
+    let primary = db.primary();
+    let mut user = user_service::find_by_id(&primary, id).await?;
+    if !verify(hasher, &user, current).await? {
+        return Err(denied());
+    }
+    set_password(hasher, &mut user, new).await?;
+    user.set_version(version);
+    if !user_persistence_async::update(db, &user)
+        .await
+        .map_err(map_conflict)?
+    {
+        return Err(ServiceError::NotFound(format!("user {id}")).into());
+    }
+    user_service::find_by_id(&primary, id).await
+}
@@ -0,0 +1,71 @@
+use crate::persistence::query::{Field, ListQuery};
+use crate::persistence::repository::VersionConflict;
+use std::fmt::{Display, Formatter};
+use tokio_postgres::error::SqlState;
+use utils::validation::ValidationErrors;
+
+/// Business errors the presentation layer maps to HTTP status codes.
+/// Anything else carried by `AppResult` is treated as an internal error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServiceError {
+    NotFound(String),
+    Validation(String),
+    /// field by field, see `utils::validation`
+    InvalidFields(ValidationErrors),
+    Conflict(String),
+    Unauthorized(String),
+    Forbidden(String),
+}
+
+impl Display for ServiceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServiceError::NotFound(msg) => write!(f, "not found: {msg}"),
+            ServiceError::Validation(msg) => write!(f, "validation failed: {msg}"),
+            ServiceError::InvalidFields(errors) => write!(f, "validation failed: {errors}"),
+            ServiceError::Conflict(msg) => write!(f, "conflict: {msg}"),
+            ServiceError::Unauthorized(msg) => write!(f, "unauthorized: {msg}"),
+            ServiceError::Forbidden(msg) => write!(f, "forbidden: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for ServiceError {}
+
+/// A stale update, or a value a unique index already holds, e.g. a taken
+/// username, is the caller's conflict to resolve.
+pub fn map_conflict(err: anyhow::Error) -> anyhow::Error {
+    if let Some(conflict) = err.downcast_ref::<VersionConflict>() {
+        return ServiceError::Conflict(conflict.to_string()).into();
+    }
+    let unique = err
+        .downcast_ref::<tokio_postgres::Error>()
+        .and_then(|err| err.as_db_error())
+        .filter(|db_err| *db_err.code() == SqlState::UNIQUE_VIOLATION);
+    match unique {
+        // `Key (username)=(..) already exists.`
+        Some(db_err) => {
+            ServiceError::Conflict(db_err.detail().unwrap_or(db_err.message()).to_string()).into()
+        }
+        None => err,
+    }
+}
+
+/// A list query the caller got wrong, e.g. a value of the wrong type for its field.
+pub fn check_query<F: Field>(query: &ListQuery<F>) -> Result<(), ServiceError> {
+    check_page(query.page_size)?;
+    query
+        .check()
+        .map_err(|err| ServiceError::Validation(format!("{err:#}")))
+}
+
+pub const MAX_PAGE_SIZE: u32 = 1000;
+
+pub fn check_page(page_size: u32) -> Result<(), ServiceError> {
+    if page_size == 0 || page_size > MAX_PAGE_SIZE {
+        return Err(ServiceError::Validation(format!(
+            "page_size must be between 1 and {MAX_PAGE_SIZE}"
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use crate::models::domain_event::OutboxEvent;
+use anyhow::anyhow;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Mutex};
+use tracing::info;
+use utils::error::app_error::AppResult;
+
+pub type SinkFuture<'a> = Pin<Box<dyn Future<Output = AppResult<()>> + Send + 'a>>;
+
+/// Where `OutboxDispatcher` delivers events. An `Ok` counts the event as
+/// delivered; an `Err` has it tried again later, so the same event may
+/// arrive more than once.
+pub trait EventSink: Send + Sync {
+    /// for the logs and `OutboxEvent::last_error`
+    fn name(&self) -> &str;
+
+    fn deliver<'a>(&'a self, event: &'a OutboxEvent) -> SinkFuture<'a>;
+}
+
+/// The sinks listed in `spec`, comma separated: `log`, `file:<path>` or
+/// `tcp:<host>:<port>`, see `TcpSink` for what the peer answers. The channel
+/// sink only exists in code.
+pub fn parse_sinks(spec: &str) -> AppResult<Vec<Arc<dyn EventSink>>> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|sink| !sink.is_empty())
+        .map(|sink| -> AppResult<Arc<dyn EventSink>> {
+            match sink.split_once(':') {
+                None if sink == "log" => Ok(Arc::new(LogSink)),
+                Some(("file", path)) if !path.is_empty() => Ok(Arc::new(FileSink::new(path))),
+                Some(("tcp", address)) if address.contains(':') => {
+                    Ok(Arc::new(TcpSink::new(address)))
+                }
+                _ => Err(anyhow!(
+                    "unknown event sink {sink}, expected log, file:<path> or tcp:<host>:<port>"
+                )),
+            }
+        })
+        .collect()
+}
+
+/// One JSON object a line, as the file and TCP sinks write them.
+fn to_line(event: &OutboxEvent) -> AppResult<Vec<u8>> {
+    let mut line = serde_json::to_vec(event)?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+/// Logs every event at info level.
+pub struct LogSink;
+
+impl EventSink for LogSink {
+    fn name(&self) -> &str {
+        "log"
+    }
+
+    fn deliver<'a>(&'a self, event: &'a OutboxEvent) -> SinkFuture<'a> {
+        Box::pin(async move {
+            info!(
+                "Event {} {} {} {}: {}",
+                event.id,
+                event.event.aggregate,
+                event.event.aggregate_id,
+                event.event.event_type.name(),
+                event.event.payload
+            );
+            Ok(())
+        })
+    }
+}
+
+/// Appends the events to a file, synced before they count as delivered.
+pub struct FileSink {
+    name: String,
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl FileSink {
+    /// The file is created on the first event.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self {
+            name: format!("file:{}", path.display()),
+            path,
+            file: Mutex::new(None),
+        }
+    }
+}
+
+impl EventSink for FileSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn deliver<'a>(&'a self, event: &'a OutboxEvent) -> SinkFuture<'a> {
+        Box::pin(async move {
+            let line = to_line(event)?;
+            let mut file = self.file.lock().await;
+            if file.is_none() {
+                let opened = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+                    .await?;
+                *file = Some(opened);
+            }
+            let opened = file.as_mut().expect("opened above");
+            let rs = async {
+                opened.write_all(&line).await?;
+                opened.sync_data().await
+            }
+            .await;
+            if rs.is_err() {
+                // reopened on the next event, e.g. after the file was rotated away
+                *file = None;
+            }
+            Ok(rs?)
+        })
+    }
+}
+
+/// Hands the events to a consumer in the same process. A full channel holds
+/// the dispatcher back; a closed one fails the delivery.
+pub struct ChannelSink {
+    tx: mpsc::Sender<OutboxEvent>,
+}
+
+impl ChannelSink {
+    pub fn new(tx: mpsc::Sender<OutboxEvent>) -> Self {
+        Self { tx }
+    }
+}
+
+impl EventSink for ChannelSink {
+    fn name(&self) -> &str {
+        "channel"
+    }
+
+    fn deliver<'a>(&'a self, event: &'a OutboxEvent) -> SinkFuture<'a> {
+        Box::pin(async move {
+            self.tx
+                .send(event.clone())
+                .await
+                .map_err(|_| anyhow!("event channel closed"))
+        })
+    }
+}
+
+/// Writes the events to a TCP peer over one connection, opened on the first
+/// event and again after a failed delivery. An event counts as delivered once
+/// the peer answers its line with an `ok` line; anything else, or none,
+/// fails it.
+pub struct TcpSink {
+    name: String,
+    address: String,
+    stream: Mutex<Option<BufReader<TcpStream>>>,
+}
+
+impl TcpSink {
+    /// `address` is `host:port`.
+    pub fn new(address: &str) -> Self {
+        Self {
+            name: format!("tcp:{address}"),
+            address: address.to_string(),
+            stream: Mutex::new(None),
+        }
+    }
+}
+
+impl EventSink for TcpSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn deliver<'a>(&'a self, event: &'a OutboxEvent) -> SinkFuture<'a> {
+        Box::pin(async move {
+            let line = to_line(event)?;
+            let mut stream = self.stream.lock().await;
+            // taken until acknowledged, so a delivery cut short by a timeout
+            // leaves no answer behind for the next event
+            let mut connected = match stream.take() {
+                Some(connected) => connected,
+                None => BufReader::new(TcpStream::connect(&self.address).await?),
+            };
+            connected.get_mut().write_all(&line).await?;
+            connected.get_mut().flush().await?;
+            let mut ack = String::new();
+            if connected.read_line(&mut ack).await? == 0 {
+                return Err(anyhow!("peer closed the connection before acknowledging"));
+            }
+            if ack.trim_end() != "ok" {
+                return Err(anyhow!("peer answered {:?} instead of ok", ack.trim_end()));
+            }
+            *stream = Some(connected);
+            Ok(())
+        })
+    }
+}
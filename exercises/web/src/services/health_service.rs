@@ -0,0 +1,57 @@
+use crate::persistence::db::Db;
+use crate::persistence::migration::pending;
+use std::time::Duration;
+use tokio::time::timeout;
+use tracing::warn;
+use utils::error::app_error::AppResult;
+
+/// How long a probe waits for the primary; `DbConfig::connect_timeout` is
+/// meant for requests and is longer by default.
+pub const READY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What `/health/ready` reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Readiness {
+    /// the primary answered within `READY_TIMEOUT`
+    pub database: bool,
+    /// versions not applied yet, unknown when the primary is down
+    pub pending_migrations: Vec<i64>,
+    /// replicas in the rotation, of those configured; reads fall back to the
+    /// primary, so these do not make the service unready
+    pub replicas_up: usize,
+    pub replicas: usize,
+}
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        self.database && self.pending_migrations.is_empty()
+    }
+}
+
+/// One round trip to the primary and the migrations check `Lifecycle::start`
+/// makes, so a service that would not start is not sent traffic either.
+pub async fn readiness(db: &Db) -> Readiness {
+    let checked = match timeout(READY_TIMEOUT, check_primary(db)).await {
+        Ok(Ok(pending)) => Some(pending),
+        Ok(Err(err)) => {
+            warn!("Readiness: database is down: {}", err);
+            None
+        }
+        Err(_) => {
+            warn!("Readiness: database did not answer in {:?}", READY_TIMEOUT);
+            None
+        }
+    };
+    Readiness {
+        database: checked.is_some(),
+        pending_migrations: checked.unwrap_or_default(),
+        replicas_up: db.replicas_up(),
+        replicas: db.config().replica_configs().len(),
+    }
+}
+
+async fn check_primary(db: &Db) -> AppResult<Vec<i64>> {
+    let conn = db.async_conn().await?;
+    conn.simple_query("select 1").await?;
+    pending(&conn).await
+}
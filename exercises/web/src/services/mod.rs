@@ -1 +1,10 @@
-
+pub mod auth_service;
+pub mod credential_service;
+pub mod error;
+pub mod event_sink;
+pub mod health_service;
+pub mod outbox_dispatcher;
+pub mod password_hasher;
+pub mod sample_rec_service;
+pub mod token_service;
+pub mod user_service;
@@ -0,0 +1,236 @@
+use crate::models::domain_event::OutboxEvent;
+use crate::persistence::db::Db;
+use crate::persistence::db_config::{env_lookup, parse_var};
+use crate::persistence::outbox_persistence::{
+    claim_with, mark_delivered_with, mark_failed_with, release_with,
+};
+use crate::services::event_sink::{parse_sinks, EventSink, LogSink};
+use crate::utils::app_context::AppContext;
+use crate::utils::lifecycle::Shutdown;
+use crate::utils::metrics::OUTBOX_EVENTS;
+use anyhow::anyhow;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::{sleep, timeout};
+use tracing::{error, warn};
+use utils::error::app_error::AppResult;
+
+/// Delivers the events of `persistence::outbox_persistence` to every sink,
+/// at least once and oldest first.
+///
+/// Each round leases a batch in one statement, hands every event to the sinks
+/// in turn outside any transaction and marks it in a statement of its own.
+/// The round stops before the next event once its `lease` is over, the rest
+/// are due again then, and on shutdown, releasing the rest. An event some sink failed
+/// is tried again, by every sink, after `retry_base` doubled per attempt up
+/// to `retry_max`; the `max_attempts`th failure makes it dead, see
+/// `outbox_persistence::requeue`. An event being retried does not hold back
+/// the ones after it, so the order across retries is not kept. Dispatchers
+/// in other processes claim other events; a sink slower than the lease may
+/// see an event twice.
+///
+/// | variable                       | default |
+/// |--------------------------------|---------|
+/// | `OUTBOX_SINKS`                 | `log`   |
+/// | `OUTBOX_POLL_MILLIS`           | `1000`  |
+/// | `OUTBOX_BATCH_SIZE`            | `100`   |
+/// | `OUTBOX_MAX_ATTEMPTS`          | `10`    |
+/// | `OUTBOX_RETRY_BASE_MILLIS`     | `1000`  |
+/// | `OUTBOX_RETRY_MAX_SECS`        | `300`   |
+/// | `OUTBOX_DELIVERY_TIMEOUT_SECS` | `10`    |
+/// | `OUTBOX_LEASE_SECS`            | `60`    |
+///
+/// `OUTBOX_SINKS` is a list, see `event_sink::parse_sinks`.
+#[derive(Clone)]
+pub struct OutboxDispatcher {
+    pub poll_interval: Duration,
+    pub batch_size: u32,
+    pub max_attempts: i32,
+    pub retry_base: Duration,
+    pub retry_max: Duration,
+    /// per event and sink, a hung sink counts as a failed one
+    pub delivery_timeout: Duration,
+    /// how long a claimed batch is left to this dispatcher
+    pub lease: Duration,
+    sinks: Vec<Arc<dyn EventSink>>,
+}
+
+/// How one round went, see `OutboxDispatcher::dispatch`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Dispatched {
+    pub delivered: usize,
+    pub retried: usize,
+    pub dead: usize,
+}
+
+impl Dispatched {
+    pub fn claimed(&self) -> usize {
+        self.delivered + self.retried + self.dead
+    }
+}
+
+impl Default for OutboxDispatcher {
+    /// To the log only.
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(1000),
+            batch_size: 100,
+            max_attempts: 10,
+            retry_base: Duration::from_millis(1000),
+            retry_max: Duration::from_secs(300),
+            delivery_timeout: Duration::from_secs(10),
+            lease: Duration::from_secs(60),
+            sinks: vec![Arc::new(LogSink)],
+        }
+    }
+}
+
+impl OutboxDispatcher {
+    pub fn load() -> AppResult<Self> {
+        Self::from_lookup(env_lookup())
+    }
+
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> AppResult<Self> {
+        let parsed = |key: &str, default: &str| parse_var::<u64>(&lookup, key, default);
+        let max_attempts = parsed("OUTBOX_MAX_ATTEMPTS", "10")?;
+        if max_attempts == 0 || max_attempts > i32::MAX as u64 {
+            return Err(anyhow!(
+                "OUTBOX_MAX_ATTEMPTS={max_attempts} is out of range"
+            ));
+        }
+        let lease = Duration::from_secs(parsed("OUTBOX_LEASE_SECS", "60")?);
+        if lease.is_zero() {
+            return Err(anyhow!("OUTBOX_LEASE_SECS must be more than 0"));
+        }
+        Ok(Self {
+            poll_interval: Duration::from_millis(parsed("OUTBOX_POLL_MILLIS", "1000")?),
+            batch_size: parsed("OUTBOX_BATCH_SIZE", "100")?.clamp(1, u32::MAX as u64) as u32,
+            max_attempts: max_attempts as i32,
+            retry_base: Duration::from_millis(parsed("OUTBOX_RETRY_BASE_MILLIS", "1000")?),
+            retry_max: Duration::from_secs(parsed("OUTBOX_RETRY_MAX_SECS", "300")?),
+            delivery_timeout: Duration::from_secs(parsed("OUTBOX_DELIVERY_TIMEOUT_SECS", "10")?),
+            lease,
+            sinks: parse_sinks(&lookup("OUTBOX_SINKS").unwrap_or_else(|| "log".to_string()))?,
+        })
+    }
+
+    /// Replaces the sinks.
+    pub fn with_sinks(mut self, sinks: Vec<Arc<dyn EventSink>>) -> Self {
+        self.sinks = sinks;
+        self
+    }
+
+    pub fn with_sink(mut self, sink: impl EventSink + 'static) -> Self {
+        self.sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// The wait before the next attempt of an event that failed `attempts` times before.
+    pub fn retry_in(&self, attempts: i32) -> Duration {
+        let factor = 1u32 << attempts.clamp(0, 20);
+        self.retry_base.saturating_mul(factor).min(self.retry_max)
+    }
+
+    /// One round, see above.
+    pub async fn dispatch(&self, db: &Db) -> AppResult<Dispatched> {
+        self.dispatch_until(db, || false).await
+    }
+
+    /// One round that stops before the next event once `stop` is true,
+    /// releasing the events not tried.
+    pub async fn dispatch_until(&self, db: &Db, stop: impl Fn() -> bool) -> AppResult<Dispatched> {
+        let leased_at = Instant::now();
+        let events = claim_with(&*db.async_conn().await?, self.batch_size, self.lease).await?;
+        let mut dispatched = Dispatched::default();
+        for (i, event) in events.iter().enumerate() {
+            if leased_at.elapsed() >= self.lease {
+                warn!(
+                    "Outbox lease of {:?} is over, {} events left to the next round",
+                    self.lease,
+                    events.len() - i
+                );
+                break;
+            }
+            if stop() {
+                let ids = events[i..].iter().map(|event| event.id).collect::<Vec<_>>();
+                release_with(&*db.async_conn().await?, &ids).await?;
+                break;
+            }
+            let delivered = self.deliver(event).await;
+            let conn = db.async_conn().await?;
+            match delivered {
+                Ok(()) => {
+                    mark_delivered_with(&*conn, event.id).await?;
+                    dispatched.delivered += 1;
+                    OUTBOX_EVENTS.inc(&["delivered"]);
+                }
+                Err(err) if event.attempts + 1 < self.max_attempts => {
+                    let retry_in = self.retry_in(event.attempts);
+                    warn!(
+                        "Event {} failed, retry {} in {:?}: {:#}",
+                        event.id,
+                        event.attempts + 1,
+                        retry_in,
+                        err
+                    );
+                    mark_failed_with(&*conn, event.id, &format!("{err:#}"), Some(retry_in)).await?;
+                    dispatched.retried += 1;
+                    OUTBOX_EVENTS.inc(&["retried"]);
+                }
+                Err(err) => {
+                    error!(
+                        "Event {} failed {} times, dead: {:#}",
+                        event.id, self.max_attempts, err
+                    );
+                    mark_failed_with(&*conn, event.id, &format!("{err:#}"), None).await?;
+                    dispatched.dead += 1;
+                    OUTBOX_EVENTS.inc(&["dead"]);
+                }
+            }
+        }
+        Ok(dispatched)
+    }
+
+    async fn deliver(&self, event: &OutboxEvent) -> AppResult<()> {
+        for sink in &self.sinks {
+            match timeout(self.delivery_timeout, sink.deliver(event)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => return Err(err.context(format!("sink {}", sink.name()))),
+                Err(_) => {
+                    return Err(anyhow!(
+                        "sink {} timed out after {:?}",
+                        sink.name(),
+                        self.delivery_timeout
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Rounds until `shutdown`, the next one at once after a full batch and
+    /// after `poll_interval` otherwise. A round in progress stops before its
+    /// next event.
+    /// Meant for `Lifecycle::worker`.
+    pub async fn run(self, ctx: AppContext, shutdown: Shutdown) -> AppResult<()> {
+        while !shutdown.is_triggered() {
+            let full = match self
+                .dispatch_until(&ctx.db, || shutdown.is_triggered())
+                .await
+            {
+                Ok(dispatched) => dispatched.claimed() >= self.batch_size as usize,
+                Err(err) => {
+                    warn!("Outbox round failed: {:#}", err);
+                    false
+                }
+            };
+            if !full {
+                tokio::select! {
+                    _ = shutdown.wait() => {}
+                    _ = sleep(self.poll_interval) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
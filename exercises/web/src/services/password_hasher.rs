@@ -0,0 +1,235 @@
+use crate::persistence::db_config::{env_lookup, parse_var};
+use anyhow::anyhow;
+use argon2::{Algorithm, Argon2, Params, Version};
+use openssl::base64::{decode_block, encode_block};
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkcs5::pbkdf2_hmac;
+use openssl::rand::rand_bytes;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use utils::error::app_error::AppResult;
+
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+
+/// How a `User::passwd` was derived, stored as text in `User::passwd_enc_method`
+/// together with its cost parameters, e.g. `pbkdf2-sha256$i=600000` or
+/// `argon2id$v=19$m=19456,t=2,p=1`. The derived password itself is kept as
+/// `base64(salt)$base64(hash)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashMethod {
+    /// legacy rows that hold the password itself, only ever verified
+    Plain,
+    Pbkdf2Sha256 {
+        iterations: u32,
+    },
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+}
+
+impl HashMethod {
+    fn argon2(memory_kib: u32, iterations: u32, parallelism: u32) -> AppResult<Argon2<'static>> {
+        let params = Params::new(memory_kib, iterations, parallelism, Some(HASH_LEN))
+            .map_err(|err| anyhow!("invalid argon2id parameters: {err}"))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+
+    fn check(&self) -> AppResult<()> {
+        match *self {
+            HashMethod::Plain => Ok(()),
+            HashMethod::Pbkdf2Sha256 { iterations: 0 } => {
+                Err(anyhow!("pbkdf2-sha256 needs at least one iteration"))
+            }
+            HashMethod::Pbkdf2Sha256 { .. } => Ok(()),
+            HashMethod::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => Self::argon2(memory_kib, iterations, parallelism).map(|_| ()),
+        }
+    }
+
+    fn derive(&self, plain: &[u8], salt: &[u8], len: usize) -> AppResult<Vec<u8>> {
+        let mut out = vec![0u8; len];
+        match *self {
+            HashMethod::Plain => return Ok(plain.to_vec()),
+            HashMethod::Pbkdf2Sha256 { iterations } => pbkdf2_hmac(
+                plain,
+                salt,
+                iterations as usize,
+                MessageDigest::sha256(),
+                &mut out,
+            )?,
+            HashMethod::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => Self::argon2(memory_kib, iterations, parallelism)?
+                .hash_password_into(plain, salt, &mut out)
+                .map_err(|err| anyhow!("argon2id failed: {err}"))?,
+        }
+        Ok(out)
+    }
+}
+
+impl Display for HashMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashMethod::Plain => f.write_str("plain"),
+            HashMethod::Pbkdf2Sha256 { iterations } => write!(f, "pbkdf2-sha256$i={iterations}"),
+            HashMethod::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => write!(
+                f,
+                "argon2id$v=19$m={memory_kib},t={iterations},p={parallelism}"
+            ),
+        }
+    }
+}
+
+impl FromStr for HashMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("unknown password method {s}");
+        // `a=1,b=2` into its numbers
+        let params = |text: &str| -> AppResult<HashMap<String, u32>> {
+            text.split(',')
+                .map(|param| {
+                    let (key, val) = param.split_once('=').ok_or_else(invalid)?;
+                    Ok((key.to_string(), val.parse::<u32>().map_err(|_| invalid())?))
+                })
+                .collect()
+        };
+        let parts = s.split('$').collect::<Vec<_>>();
+        let method = match parts.as_slice() {
+            ["plain"] => HashMethod::Plain,
+            ["pbkdf2-sha256", text] => {
+                let params = params(text)?;
+                HashMethod::Pbkdf2Sha256 {
+                    iterations: *params.get("i").ok_or_else(invalid)?,
+                }
+            }
+            ["argon2id", "v=19", text] => {
+                let params = params(text)?;
+                let param = |key: &str| params.get(key).copied().ok_or_else(invalid);
+                HashMethod::Argon2id {
+                    memory_kib: param("m")?,
+                    iterations: param("t")?,
+                    parallelism: param("p")?,
+                }
+            }
+            _ => return Err(invalid()),
+        };
+        method.check()?;
+        Ok(method)
+    }
+}
+
+/// Hashes new passwords with the configured method and verifies stored ones
+/// with whatever method they were hashed with, read from `.env.{ENV}` like
+/// `DbConfig`. `PASSWD_HASH_METHOD` is `argon2id` or `pbkdf2-sha256`.
+///
+/// | variable                    | default    |
+/// |-----------------------------|------------|
+/// | `PASSWD_HASH_METHOD`        | `argon2id` |
+/// | `PASSWD_PBKDF2_ITERATIONS`  | `600000`   |
+/// | `PASSWD_ARGON2_MEMORY_KIB`  | `19456`    |
+/// | `PASSWD_ARGON2_ITERATIONS`  | `2`        |
+/// | `PASSWD_ARGON2_PARALLELISM` | `1`        |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordHasher {
+    method: HashMethod,
+}
+
+impl Default for PasswordHasher {
+    /// The OWASP recommended argon2id parameters.
+    fn default() -> Self {
+        Self {
+            method: HashMethod::Argon2id {
+                memory_kib: 19456,
+                iterations: 2,
+                parallelism: 1,
+            },
+        }
+    }
+}
+
+impl PasswordHasher {
+    pub fn new(method: HashMethod) -> AppResult<Self> {
+        if method == HashMethod::Plain {
+            return Err(anyhow!("passwords are never stored as plain text"));
+        }
+        method.check()?;
+        Ok(Self { method })
+    }
+
+    pub fn load() -> AppResult<Self> {
+        Self::from_lookup(env_lookup())
+    }
+
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> AppResult<Self> {
+        let parsed = |key: &str, default: &str| parse_var::<u32>(&lookup, key, default);
+        let method = match lookup("PASSWD_HASH_METHOD").as_deref() {
+            None | Some("argon2id") => HashMethod::Argon2id {
+                memory_kib: parsed("PASSWD_ARGON2_MEMORY_KIB", "19456")?,
+                iterations: parsed("PASSWD_ARGON2_ITERATIONS", "2")?,
+                parallelism: parsed("PASSWD_ARGON2_PARALLELISM", "1")?,
+            },
+            Some("pbkdf2-sha256") => HashMethod::Pbkdf2Sha256 {
+                iterations: parsed("PASSWD_PBKDF2_ITERATIONS", "600000")?,
+            },
+            Some(other) => return Err(anyhow!("unknown PASSWD_HASH_METHOD {other}")),
+        };
+        Self::new(method)
+    }
+
+    pub fn method(&self) -> HashMethod {
+        self.method
+    }
+
+    /// A fresh salt and hash of `plain`, with the method string to store next to it.
+    pub fn hash(&self, plain: &str) -> AppResult<(String, String)> {
+        let mut salt = [0u8; SALT_LEN];
+        rand_bytes(&mut salt)?;
+        let hash = self.method.derive(plain.as_bytes(), &salt, HASH_LEN)?;
+        Ok((
+            format!("{}${}", encode_block(&salt), encode_block(&hash)),
+            self.method.to_string(),
+        ))
+    }
+
+    /// Whether `plain` is the password `stored` was derived from with `method`,
+    /// compared in constant time. A stored value that does not parse is an error,
+    /// not a mismatch.
+    pub fn verify(&self, stored: &str, method: &str, plain: &str) -> AppResult<bool> {
+        let method = method.parse::<HashMethod>()?;
+        let (salt, expected) = match method {
+            HashMethod::Plain => (vec![], stored.as_bytes().to_vec()),
+            _ => {
+                let (salt, hash) = stored
+                    .split_once('$')
+                    .ok_or_else(|| anyhow!("stored password is not salt$hash"))?;
+                (decode_block(salt)?, decode_block(hash)?)
+            }
+        };
+        let actual = method.derive(plain.as_bytes(), &salt, expected.len())?;
+        // only the legacy plain text can differ in length
+        Ok(actual.len() == expected.len() && memcmp::eq(&actual, &expected))
+    }
+
+    /// Whether a password stored with `method` should be hashed again with the
+    /// configured one, e.g. after the algorithm or its cost changed.
+    pub fn needs_rehash(&self, method: &str) -> bool {
+        method
+            .parse::<HashMethod>()
+            .map_or(true, |method| method != self.method)
+    }
+}
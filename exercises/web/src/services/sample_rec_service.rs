@@ -0,0 +1,135 @@
+use crate::dto::sample_rec_dto::SampleRecordDto;
+use crate::models::domain_event::{DomainEvent, EventType};
+use crate::models::sample_rec::SampleRecord;
+use crate::persistence::db::Db;
+use crate::persistence::outbox_persistence;
+use crate::persistence::page::SortOrder;
+use crate::persistence::query::ListQuery;
+use crate::persistence::repository::AsyncRepository;
+use crate::persistence::sample_rec_persistence_async;
+use crate::persistence::sample_rec_persistence_async::{SampleRecordField, SampleRecordRepository};
+use crate::persistence::tx::Tx;
+use crate::services::error::{check_page, check_query, map_conflict, ServiceError};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use tokio_stream::Stream;
+use utils::error::app_error::AppResult;
+
+/// `DomainEvent::aggregate` of the events of every write below.
+pub const AGGREGATE: &str = "sample_record";
+
+fn validate(val: &SampleRecord) -> Result<(), ServiceError> {
+    if val.name().trim().is_empty() {
+        return Err(ServiceError::Validation(
+            "name must not be empty".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn find(db: &Db, page_no: u32, page_size: u32) -> AppResult<Vec<SampleRecord>> {
+    check_page(page_size)?;
+    SampleRecordRepository::find(db, page_no, page_size).await
+}
+
+pub async fn select(
+    db: &Db,
+    query: &ListQuery<SampleRecordField>,
+) -> AppResult<Vec<Map<String, Value>>> {
+    check_query(query)?;
+    sample_rec_persistence_async::select(db, query).await
+}
+
+pub async fn stream_all(
+    db: &Db,
+    order: SortOrder,
+) -> AppResult<impl Stream<Item = AppResult<SampleRecord>>> {
+    sample_rec_persistence_async::stream_all(db, order).await
+}
+
+pub async fn find_by_id(db: &Db, _id: i64) -> AppResult<SampleRecord> {
+    SampleRecordRepository::find_by_id(db, &_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("sample record {_id}")).into())
+}
+
+/// Returns the record as stored, with the timestamp and version persistence set.
+pub async fn create(db: &Db, val: &SampleRecord) -> AppResult<SampleRecord> {
+    validate(val)?;
+    let tx = db.begin().await?;
+    let rs = create_in(&tx, val).await;
+    tx.finish(rs).await
+}
+
+/// `val.version` must be the one read last, see `AsyncRepository::VERSION_COLUMN`.
+pub async fn update(db: &Db, val: &SampleRecord) -> AppResult<SampleRecord> {
+    validate(val)?;
+    let tx = db.begin().await?;
+    let rs = update_in(&tx, val).await;
+    tx.finish(rs).await
+}
+
+pub async fn delete(db: &Db, _id: i64) -> AppResult<()> {
+    let tx = db.begin().await?;
+    let rs = delete_in(&tx, _id).await;
+    tx.finish(rs).await
+}
+
+// ----- the writes above, each with its event in one `Tx` -----
+
+async fn create_in(tx: &Tx, val: &SampleRecord) -> AppResult<SampleRecord> {
+    if SampleRecordRepository::exists_with(tx.client(), val.id()).await? {
+        // a soft deleted row keeps its key until purged
+        let state = match SampleRecordRepository::find_by_id_with(tx.client(), val.id()).await? {
+            Some(_) => "already exists",
+            None => "is deleted, restore it instead",
+        };
+        return Err(ServiceError::Conflict(format!("sample record {} {state}", val.id())).into());
+    }
+    SampleRecordRepository::insert_with(tx.client(), val)
+        .await
+        .map_err(map_conflict)?;
+    let stored = find_by_id_with(tx, *val.id()).await?;
+    let payload = SampleRecordDto::from(&stored);
+    publish(tx, *val.id(), EventType::Created, &payload).await?;
+    Ok(stored)
+}
+
+async fn update_in(tx: &Tx, val: &SampleRecord) -> AppResult<SampleRecord> {
+    if SampleRecordRepository::update_with(tx.client(), val)
+        .await
+        .map_err(map_conflict)?
+        == 0
+    {
+        return Err(ServiceError::NotFound(format!("sample record {}", val.id())).into());
+    }
+    let stored = find_by_id_with(tx, *val.id()).await?;
+    let payload = SampleRecordDto::from(&stored);
+    publish(tx, *val.id(), EventType::Updated, &payload).await?;
+    Ok(stored)
+}
+
+async fn delete_in(tx: &Tx, _id: i64) -> AppResult<()> {
+    if SampleRecordRepository::delete_with(tx.client(), &_id).await? == 0 {
+        return Err(ServiceError::NotFound(format!("sample record {_id}")).into());
+    }
+    publish(tx, _id, EventType::Deleted, &json!({ "id": _id })).await
+}
+
+async fn find_by_id_with(tx: &Tx, _id: i64) -> AppResult<SampleRecord> {
+    SampleRecordRepository::find_by_id_with(tx.client(), &_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("sample record {_id}")).into())
+}
+
+/// In the transaction of the change, see `persistence::outbox_persistence`.
+async fn publish(
+    tx: &Tx,
+    _id: i64,
+    event_type: EventType,
+    payload: &impl Serialize,
+) -> AppResult<()> {
+    let event = DomainEvent::new(AGGREGATE, _id, event_type, payload)?;
+    outbox_persistence::insert_with(tx.client(), &event).await?;
+    Ok(())
+}
@@ -0,0 +1,267 @@
+use crate::models::user::User;
+use crate::models::user_status::UserStatus;
+use crate::persistence::db_config::{env_lookup, parse_var};
+use crate::services::error::ServiceError;
+use anyhow::anyhow;
+use chrono::Utc;
+use macros::ApiSchema;
+use openssl::base64::{decode_block, encode_block};
+use openssl::bn::BigNum;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::nid::Nid;
+use openssl::pkey::{Private, Public};
+use openssl::rand::rand_bytes;
+use openssl::sha::sha256;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::warn;
+use utils::error::app_error::AppResult;
+
+/// P-256 coordinates, and the halves of a JOSE ES256 signature
+const COORD_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenUse {
+    Access,
+    Refresh,
+}
+
+/// The payload of both token kinds; `sub` is the user id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Claims {
+    pub iss: String,
+    pub aud: String,
+    pub sub: String,
+    pub iat: i64,
+    pub exp: i64,
+    pub jti: String,
+    pub token_use: TokenUse,
+    pub username: String,
+    pub status: UserStatus,
+}
+
+/// Who a request is made by, see `require_auth` for where the status comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub user_id: i64,
+    pub username: String,
+    pub status: UserStatus,
+}
+
+impl Principal {
+    pub fn of(user: &User) -> Self {
+        Self {
+            user_id: *user.id(),
+            username: user.username().clone(),
+            status: user.user_status(),
+        }
+    }
+
+    /// Disabled accounts never pass, whatever `status` is asked for.
+    pub fn require(&self, status: UserStatus) -> Result<(), ServiceError> {
+        if !self.status.is_enabled() || self.status < status {
+            return Err(ServiceError::Forbidden(format!(
+                "{} is not {status:?}",
+                self.username
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// What login and refresh hand out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ApiSchema)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    /// seconds the access token is good for
+    pub expires_in: u64,
+}
+
+/// Signs and checks ES256 JWTs with a P-256 key, read from `.env.{ENV}` like
+/// `DbConfig`. Without `JWT_PRIVATE_KEY_FILE` a key is generated on start,
+/// so the tokens handed out do not survive a restart.
+///
+/// | variable               | default            |
+/// |------------------------|--------------------|
+/// | `JWT_PRIVATE_KEY_FILE` | generated          |
+/// | `JWT_ISSUER`           | `web`              |
+/// | `JWT_AUDIENCE`         | `web`              |
+/// | `JWT_ACCESS_TTL_SECS`  | `900`              |
+/// | `JWT_REFRESH_TTL_SECS` | `1209600`, 14 days |
+#[derive(Clone)]
+pub struct TokenService {
+    signing_key: EcKey<Private>,
+    verifying_key: EcKey<Public>,
+    pub issuer: String,
+    pub audience: String,
+    pub access_ttl: Duration,
+    pub refresh_ttl: Duration,
+}
+
+impl TokenService {
+    /// A fresh key and the default settings.
+    pub fn generate() -> AppResult<Self> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        Self::with_key(EcKey::generate(&group)?)
+    }
+
+    /// `pem` is a P-256 private key, PKCS#8 or SEC1.
+    pub fn from_pem(pem: &[u8]) -> AppResult<Self> {
+        Self::with_key(EcKey::private_key_from_pem(pem)?)
+    }
+
+    fn with_key(signing_key: EcKey<Private>) -> AppResult<Self> {
+        if signing_key.group().curve_name() != Some(Nid::X9_62_PRIME256V1) {
+            return Err(anyhow!("ES256 needs a P-256 key"));
+        }
+        let verifying_key = EcKey::from_public_key(signing_key.group(), signing_key.public_key())?;
+        Ok(Self {
+            signing_key,
+            verifying_key,
+            issuer: "web".to_string(),
+            audience: "web".to_string(),
+            access_ttl: Duration::from_secs(900),
+            refresh_ttl: Duration::from_secs(14 * 24 * 3600),
+        })
+    }
+
+    pub fn load() -> AppResult<Self> {
+        Self::from_lookup(env_lookup())
+    }
+
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> AppResult<Self> {
+        let parsed = |key: &str, default: &str| parse_var::<u64>(&lookup, key, default);
+        let mut service = match lookup("JWT_PRIVATE_KEY_FILE").map(PathBuf::from) {
+            Some(path) => Self::from_pem(&fs::read(&path).map_err(|err| {
+                anyhow!(
+                    "can not read JWT_PRIVATE_KEY_FILE {}: {err}",
+                    path.display()
+                )
+            })?)?,
+            None => {
+                warn!("JWT_PRIVATE_KEY_FILE is not configured, tokens will not survive a restart");
+                Self::generate()?
+            }
+        };
+        service.issuer = lookup("JWT_ISSUER").unwrap_or_else(|| "web".to_string());
+        service.audience = lookup("JWT_AUDIENCE").unwrap_or_else(|| "web".to_string());
+        service.access_ttl = Duration::from_secs(parsed("JWT_ACCESS_TTL_SECS", "900")?);
+        service.refresh_ttl = Duration::from_secs(parsed("JWT_REFRESH_TTL_SECS", "1209600")?);
+        Ok(service)
+    }
+
+    /// The claims of a new token for `principal`, good for the ttl of `token_use`.
+    pub fn claims(&self, principal: &Principal, token_use: TokenUse) -> AppResult<Claims> {
+        let ttl = match token_use {
+            TokenUse::Access => self.access_ttl,
+            TokenUse::Refresh => self.refresh_ttl,
+        };
+        let mut jti = [0u8; 16];
+        rand_bytes(&mut jti)?;
+        let iat = Utc::now().timestamp();
+        Ok(Claims {
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+            sub: principal.user_id.to_string(),
+            iat,
+            exp: iat + ttl.as_secs() as i64,
+            jti: base64_url(&jti),
+            token_use,
+            username: principal.username.clone(),
+            status: principal.status,
+        })
+    }
+
+    pub fn sign(&self, claims: &Claims) -> AppResult<String> {
+        let header = json!({ "alg": "ES256", "typ": "JWT" });
+        let input = format!(
+            "{}.{}",
+            base64_url(header.to_string().as_bytes()),
+            base64_url(&serde_json::to_vec(claims)?)
+        );
+        let sig = EcdsaSig::sign(&sha256(input.as_bytes()), &self.signing_key)?;
+        let mut raw = sig.r().to_vec_padded(COORD_LEN as i32)?;
+        raw.extend(sig.s().to_vec_padded(COORD_LEN as i32)?);
+        Ok(format!("{input}.{}", base64_url(&raw)))
+    }
+
+    /// The claims of `token` if it is signed by this service's key, for this
+    /// issuer and audience, of the `token_use` asked for and not expired.
+    pub fn verify(&self, token: &str, token_use: TokenUse) -> Result<Claims, ServiceError> {
+        let invalid = |why: &str| ServiceError::Unauthorized(format!("invalid token: {why}"));
+        let (input, sig) = token.rsplit_once('.').ok_or_else(|| invalid("malformed"))?;
+        let (header, payload) = input.split_once('.').ok_or_else(|| invalid("malformed"))?;
+        let header = from_base64_url(header)
+            .and_then(|header| Ok(serde_json::from_slice::<serde_json::Value>(&header)?))
+            .map_err(|_| invalid("malformed header"))?;
+        // the algorithm is fixed, never taken from the token
+        if header["alg"] != "ES256" {
+            return Err(invalid("unexpected algorithm"));
+        }
+        let raw = from_base64_url(sig).map_err(|_| invalid("malformed signature"))?;
+        if raw.len() != 2 * COORD_LEN || !self.check_signature(input, &raw) {
+            return Err(invalid("bad signature"));
+        }
+        let claims = from_base64_url(payload)
+            .and_then(|payload| Ok(serde_json::from_slice::<Claims>(&payload)?))
+            .map_err(|_| invalid("malformed claims"))?;
+        if claims.iss != self.issuer {
+            return Err(invalid("unexpected issuer"));
+        }
+        if claims.aud != self.audience {
+            return Err(invalid("unexpected audience"));
+        }
+        if claims.token_use != token_use {
+            return Err(invalid("unexpected token use"));
+        }
+        if claims.exp <= Utc::now().timestamp() {
+            return Err(invalid("expired"));
+        }
+        Ok(claims)
+    }
+
+    fn check_signature(&self, input: &str, raw: &[u8]) -> bool {
+        let sig = BigNum::from_slice(&raw[..COORD_LEN]).and_then(|r| {
+            EcdsaSig::from_private_components(r, BigNum::from_slice(&raw[COORD_LEN..])?)
+        });
+        sig.and_then(|sig| sig.verify(&sha256(input.as_bytes()), &self.verifying_key))
+            .unwrap_or(false)
+    }
+
+    /// The principal of a valid access token.
+    pub fn authenticate(&self, token: &str) -> Result<Principal, ServiceError> {
+        let claims = self.verify(token, TokenUse::Access)?;
+        Ok(Principal {
+            user_id: claims.sub.parse().map_err(|_| {
+                ServiceError::Unauthorized("invalid token: bad subject".to_string())
+            })?,
+            username: claims.username,
+            status: claims.status,
+        })
+    }
+}
+
+fn base64_url(bytes: &[u8]) -> String {
+    encode_block(bytes)
+        .trim_end_matches('=')
+        .replace('+', "-")
+        .replace('/', "_")
+}
+
+fn from_base64_url(text: &str) -> AppResult<Vec<u8>> {
+    if text.contains(['+', '/', '=']) {
+        return Err(anyhow!("not base64url"));
+    }
+    let mut padded = text.replace('-', "+").replace('_', "/");
+    while !padded.len().is_multiple_of(4) {
+        padded.push('=');
+    }
+    Ok(decode_block(&padded)?)
+}
@@ -0,0 +1,133 @@
+use crate::dto::user_dto::UserDto;
+use crate::models::domain_event::{DomainEvent, EventType};
+use crate::models::user::User;
+use crate::persistence::db::Db;
+use crate::persistence::outbox_persistence;
+use crate::persistence::query::ListQuery;
+use crate::persistence::repository::AsyncRepository;
+use crate::persistence::tx::Tx;
+use crate::persistence::user_persistence_async;
+use crate::persistence::user_persistence_async::{UserField, UserRepository};
+use crate::services::credential_service::set_password;
+use crate::services::error::{check_page, check_query, map_conflict, ServiceError};
+use crate::services::password_hasher::PasswordHasher;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use utils::error::app_error::AppResult;
+
+/// `DomainEvent::aggregate` of the events of every write below.
+pub const AGGREGATE: &str = "user";
+
+fn validate(user: &User) -> Result<(), ServiceError> {
+    if user.username().trim().is_empty() {
+        return Err(ServiceError::Validation(
+            "username must not be empty".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn find(db: &Db, page_no: u32, page_size: u32) -> AppResult<Vec<User>> {
+    check_page(page_size)?;
+    user_persistence_async::find(db, page_no, page_size).await
+}
+
+pub async fn select(db: &Db, query: &ListQuery<UserField>) -> AppResult<Vec<Map<String, Value>>> {
+    check_query(query)?;
+    user_persistence_async::select(db, query).await
+}
+
+pub async fn find_by_id(db: &Db, _id: i64) -> AppResult<User> {
+    user_persistence_async::find_by_id(db, _id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("user {_id}")).into())
+}
+
+/// `user.passwd` is the plain password, stored hashed with `hasher`. Returns the
+/// user as stored, with the timestamps and version persistence set.
+pub async fn create(db: &Db, hasher: &PasswordHasher, user: &User) -> AppResult<User> {
+    validate(user)?;
+    let mut user = user.clone();
+    let plain = user.passwd().clone();
+    set_password(hasher, &mut user, &plain).await?;
+    let tx = db.begin().await?;
+    let rs = create_in(&tx, &user).await;
+    tx.finish(rs).await
+}
+
+/// `user.version` must be the one read last, see `AsyncRepository::VERSION_COLUMN`.
+/// The password is kept, see `credential_service::change_password`.
+pub async fn update(db: &Db, user: &User) -> AppResult<User> {
+    validate(user)?;
+    let tx = db.begin().await?;
+    let rs = update_in(&tx, user).await;
+    tx.finish(rs).await
+}
+
+pub async fn delete(db: &Db, _id: i64) -> AppResult<()> {
+    let tx = db.begin().await?;
+    let rs = delete_in(&tx, _id).await;
+    tx.finish(rs).await
+}
+
+// ----- the writes above, each with its event in one `Tx` -----
+
+async fn create_in(tx: &Tx, user: &User) -> AppResult<User> {
+    if UserRepository::exists_with(tx.client(), user.id()).await? {
+        // a soft deleted row keeps its key until purged
+        let state = match UserRepository::find_by_id_with(tx.client(), user.id()).await? {
+            Some(_) => "already exists",
+            None => "is deleted, restore it instead",
+        };
+        return Err(ServiceError::Conflict(format!("user {} {state}", user.id())).into());
+    }
+    UserRepository::insert_with(tx.client(), user)
+        .await
+        .map_err(map_conflict)?;
+    let stored = find_by_id_with(tx, *user.id()).await?;
+    publish(tx, *user.id(), EventType::Created, &UserDto::from(&stored)).await?;
+    Ok(stored)
+}
+
+async fn update_in(tx: &Tx, user: &User) -> AppResult<User> {
+    let stored = find_by_id_with(tx, *user.id()).await?;
+    let mut user = user.clone();
+    user.set_passwd(stored.passwd().clone());
+    user.set_passwd_enc_method(stored.passwd_enc_method().clone());
+    if UserRepository::update_with(tx.client(), &user)
+        .await
+        .map_err(map_conflict)?
+        == 0
+    {
+        return Err(ServiceError::NotFound(format!("user {}", user.id())).into());
+    }
+    let stored = find_by_id_with(tx, *user.id()).await?;
+    publish(tx, *user.id(), EventType::Updated, &UserDto::from(&stored)).await?;
+    Ok(stored)
+}
+
+async fn delete_in(tx: &Tx, _id: i64) -> AppResult<()> {
+    if UserRepository::delete_with(tx.client(), &_id).await? == 0 {
+        return Err(ServiceError::NotFound(format!("user {_id}")).into());
+    }
+    publish(tx, _id, EventType::Deleted, &json!({ "id": _id })).await
+}
+
+async fn find_by_id_with(tx: &Tx, _id: i64) -> AppResult<User> {
+    UserRepository::find_by_id_with(tx.client(), &_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("user {_id}")).into())
+}
+
+/// In the transaction of the change, see `persistence::outbox_persistence`.
+/// The payload is a `UserDto`, never with the password.
+async fn publish(
+    tx: &Tx,
+    _id: i64,
+    event_type: EventType,
+    payload: &impl Serialize,
+) -> AppResult<()> {
+    let event = DomainEvent::new(AGGREGATE, _id, event_type, payload)?;
+    outbox_persistence::insert_with(tx.client(), &event).await?;
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use crate::persistence::db::Db;
+use crate::services::password_hasher::PasswordHasher;
+use crate::services::token_service::TokenService;
+use utils::error::app_error::AppResult;
+
+/// Everything the request handlers depend on, built once at boot and shared
+/// as the router state; tests build their own around a throwaway database.
+#[derive(Clone)]
+pub struct AppContext {
+    pub db: Db,
+    pub hasher: PasswordHasher,
+    pub tokens: TokenService,
+}
+
+impl AppContext {
+    /// The default hasher and a token key of its own, see `TokenService::generate`.
+    pub fn new(db: Db) -> AppResult<Self> {
+        Ok(Self {
+            db,
+            hasher: PasswordHasher::default(),
+            tokens: TokenService::generate()?,
+        })
+    }
+
+    pub fn with_hasher(mut self, hasher: PasswordHasher) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    pub fn with_tokens(mut self, tokens: TokenService) -> Self {
+        self.tokens = tokens;
+        self
+    }
+}
@@ -1,11 +1,96 @@
-use tracing::info;
-use utils::log::configuration::init_logger;
+use crate::persistence::db::Db;
+use crate::persistence::db_config::DbConfig;
+use crate::persistence::migration::{migrate_down, migrate_up, status};
+use crate::services::outbox_dispatcher::OutboxDispatcher;
+use crate::services::password_hasher::PasswordHasher;
+use crate::services::token_service::TokenService;
+use crate::utils::app_context::AppContext;
+use crate::utils::lifecycle::{shutdown_signal, Lifecycle};
+use anyhow::anyhow;
+use std::env;
+use tracing::{error, info};
+use utils::error::app_error::AppResult;
+use utils::log::configuration::{flush_logger, init_logger};
+
+/// Config first, so a bad one fails before anything is started, then the rest
+/// of `Lifecycle` until SIGINT/SIGTERM.
+fn init_http_ws() -> AppResult<()> {
+    let db_config = DbConfig::load()?;
+    let hasher = PasswordHasher::load()?;
+    let tokens = TokenService::load()?;
+    let outbox = OutboxDispatcher::load()?;
+    let lifecycle =
+        Lifecycle::load()?.worker("outbox", move |ctx, shutdown| outbox.run(ctx, shutdown));
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    rt.block_on(async {
+        // built from the loaded parts, `AppContext::new` would make a key to drop
+        let ctx = AppContext {
+            db: Db::new(db_config),
+            hasher,
+            tokens,
+        };
+        lifecycle.run(ctx, shutdown_signal()).await?;
+        Ok(())
+    })
+}
+/// `web migrate up`, `web migrate down [steps]` or `web migrate status`.
+fn run_migrate(args: &[String]) -> AppResult<()> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    rt.block_on(async {
+        let db = Db::new(DbConfig::load()?);
+        let mut conn = db.async_conn().await?;
+        match args.first().map(String::as_str) {
+            Some("up") => {
+                let applied = migrate_up(&mut conn).await?;
+                info!("Applied migrations: {:?}", applied);
+            }
+            Some("down") => {
+                let steps = match args.get(1) {
+                    Some(steps) => steps.parse::<usize>()?,
+                    None => 1,
+                };
+                let reverted = migrate_down(&mut conn, steps).await?;
+                info!("Reverted migrations: {:?}", reverted);
+            }
+            Some("status") => {
+                for migration in status(&conn).await? {
+                    match migration.applied_at {
+                        Some(applied_at) => println!("{} applied {}", migration.name, applied_at),
+                        None => println!("{} pending", migration.name),
+                    }
+                }
+            }
+            _ => return Err(anyhow!("usage: web migrate up|down [steps]|status")),
+        }
+        Ok(())
+    })
+}
 
-fn init_http_ws() {}
 pub(crate) fn boot() {
     init_logger();
     info!("Logging up!");
-    init_http_ws();
-    info!("Http WS up!");
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(String::as_str) == Some("migrate") {
+        if let Err(err) = run_migrate(&args[1..]) {
+            error!("Migration failed: {:?}", err);
+            flush_logger();
+            std::process::exit(1);
+        }
+        flush_logger();
+        return;
+    }
     info!("!!!Started!!!");
+    let rs = init_http_ws();
+    if let Err(err) = &rs {
+        error!("Http WS failed: {:?}", err);
+    }
+    info!("!!!Stopped!!!");
+    flush_logger();
+    if rs.is_err() {
+        std::process::exit(1);
+    }
 }
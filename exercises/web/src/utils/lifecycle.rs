@@ -0,0 +1,272 @@
+use crate::persistence::db_config::{env_lookup, parse_var};
+use crate::persistence::migration::pending;
+use crate::presentation::router;
+use crate::utils::app_context::AppContext;
+use anyhow::anyhow;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{timeout_at, Instant};
+use tracing::{error, info, warn};
+use utils::error::app_error::AppResult;
+
+type WorkerFuture = Pin<Box<dyn Future<Output = AppResult<()>> + Send>>;
+type WorkerFn = Box<dyn FnOnce(AppContext, Shutdown) -> WorkerFuture + Send>;
+
+/// Handed to the server and every worker; resolves once it is their turn to
+/// stop, see `Lifecycle`.
+#[derive(Clone)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    pub async fn wait(&self) {
+        let mut rx = self.0.clone();
+        // a dropped sender means the lifecycle is gone, stop as well
+        let _ = rx.wait_for(|stopping| *stopping).await;
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.0.borrow()
+    }
+}
+
+/// Starts the parts of the service in order and stops them in reverse:
+///
+/// 1. logger and 2. config, by `boot` before a runtime exists
+/// 3. DB pools, warmed up with one round trip
+/// 4. migrations check, refusing to start with pending ones
+/// 5. HTTP server
+/// 6. background workers, see `worker`
+///
+/// On shutdown the server stops accepting and drains the requests in flight,
+/// then the workers are told to stop; whatever is left at `shutdown_timeout`
+/// is abandoned. The pools are closed last, the logger is flushed by `boot`.
+///
+/// | variable                | default     |
+/// |-------------------------|-------------|
+/// | `HTTP_ADDRESS`          | `127.0.0.1` |
+/// | `HTTP_PORT`             | `8080`      |
+/// | `SHUTDOWN_TIMEOUT_SECS` | `30`        |
+pub struct Lifecycle {
+    pub http_address: String,
+    pub shutdown_timeout: Duration,
+    workers: Vec<(&'static str, WorkerFn)>,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self {
+            http_address: "127.0.0.1:8080".to_string(),
+            shutdown_timeout: Duration::from_secs(30),
+            workers: vec![],
+        }
+    }
+}
+
+impl Lifecycle {
+    pub fn load() -> AppResult<Self> {
+        Self::from_lookup(env_lookup())
+    }
+
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> AppResult<Self> {
+        let address = lookup("HTTP_ADDRESS").unwrap_or_else(|| "127.0.0.1".to_string());
+        let port = lookup("HTTP_PORT").unwrap_or_else(|| "8080".to_string());
+        Ok(Self {
+            http_address: format!("{address}:{port}"),
+            shutdown_timeout: Duration::from_secs(parse_var(
+                &lookup,
+                "SHUTDOWN_TIMEOUT_SECS",
+                "30",
+            )?),
+            workers: vec![],
+        })
+    }
+
+    /// A background task started after the server; it should return soon
+    /// after `Shutdown::wait` resolves.
+    pub fn worker<F, Fut>(mut self, name: &'static str, worker: F) -> Self
+    where
+        F: FnOnce(AppContext, Shutdown) -> Fut + Send + 'static,
+        Fut: Future<Output = AppResult<()>> + Send + 'static,
+    {
+        self.workers.push((
+            name,
+            Box::new(move |ctx, shutdown| Box::pin(worker(ctx, shutdown))),
+        ));
+        self
+    }
+
+    /// Steps 3 to 6; a failed step stops the ones started before it.
+    pub async fn start(self, ctx: AppContext) -> AppResult<Running> {
+        let conn = ctx.db.async_conn().await?;
+        conn.simple_query("select 1").await?;
+        let state = ctx.db.async_pool().await?.state();
+        info!("DB pool up, {} connections", state.connections);
+
+        let pending = pending(&conn).await?;
+        drop(conn);
+        if !pending.is_empty() {
+            return Err(anyhow!(
+                "migrations {pending:?} are pending, run `web migrate up` first"
+            ));
+        }
+
+        // the workers are told after the server drained, they may still be
+        // handed work by the requests in flight
+        let (stop_server, rx) = watch::channel(false);
+        let server_shutdown = Shutdown(rx);
+        let (stop_workers, rx) = watch::channel(false);
+        let shutdown = Shutdown(rx);
+        let listener = TcpListener::bind(&self.http_address).await?;
+        let local_addr = listener.local_addr()?;
+        let server = {
+            let app = router(ctx.clone());
+            tokio::spawn(async move {
+                axum::serve(listener, app)
+                    .with_graceful_shutdown(async move { server_shutdown.wait().await })
+                    .await
+            })
+        };
+        info!("Http WS up at {}!", local_addr);
+
+        let workers = self
+            .workers
+            .into_iter()
+            .map(|(name, worker)| {
+                info!("Worker {} up", name);
+                (name, tokio::spawn(worker(ctx.clone(), shutdown.clone())))
+            })
+            .collect();
+        Ok(Running {
+            local_addr,
+            shutdown_timeout: self.shutdown_timeout,
+            stop_server,
+            stop_workers,
+            server,
+            workers,
+            ctx,
+        })
+    }
+
+    /// `start`, then `Running::stop` once `signal` resolves, e.g. `shutdown_signal()`.
+    pub async fn run(
+        self,
+        ctx: AppContext,
+        signal: impl Future<Output = ()>,
+    ) -> AppResult<Stopped> {
+        let running = self.start(ctx).await?;
+        signal.await;
+        Ok(running.stop().await)
+    }
+}
+
+/// A started service, see `Lifecycle::start`.
+pub struct Running {
+    pub local_addr: SocketAddr,
+    shutdown_timeout: Duration,
+    stop_server: watch::Sender<bool>,
+    stop_workers: watch::Sender<bool>,
+    server: JoinHandle<std::io::Result<()>>,
+    workers: Vec<(&'static str, JoinHandle<AppResult<()>>)>,
+    ctx: AppContext,
+}
+
+/// How a shutdown went.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stopped {
+    /// every request in flight got its response
+    pub drained: bool,
+    pub workers_stopped: Vec<&'static str>,
+    /// still running at the deadline, or failed
+    pub workers_abandoned: Vec<&'static str>,
+    /// no connection was in use anymore, see `Db::close`
+    pub pools_closed: bool,
+}
+
+impl Running {
+    pub async fn stop(mut self) -> Stopped {
+        let deadline = Instant::now() + self.shutdown_timeout;
+        info!("Shutting down, {:?} to finish", self.shutdown_timeout);
+        let _ = self.stop_server.send(true);
+        let drained = match timeout_at(deadline, &mut self.server).await {
+            Ok(Ok(Ok(()))) => true,
+            Ok(Ok(Err(err))) => {
+                error!("Http WS failed: {:?}", err);
+                false
+            }
+            Ok(Err(err)) => {
+                error!("Http WS panicked: {:?}", err);
+                false
+            }
+            Err(_) => {
+                warn!(
+                    "Requests still in flight after {:?}, dropped",
+                    self.shutdown_timeout
+                );
+                self.server.abort();
+                let _ = self.server.await;
+                false
+            }
+        };
+        info!("Http WS down");
+        let _ = self.stop_workers.send(true);
+        let mut stopped = Stopped {
+            drained,
+            ..Stopped::default()
+        };
+
+        for (name, mut worker) in self.workers {
+            match timeout_at(deadline, &mut worker).await {
+                Ok(Ok(Ok(()))) => {
+                    info!("Worker {} down", name);
+                    stopped.workers_stopped.push(name);
+                    continue;
+                }
+                Ok(Ok(Err(err))) => error!("Worker {} failed: {:?}", name, err),
+                Ok(Err(err)) => error!("Worker {} panicked: {:?}", name, err),
+                Err(_) => {
+                    warn!(
+                        "Worker {} still running after {:?}, dropped",
+                        name, self.shutdown_timeout
+                    );
+                    worker.abort();
+                    let _ = worker.await;
+                }
+            }
+            stopped.workers_abandoned.push(name);
+        }
+
+        let db = self.ctx.db.clone();
+        drop(self.ctx);
+        stopped.pools_closed = db.close();
+        info!("Stopped: {:?}", stopped);
+        stopped
+    }
+}
+
+/// Resolves on the first SIGINT or SIGTERM.
+pub async fn shutdown_signal() {
+    let interrupt = tokio::signal::ctrl_c();
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(err) => {
+                warn!("SIGTERM can not be handled: {}", err);
+                std::future::pending::<()>().await
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = interrupt => info!("SIGINT received"),
+        _ = terminate => info!("SIGTERM received"),
+    }
+}
@@ -11,11 +11,11 @@
 macro_rules! record {
     {
         $(#[$attr:meta])*
-        $s_name:ident { $( $f_name:ident : $f_type:ty),* $(,)? }} => {paste::paste! {
+        $s_name:ident { $( $(#[$f_attr:meta])* $f_name:ident : $f_type:ty),* $(,)? }} => {paste::paste! {
             #[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
             $(#[$attr])*
             pub struct $s_name {
-                $( pub $f_name: $f_type ),*
+                $( $(#[$f_attr])* pub $f_name: $f_type ),*
             }
 
             impl $s_name {
@@ -34,6 +34,24 @@ macro_rules! record {
                     self.$f_name = val;
                 }
                 )*
-        }}
+            }
+
+            // every field but the `Option`s is required
+            impl ::utils::openapi::ApiSchema for $s_name {
+                fn schema_name() -> Option<String> {
+                    Some(stringify!($s_name).to_string())
+                }
+
+                fn schema(components: &mut ::utils::openapi::Components) -> ::utils::openapi::Value {
+                    ::utils::openapi::object_schema("", vec![$(
+                        (
+                            stringify!($f_name),
+                            <$f_type as ::utils::openapi::ApiSchema>::reference(components),
+                            <$f_type as ::utils::openapi::ApiSchema>::OPTIONAL,
+                        )
+                    ),*])
+                }
+            }
+        }
     };
 }
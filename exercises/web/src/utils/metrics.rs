@@ -0,0 +1,101 @@
+use crate::persistence::db::{Db, PoolStats};
+use std::future::Future;
+use std::time::Instant;
+use utils::metrics::{render_counter, render_gauge, CounterVec, HistogramVec, LATENCY_BUCKETS};
+
+/// Recorded by `presentation::metrics::record_metrics`; `route` is the
+/// matched path, e.g. `/users/{id}`, so ids do not make series of their own.
+pub static HTTP_REQUESTS: CounterVec = CounterVec::new(
+    "http_requests_total",
+    "Responses by method, route and status.",
+    &["method", "route", "status"],
+);
+
+pub static HTTP_REQUEST_DURATION: HistogramVec = HistogramVec::new(
+    "http_request_duration_seconds",
+    "Time to the response head by method and route.",
+    &["method", "route"],
+    LATENCY_BUCKETS,
+);
+
+/// Recorded by the persistence calls wrapped in `timed`.
+pub static DB_QUERY_DURATION: HistogramVec = HistogramVec::new(
+    "db_query_duration_seconds",
+    "Persistence calls by table and operation, failed ones included.",
+    &["table", "op"],
+    LATENCY_BUCKETS,
+);
+
+/// Recorded by `services::outbox_dispatcher`, one per event and round.
+pub static OUTBOX_EVENTS: CounterVec = CounterVec::new(
+    "outbox_events_total",
+    "Outbox events by outcome: delivered, retried or dead.",
+    &["outcome"],
+);
+
+/// `query` timed into `DB_QUERY_DURATION`.
+pub async fn timed<T>(table: &str, op: &str, query: impl Future<Output = T>) -> T {
+    let started = Instant::now();
+    let rs = query.await;
+    DB_QUERY_DURATION.observe_duration(&[table, op], started.elapsed());
+    rs
+}
+
+/// Name, help and value of a gauge per pool.
+type PoolGauge = (&'static str, &'static str, fn(&PoolStats) -> f64);
+
+const POOL_GAUGES: [PoolGauge; 4] = [
+    (
+        "db_pool_connections",
+        "Connections open, idle or in use.",
+        |pool| pool.connections as f64,
+    ),
+    (
+        "db_pool_idle_connections",
+        "Connections open and not in use.",
+        |pool| pool.idle as f64,
+    ),
+    (
+        "db_pool_max_connections",
+        "Connections the pool may open.",
+        |pool| pool.max_size as f64,
+    ),
+    (
+        "db_pool_waiting",
+        "Callers waiting for a connection.",
+        |pool| pool.waiting as f64,
+    ),
+];
+
+/// Everything above and the state of the pools of `db`, as `/metrics` serves it.
+pub fn render(db: &Db) -> String {
+    let mut out = String::new();
+    HTTP_REQUESTS.render(&mut out);
+    HTTP_REQUEST_DURATION.render(&mut out);
+    DB_QUERY_DURATION.render(&mut out);
+    OUTBOX_EVENTS.render(&mut out);
+
+    let stats = db.pool_stats();
+    for (name, help, val) in POOL_GAUGES {
+        let samples = stats
+            .iter()
+            .map(|pool| (vec![pool.server.clone(), pool.kind.to_string()], val(pool)))
+            .collect::<Vec<_>>();
+        render_gauge(&mut out, name, help, &["server", "pool"], &samples);
+    }
+    let timed_out = stats
+        .iter()
+        .map(|pool| {
+            let labels = vec![pool.server.clone(), pool.kind.to_string()];
+            (labels, pool.timed_out as f64)
+        })
+        .collect::<Vec<_>>();
+    render_counter(
+        &mut out,
+        "db_pool_checkouts_timed_out_total",
+        "Callers that gave up waiting for a connection.",
+        &["server", "pool"],
+        &timed_out,
+    );
+    out
+}
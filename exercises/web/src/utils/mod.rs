@@ -1,2 +1,5 @@
+pub mod app_context;
 pub(crate) mod boot;
+pub mod lifecycle;
 pub(crate) mod macros;
+pub mod metrics;
@@ -0,0 +1,195 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{Days, Local, NaiveDate, NaiveDateTime};
+    use macros::Validate;
+    use serde_json::json;
+    use utils::validation::Validate;
+    use web::dto::sample_rec_dto::{NewSampleRecord, SampleRecordDto};
+    use web::dto::user_dto::{NewUser, UserDto, UserUpdate};
+    use web::models::sample_rec::SampleRecord;
+    use web::models::user::User;
+
+    #[derive(Debug, Default, Validate)]
+    struct Contact {
+        #[validate(required, email, length(max = 32))]
+        email: String,
+        #[validate(email)]
+        backup_email: Option<String>,
+        #[validate(range(min = -10, max = 10))]
+        offset: i32,
+        #[validate(range(min = 0.5))]
+        ratio: f64,
+        #[validate(required, length(min = 1, max = 2))]
+        tags: Vec<String>,
+        #[validate(date(min = "2000-01-01", max = "today"))]
+        since: Option<NaiveDateTime>,
+        #[validate(required)]
+        nick: Option<String>,
+        #[validate(regex = "^[0-9]{5}$")]
+        zip: Option<String>,
+    }
+
+    fn valid() -> Contact {
+        Contact {
+            email: "someone@example.com".to_string(),
+            backup_email: None,
+            offset: -10,
+            ratio: 0.5,
+            tags: vec!["a".to_string()],
+            since: None,
+            nick: Some("nick".to_string()),
+            zip: Some("12345".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(valid().validate().is_ok());
+
+        let errors = Contact::default().validate().unwrap_err();
+        assert_eq!(
+            errors.fields().keys().collect::<Vec<_>>(),
+            ["email", "nick", "ratio", "tags"]
+        );
+        // all the checks of a field are reported
+        assert_eq!(
+            errors.get("email").unwrap(),
+            &["is required", "must be an email address"]
+        );
+        assert_eq!(
+            errors.get("tags").unwrap(),
+            &["is required", "must be 1 to 2 long"]
+        );
+        assert_eq!(errors.get("ratio").unwrap(), &["must be at least 0.5"]);
+
+        let tomorrow = Local::now().date_naive() + Days::new(1);
+        let contact = Contact {
+            email: format!("{}@example.com", "x".repeat(30)),
+            backup_email: Some("not an email".to_string()),
+            offset: 11,
+            since: Some(tomorrow.and_hms_opt(0, 0, 0).unwrap()),
+            zip: Some("1234".to_string()),
+            ..valid()
+        };
+        let errors = contact.validate().unwrap_err();
+        assert_eq!(errors.get("email").unwrap(), &["must be at most 32 long"]);
+        assert_eq!(
+            errors.get("backup_email").unwrap(),
+            &["must be an email address"]
+        );
+        assert_eq!(
+            errors.get("offset").unwrap(),
+            &["must be between -10 and 10"]
+        );
+        assert_eq!(errors.get("since").unwrap(), &["must not be after today"]);
+        assert_eq!(errors.get("zip").unwrap(), &["must match ^[0-9]{5}$"]);
+        assert_eq!(
+            serde_json::to_value(&errors).unwrap()["offset"],
+            json!(["must be between -10 and 10"])
+        );
+        assert!(errors
+            .to_string()
+            .starts_with("backup_email must be an email address; "));
+
+        for bad in ["", "a@", "@b.c", "a@b", "a b@c.d", "a@b..c", "a@@b.c"] {
+            let contact = Contact {
+                email: bad.to_string(),
+                ..valid()
+            };
+            assert!(contact.validate().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_user_mapping() {
+        let user = User::new(
+            1,
+            NaiveDateTime::default(),
+            NaiveDateTime::default(),
+            NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            "hashed".to_string(),
+            "pbkdf2-sha256$i=1000".to_string(),
+            "screen 1".to_string(),
+            1,
+            "user_1".to_string(),
+            1,
+            "/1/".to_string(),
+            3,
+            None,
+        );
+        let dto = UserDto::from(&user);
+        assert_eq!(dto.username, "user_1");
+        assert_eq!(dto.version, 3);
+        let val = serde_json::to_value(&dto).unwrap();
+        assert!(val.get("passwd").is_none());
+        assert!(val.get("passwd_enc_method").is_none());
+
+        let new_user = serde_json::from_value::<NewUser>(json!({
+            "id": 2,
+            "dob": "1990-01-01",
+            "passwd": "secret",
+            "passwd_enc_method": "plain",
+            "screenname": "screen 2",
+            "status": 1,
+            "username": "user_2",
+            "org_id": 1,
+            "org_treepath": "/1/"
+        }))
+        .unwrap();
+        assert!(new_user.validate().is_ok());
+        let user = User::from(new_user);
+        assert_eq!(*user.id(), 2);
+        assert_eq!(user.passwd(), "secret");
+        // left to the hasher
+        assert_eq!(user.passwd_enc_method(), "");
+
+        let update = serde_json::from_value::<UserUpdate>(json!({
+            "dob": "1990-01-01",
+            "passwd": "ignored",
+            "screenname": "screen 2",
+            "status": 1,
+            "username": "user_2",
+            "org_id": 1,
+            "org_treepath": "1/",
+            "version": 4
+        }))
+        .unwrap();
+        let errors = update.validate().unwrap_err();
+        assert_eq!(errors.fields().keys().collect::<Vec<_>>(), ["org_treepath"]);
+        let user = User::from(update);
+        assert_eq!(user.passwd(), "");
+        assert_eq!(*user.version(), 4);
+    }
+
+    #[test]
+    fn test_sample_record_mapping() {
+        let rec = SampleRecord::new(
+            1,
+            "rec 1".to_string(),
+            true,
+            NaiveDateTime::default(),
+            2,
+            None,
+        );
+        let dto = SampleRecordDto::from(&rec);
+        assert_eq!(
+            serde_json::to_value(&dto).unwrap(),
+            json!({
+                "id": 1,
+                "name": "rec 1",
+                "available": true,
+                "created_at": "1970-01-01T00:00:00",
+                "version": 2
+            })
+        );
+        let new_rec = NewSampleRecord {
+            id: 0,
+            name: "x".repeat(256),
+            available: false,
+        };
+        let errors = new_rec.validate().unwrap_err();
+        assert_eq!(errors.fields().keys().collect::<Vec<_>>(), ["id", "name"]);
+        let rec = SampleRecord::from(new_rec);
+        assert_eq!(rec.name().len(), 256);
+    }
+}
@@ -1,4 +1,8 @@
 // #![allow(warnings)]
 #![allow(clippy::too_many_arguments, unused_variables, dead_code)]
 
+pub(crate) mod dto;
 pub(crate) mod persistence;
+pub(crate) mod presentation;
+pub(crate) mod services;
+pub(crate) mod utils;
@@ -1,4 +1,17 @@
 pub(crate) mod test_common;
+mod test_db_config;
+mod test_migration;
+mod test_notification;
+mod test_org_tree;
+mod test_page;
+mod test_query;
+mod test_replica;
+mod test_repository;
 mod test_sample_rec_persistence;
 mod test_sample_rec_persistence_async;
+mod test_soft_delete;
+mod test_tls;
+mod test_tx;
 mod test_user_persistence;
+mod test_user_persistence_async;
+mod test_version;
@@ -1,5 +1,165 @@
+use chrono::{Local, NaiveDate};
+use diesel::{Connection, PgConnection, RunQueryDsl};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Once;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+use utils::error::app_error::AppResult;
+use web::models::sample_rec::SampleRecord;
+use web::models::user::User;
+use web::persistence::db::Db;
+use web::persistence::db_config::DbConfig;
+use web::persistence::migration::migrate_up;
+
+static TEST_DB: Once = Once::new();
+static SCHEMA_SEQ: AtomicU32 = AtomicU32::new(0);
+
+/// Migrates a fresh test schema and points `DB_SCHEMA` at it, so the process
+/// wide pools created afterwards never touch the real tables. Must run before
+/// the first `get_connection`/`get_async_connection` of the process; tests
+/// that don't go through those statics should use [`TestDb`] instead.
+pub(crate) fn init_test_db() {
+    TEST_DB.call_once(|| {
+        let config = DbConfig::load().expect("test db config");
+        let schema = create_test_schema(&config).expect("test schema setup failed");
+        std::env::set_var("DB_SCHEMA", schema);
+    });
+}
+
+/// A migrated schema of its own for one test, dropped with the value.
+///
+/// Derefs to a `Db` with its own pools, pass `&test_db` wherever a `&Db` is
+/// expected.
+pub(crate) struct TestDb {
+    db: Db,
+}
+
+impl TestDb {
+    pub(crate) fn new() -> AppResult<Self> {
+        let mut config = DbConfig::load()?;
+        config.min_pool_size = 1;
+        config.max_pool_size = 4;
+        config.schema = Some(create_test_schema(&config)?);
+        Ok(Self {
+            db: Db::new(config),
+        })
+    }
+
+    pub(crate) fn schema(&self) -> &str {
+        self.db.config().schema.as_deref().unwrap_or_default()
+    }
+}
+
+impl Deref for TestDb {
+    type Target = Db;
+
+    fn deref(&self) -> &Db {
+        &self.db
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let mut config = self.db.config().clone();
+        config.schema = None;
+        let sql = format!("drop schema if exists {} cascade", self.schema());
+        let rs = PgConnection::establish(&config.conn_string())
+            .map_err(anyhow::Error::from)
+            .and_then(|mut conn| Ok(diesel::sql_query(sql).execute(&mut conn)?));
+        if let Err(err) = rs {
+            warn!("Drop of test schema {} failed: {}", self.schema(), err);
+        }
+    }
+}
+
+/// A live record named `name of <id>`, created now.
+pub(crate) fn new_rec(_id: i64) -> SampleRecord {
+    SampleRecord::new(
+        _id,
+        format!("name of {}", _id),
+        false,
+        Local::now().naive_local(),
+        0,
+        None,
+    )
+}
+
+/// A live, active `user_<id>` of org 1, password `secret` stored `plain`.
+pub(crate) fn new_user(_id: i64) -> User {
+    let now = Local::now().naive_local();
+    User::new(
+        _id,
+        now,
+        now,
+        NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+        "secret".to_string(),
+        "plain".to_string(),
+        format!("screen {}", _id),
+        1,
+        format!("user_{}", _id),
+        1,
+        "/1/".to_string(),
+        0,
+        None,
+    )
+}
+
+/// Creates and migrates `test_<secs>_<pid>_<seq>`, on a thread and runtime of
+/// its own since it is called from sync and async tests alike.
+fn create_test_schema(config: &DbConfig) -> AppResult<String> {
+    let mut config = config.clone();
+    std::thread::spawn(move || {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(async {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+                let schema = format!(
+                    "test_{}_{}_{}",
+                    now,
+                    std::process::id(),
+                    SCHEMA_SEQ.fetch_add(1, Ordering::Relaxed)
+                );
+                config.schema = None;
+                let (client, connection) =
+                    config.pg_config().connect(config.tls_connector()?).await?;
+                tokio::spawn(connection);
+                // left behind by killed runs, a schema is dropped once it is an hour old
+                for row in client
+                    .query(
+                        "select nspname from pg_namespace where nspname ~ '^test_[0-9]+_[0-9]+(_[0-9]+)?$'",
+                        &[],
+                    )
+                    .await?
+                {
+                    let stale: String = row.get(0);
+                    let created = stale.split('_').nth(1).and_then(|secs| secs.parse::<u64>().ok());
+                    if created.is_some_and(|secs| secs + 3600 < now) {
+                        client
+                            .batch_execute(&format!("drop schema if exists {stale} cascade"))
+                            .await?;
+                    }
+                }
+                client
+                    .batch_execute(&format!("create schema {schema}"))
+                    .await?;
+
+                config.schema = Some(schema.clone());
+                let (mut client, connection) =
+                    config.pg_config().connect(config.tls_connector()?).await?;
+                tokio::spawn(connection);
+                migrate_up(&mut client).await?;
+                Ok(schema)
+            })
+    })
+    .join()
+    .map_err(|_| anyhow::anyhow!("test schema setup panicked"))?
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::persistence::test_common::{init_test_db, TestDb};
     use chrono::{NaiveDateTime, Utc};
     use diesel::{QueryDsl, RunQueryDsl};
     use tokio::pin;
@@ -11,10 +171,14 @@ mod tests {
     use web::models::sample_rec::sample_recs::dsl::sample_recs;
     use web::models::sample_rec::SampleRecord;
     use web::persistence::common::{get_async_connection, get_connection};
+    use web::persistence::repository::AsyncRepository;
+    use web::persistence::sample_rec_persistence::{find_by_id_with, insert_with};
+    use web::persistence::sample_rec_persistence_async::SampleRecordRepository;
 
     #[test]
     fn test_get_conn_pool() -> AppResult<()> {
         init_logger();
+        init_test_db();
         let mut conn = get_connection()?;
         info!("Get connection from pool successfully!");
         let _rs = sample_recs
@@ -31,6 +195,7 @@ mod tests {
     #[tokio::test]
     async fn test_get_async_connection() -> AppResult<()> {
         init_logger();
+        init_test_db();
         let conn = get_async_connection().await?;
         info!("Start getting async connection from pool ...");
         let sql = "SELECT id_, name_, available, created_at FROM test_rec where created_at < $1 order by id_ DESC limit $2";
@@ -45,10 +210,50 @@ mod tests {
                     row.get("name_"),
                     row.get("available"),
                     row.get::<_, NaiveDateTime>("created_at"),
+                    0,
+                    None,
                 );
                 info!("{:#?}", test_rec);
             }
         }
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_isolated_test_dbs() -> AppResult<()> {
+        init_logger();
+        let first = TestDb::new()?;
+        let second = TestDb::new()?;
+        assert_ne!(first.schema(), second.schema());
+
+        let rec = SampleRecord::new(
+            1,
+            "only in first".to_string(),
+            false,
+            Default::default(),
+            0,
+            None,
+        );
+        insert_with(&mut *first.conn()?, &rec)?;
+        assert!(find_by_id_with(&mut *first.conn()?, 1)?.is_some());
+        assert!(find_by_id_with(&mut *second.conn()?, 1)?.is_none());
+        assert!(SampleRecordRepository::find_by_id(&second, &1)
+            .await?
+            .is_none());
+
+        let schema = first.schema().to_string();
+        let mut admin = first.config().clone();
+        admin.schema = None;
+        drop(first);
+        let (client, connection) = admin.pg_config().connect(admin.tls_connector()?).await?;
+        tokio::spawn(connection);
+        let row = client
+            .query_one(
+                "select count(*) from pg_namespace where nspname = $1",
+                &[&schema],
+            )
+            .await?;
+        assert_eq!(row.get::<_, i64>(0), 0);
+        Ok(())
+    }
 }
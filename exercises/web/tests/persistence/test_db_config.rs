@@ -0,0 +1,150 @@
+#[cfg(test)]
+mod tests {
+    use diesel::sql_types::Text;
+    use diesel::{sql_query, QueryableByName, RunQueryDsl};
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::persistence::common::{create_async_conn_pool, create_conn_pool};
+    use web::persistence::db_config::{DbConfig, SslMode};
+
+    fn config_of(pairs: &[(&str, &str)]) -> AppResult<DbConfig> {
+        let map = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<String, String>>();
+        DbConfig::from_lookup(|key| map.get(key).cloned())
+    }
+
+    const REQUIRED: [(&str, &str); 4] = [
+        ("DB_ADDRESS", "localhost"),
+        ("DB_NAME", "test_db"),
+        ("DB_USERNAME", "test_db"),
+        ("DB_PASSWORD", "test_db"),
+    ];
+
+    #[test]
+    fn test_defaults() -> AppResult<()> {
+        let config = config_of(&REQUIRED)?;
+        assert_eq!(config.port, 5432);
+        assert_eq!(config.ssl_mode, SslMode::Prefer);
+        assert_eq!(config.connect_timeout, Duration::from_secs(30));
+        assert_eq!(config.idle_timeout, Some(Duration::from_secs(600)));
+        assert_eq!(config.statement_timeout, None);
+        assert_eq!(config.application_name, "web");
+        assert_eq!((config.min_pool_size, config.max_pool_size), (1, 5));
+        assert!(config.replicas.is_empty());
+        assert_eq!(config.replica_timeout, Duration::from_secs(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_replicas() -> AppResult<()> {
+        let mut pairs = REQUIRED.to_vec();
+        pairs.push(("DB_REPLICAS", "replica1, replica2:5433,"));
+        let config = config_of(&pairs)?;
+        assert_eq!(
+            config.replicas,
+            vec![
+                ("replica1".to_string(), 5432),
+                ("replica2".to_string(), 5433)
+            ]
+        );
+        let replicas = config.replica_configs();
+        assert_eq!(replicas[1].address, "replica2");
+        assert_eq!(replicas[1].port, 5433);
+        assert_eq!(replicas[1].username, config.username);
+        assert!(replicas.iter().all(|replica| replica.replicas.is_empty()));
+
+        pairs.push(("DB_REPLICAS", "replica1:primary"));
+        assert!(config_of(&pairs).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        assert!(config_of(&REQUIRED[1..]).is_err());
+        let mut pairs = REQUIRED.to_vec();
+        pairs.push(("DB_SSL_MODE", "sometimes"));
+        assert!(config_of(&pairs).is_err());
+        let mut pairs = REQUIRED.to_vec();
+        pairs.push(("DB_MAX_POOL_SIZE", "many"));
+        assert!(config_of(&pairs).is_err());
+        let mut pairs = REQUIRED.to_vec();
+        pairs.push(("DB_MIN_POOL_SIZE", "8"));
+        assert!(config_of(&pairs).is_err());
+        let mut pairs = REQUIRED.to_vec();
+        pairs.push(("DB_REPLICA_TIMEOUT_MS", "0"));
+        assert!(config_of(&pairs).is_err());
+        let mut pairs = REQUIRED.to_vec();
+        pairs.push(("DB_CONNECT_TIMEOUT_SECS", "0"));
+        assert!(config_of(&pairs).is_err());
+    }
+
+    #[test]
+    fn test_conn_string_quoting() -> AppResult<()> {
+        let mut config = config_of(&REQUIRED)?;
+        config.password = "it's a \\secret".to_string();
+        config.statement_timeout = Some(Duration::from_millis(1500));
+        let conn_string = config.conn_string();
+        assert!(conn_string.contains(r"password='it\'s a \\secret'"));
+        assert!(conn_string.contains("options='-c statement_timeout=1500'"));
+        let parsed = conn_string.parse::<tokio_postgres::Config>()?;
+        assert_eq!(parsed.get_password(), Some("it's a \\secret".as_bytes()));
+        Ok(())
+    }
+
+    #[derive(QueryableByName)]
+    struct Setting {
+        #[diesel(sql_type = Text)]
+        app: String,
+        #[diesel(sql_type = Text)]
+        timeout: String,
+    }
+
+    #[tokio::test]
+    async fn test_pools_from_config() -> AppResult<()> {
+        init_logger();
+        let mut config = DbConfig::load()?;
+        config.application_name = "web_config_test".to_string();
+        config.statement_timeout = Some(Duration::from_millis(2500));
+        config.min_pool_size = 1;
+        config.max_pool_size = 2;
+
+        let pool = create_async_conn_pool(&config).await?;
+        let conn = pool.get().await?;
+        let row = conn
+            .query_one(
+                "select current_setting('application_name'), current_setting('statement_timeout')",
+                &[],
+            )
+            .await?;
+        assert_eq!(row.get::<_, String>(0), "web_config_test");
+        assert_eq!(row.get::<_, String>(1), "2500ms");
+        drop(conn);
+
+        let sync_config = config.clone();
+        let setting = tokio::task::spawn_blocking(move || -> AppResult<Setting> {
+            let pool = create_conn_pool(&sync_config)?;
+            let mut conn = pool.get()?;
+            Ok(sql_query(
+                "select current_setting('application_name') as app, current_setting('statement_timeout') as timeout",
+            )
+            .get_result::<Setting>(&mut conn)?)
+        })
+        .await??;
+        assert_eq!(setting.app, "web_config_test");
+        assert_eq!(setting.timeout, "2500ms");
+
+        config.password = "wrong".to_string();
+        config.connect_timeout = Duration::from_secs(1);
+        // a bad pool is an error, not a panic
+        assert!(create_async_conn_pool(&config).await.is_err());
+        let mut zero = config.clone();
+        zero.connect_timeout = Duration::ZERO;
+        assert!(create_async_conn_pool(&zero).await.is_err());
+        assert!(create_conn_pool(&zero).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod tests {
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::persistence::db_config::DbConfig;
+    use web::persistence::migration::{migrate_down, migrate_up, pending, status, MIGRATIONS};
+
+    #[tokio::test]
+    async fn test_migrate_up_down() -> AppResult<()> {
+        init_logger();
+        let mut config = DbConfig::load()?;
+        let schema = format!("test_migration_{}", std::process::id());
+        config.schema = None;
+        let (admin, connection) = config.pg_config().connect(config.tls_connector()?).await?;
+        tokio::spawn(connection);
+        admin
+            .batch_execute(&format!(
+                "drop schema if exists {schema} cascade; create schema {schema}"
+            ))
+            .await?;
+
+        config.schema = Some(schema.clone());
+        let (mut client, connection) = config.pg_config().connect(config.tls_connector()?).await?;
+        tokio::spawn(connection);
+        let all = MIGRATIONS.iter().map(|m| m.version).collect::<Vec<_>>();
+        assert_eq!(pending(&client).await?, all);
+        // looking does not create the history table
+        let row = client
+            .query_one("select to_regclass('schema_migrations') is null", &[])
+            .await?;
+        assert!(row.get::<_, bool>(0));
+
+        assert_eq!(migrate_up(&mut client).await?, all);
+        // applied ones are skipped
+        assert!(migrate_up(&mut client).await?.is_empty());
+        assert!(status(&client)
+            .await?
+            .iter()
+            .all(|s| s.applied_at.is_some()));
+        client
+            .batch_execute("select 1 from test_rec, user_")
+            .await?;
+
+        let last = *all.last().unwrap();
+        assert_eq!(migrate_down(&mut client, 1).await?, vec![last]);
+        assert_eq!(pending(&client).await?, vec![last]);
+
+        migrate_down(&mut client, all.len()).await?;
+        assert_eq!(pending(&client).await?, all);
+        assert!(client.batch_execute("select 1 from user_").await.is_err());
+
+        admin
+            .batch_execute(&format!("drop schema {schema} cascade"))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::TestDb;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use std::time::Duration;
+    use tokio::time::timeout;
+    use tokio_stream::StreamExt;
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::persistence::db::Db;
+    use web::persistence::notification::{
+        notify, notify_with, subscribe, Notification, Subscription,
+    };
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Changed {
+        table: String,
+        id: i64,
+    }
+
+    fn changed(id: i64) -> Changed {
+        Changed {
+            table: "test_rec".to_string(),
+            id,
+        }
+    }
+
+    /// Every test on a channel of its own, the server is shared.
+    fn channel(db: &TestDb) -> String {
+        format!("changes_{}", db.schema())
+    }
+
+    async fn next<T: for<'de> Deserialize<'de>>(
+        subscription: &mut Subscription<T>,
+    ) -> AppResult<Notification<T>> {
+        timeout(Duration::from_secs(10), subscription.next())
+            .await?
+            .expect("subscription ended")
+    }
+
+    async fn payload<T: for<'de> Deserialize<'de>>(subscription: &mut Subscription<T>) -> T {
+        match next(subscription).await.unwrap() {
+            Notification::Message { payload, .. } => payload,
+            Notification::Resubscribed => panic!("expected a message, got a resubscribe"),
+        }
+    }
+
+    /// Backends listening on `channel`, by the statement they ran last.
+    async fn listeners(db: &Db, channel: &str) -> AppResult<i64> {
+        let conn = db.async_conn().await?;
+        let row = conn
+            .query_one(
+                "select count(*) from pg_stat_activity where query = $1",
+                &[&format!("listen \"{channel}\"")],
+            )
+            .await?;
+        Ok(row.try_get(0)?)
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_and_notify() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let channel = channel(&db);
+        let mut subscription = subscribe::<Changed>(&db, &channel).await?;
+        assert_eq!(subscription.channel(), channel);
+
+        notify(&db, &channel, &changed(1)).await?;
+        notify(&db, "some_other_channel", &changed(2)).await?;
+        notify(&db, &channel, &changed(3)).await?;
+        assert_eq!(payload(&mut subscription).await, changed(1));
+        assert_eq!(payload(&mut subscription).await, changed(3));
+
+        // a payload of another shape is an error, the stream goes on
+        notify(&db, &channel, &json!({ "unexpected": true })).await?;
+        notify(&db, &channel, &changed(4)).await?;
+        let err = next(&mut subscription).await.unwrap_err();
+        assert!(err.to_string().contains("does not decode"), "{err}");
+        assert_eq!(payload(&mut subscription).await, changed(4));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_notify_on_commit() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let channel = channel(&db);
+        let mut subscription = subscribe::<Changed>(&db, &channel).await?;
+
+        let tx = db.begin().await?;
+        notify_with(tx.client(), &channel, &changed(1)).await?;
+        tx.rollback().await?;
+        let tx = db.begin().await?;
+        notify_with(tx.client(), &channel, &changed(2)).await?;
+        tx.commit().await?;
+        assert_eq!(payload(&mut subscription).await, changed(2));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resubscribes_after_connection_loss() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let channel = channel(&db);
+        let mut subscription = subscribe::<Changed>(&db, &channel).await?;
+        assert_eq!(listeners(&db, &channel).await?, 1);
+
+        let conn = db.async_conn().await?;
+        conn.execute(
+            "select pg_terminate_backend(pid) from pg_stat_activity where query = $1",
+            &[&format!("listen \"{channel}\"")],
+        )
+        .await?;
+        drop(conn);
+        assert_eq!(next(&mut subscription).await?, Notification::Resubscribed);
+
+        notify(&db, &channel, &changed(1)).await?;
+        assert_eq!(payload(&mut subscription).await, changed(1));
+
+        // dropping it closes the connection
+        drop(subscription);
+        let mut left = 1;
+        for _ in 0..50 {
+            left = listeners(&db, &channel).await?;
+            if left == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(left, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_fails_without_server() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let mut config = db.config().clone();
+        config.port = 1;
+        config.connect_timeout = Duration::from_secs(1);
+        let err = subscribe::<Changed>(&Db::new(config), "changes")
+            .await
+            .err()
+            .expect("subscribed without a server");
+        assert!(!err.to_string().is_empty());
+        assert!(subscribe::<Changed>(&db, "").await.is_err());
+        // the server would cut it to 63 bytes and listen on another channel
+        let long = "c".repeat(64);
+        let err = subscribe::<Changed>(&db, &long).await.err().unwrap();
+        assert!(err.to_string().contains("longer than 63"), "{err}");
+        subscribe::<Changed>(&db, &long[1..]).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_channel_names_are_quoted() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let channel = format!("Mixed \"Case\"; {}", db.schema());
+        let mut subscription = subscribe::<String>(&db, &channel).await?;
+        notify(&db, &channel, &"hello").await?;
+        assert_eq!(payload(&mut subscription).await, "hello");
+        Ok(())
+    }
+}
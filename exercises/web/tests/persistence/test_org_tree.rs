@@ -0,0 +1,147 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::{new_user, TestDb};
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::models::org_path::OrgPath;
+    use web::models::user::User;
+    use web::persistence::org_tree::{descendants, find_ancestors, find_subtree, move_subtree};
+    use web::persistence::repository::AsyncRepository;
+    use web::persistence::user_persistence_async::UserRepository;
+
+    fn path(text: &str) -> OrgPath {
+        OrgPath::parse(text).unwrap()
+    }
+
+    fn user_in(_id: i64, treepath: &str) -> User {
+        let org = path(treepath);
+        let mut user = new_user(_id);
+        user.set_org_id(org.org_id());
+        user.set_org_treepath(org.to_string());
+        user
+    }
+
+    fn ids(users: &[User]) -> Vec<i64> {
+        users.iter().map(|user| *user.id()).collect()
+    }
+
+    #[test]
+    fn test_org_path() {
+        let org = path("/1/20/300/");
+        assert_eq!(org.ids(), [1, 20, 300]);
+        assert_eq!(org.org_id(), 300);
+        assert_eq!(org.depth(), 3);
+        assert_eq!(org.parent(), Some(path("/1/20/")));
+        assert_eq!(org.ancestors(), [path("/1/"), path("/1/20/")]);
+        assert!(path("/1/").parent().is_none());
+        assert!(path("/1/").ancestors().is_empty());
+        assert_eq!(OrgPath::root(1).child(20).child(300), org);
+        assert!(path("/1/20/").contains(&org));
+        assert!(org.contains(&org));
+        // a prefix of the digits is not an ancestor
+        assert!(!path("/1/2/").contains(&org));
+
+        for bad in [
+            "",
+            "/",
+            "1/",
+            "/1",
+            "//",
+            "/1//2/",
+            "/a/",
+            "/99999999999999999999/",
+        ] {
+            assert!(OrgPath::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subtree_queries() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let users = [
+            user_in(1, "/1/"),
+            user_in(2, "/1/2/"),
+            user_in(3, "/1/2/3/"),
+            user_in(4, "/1/4/"),
+            user_in(5, "/5/"),
+            user_in(6, "/1/2/3/"),
+            user_in(7, "/1/23/"),
+        ];
+        UserRepository::insert_batch(&db, &users).await?;
+        UserRepository::delete(&db, &6).await?;
+
+        assert_eq!(
+            ids(&find_subtree(&db, &path("/1/2/"), 0, 10).await?),
+            [2, 3]
+        );
+        assert_eq!(ids(&find_subtree(&db, &path("/1/"), 1, 2).await?), [3, 7]);
+        assert_eq!(ids(&find_ancestors(&db, &path("/1/2/3/")).await?), [1, 2]);
+        assert_eq!(
+            descendants(&db, &path("/1/")).await?,
+            [
+                path("/1/2/"),
+                path("/1/2/3/"),
+                path("/1/23/"),
+                path("/1/4/")
+            ]
+        );
+        assert!(descendants(&db, &path("/5/")).await?.is_empty());
+
+        // a treepath that is not one is refused by the database too
+        let conn = db.async_conn().await?;
+        assert!(conn
+            .execute("update user_ set org_treepath = '1/' where id_ = 1", &[])
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_move_subtree() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let users = [
+            user_in(1, "/1/"),
+            user_in(2, "/1/2/"),
+            user_in(3, "/1/2/3/"),
+            user_in(4, "/1/4/"),
+            user_in(5, "/5/"),
+            user_in(6, "/1/2/3/"),
+            user_in(8, "/5/4/"),
+        ];
+        UserRepository::insert_batch(&db, &users).await?;
+        UserRepository::delete(&db, &6).await?;
+
+        let moved = move_subtree(&db, &path("/1/2/"), Some(&path("/5/"))).await?;
+        assert_eq!(moved, path("/5/2/"));
+        assert_eq!(ids(&find_subtree(&db, &moved, 0, 10).await?), [2, 3]);
+        assert!(find_subtree(&db, &path("/1/2/"), 0, 10).await?.is_empty());
+        let user = UserRepository::find_by_id(&db, &3).await?.unwrap();
+        assert_eq!(user.org_treepath(), "/5/2/3/");
+        assert_eq!(*user.org_id(), 3);
+        assert_eq!(*user.version(), 1);
+        // the soft deleted one moved along
+        UserRepository::restore(&db, &6).await?;
+        let user = UserRepository::find_by_id(&db, &6).await?.unwrap();
+        assert_eq!(user.org_treepath(), "/5/2/3/");
+
+        assert!(move_subtree(&db, &path("/5/"), Some(&path("/5/2/3/")))
+            .await
+            .is_err());
+        // org 4 is already under 5, nothing changes
+        assert!(move_subtree(&db, &path("/1/4/"), Some(&path("/5/")))
+            .await
+            .is_err());
+        let user = UserRepository::find_by_id(&db, &4).await?.unwrap();
+        assert_eq!(user.org_treepath(), "/1/4/");
+        assert!(move_subtree(&db, &path("/9/"), Some(&path("/5/")))
+            .await
+            .is_err());
+
+        let moved = move_subtree(&db, &path("/5/2/"), None).await?;
+        assert_eq!(moved, path("/2/"));
+        assert_eq!(descendants(&db, &path("/2/")).await?, [path("/2/3/")]);
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::{new_rec, TestDb};
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::models::sample_rec::SampleRecord;
+    use web::persistence::db::Db;
+    use web::persistence::page::{decode_cursor, encode_cursor, SortOrder};
+    use web::persistence::{sample_rec_persistence, sample_rec_persistence_async};
+
+    const IDS: std::ops::RangeInclusive<i64> = 5001..=5025;
+
+    fn prepare(db: &Db) -> AppResult<()> {
+        for _id in IDS {
+            sample_rec_persistence::delete(db, _id)?;
+        }
+        let recs = IDS.map(new_rec).collect::<Vec<SampleRecord>>();
+        sample_rec_persistence::insert_batch(db, &recs)
+    }
+
+    fn assert_walk(ids: &[i64], order: SortOrder) {
+        let sorted = ids.windows(2).all(|w| match order {
+            SortOrder::Asc => w[0] < w[1],
+            SortOrder::Desc => w[0] > w[1],
+        });
+        assert!(sorted, "{:?} not in {:?} order", ids, order);
+        assert!(IDS.clone().all(|_id| ids.contains(&_id)));
+    }
+
+    #[test]
+    fn test_cursor_round_trip() -> AppResult<()> {
+        let cursor = encode_cursor(&42i64, SortOrder::Asc);
+        assert!(!cursor.contains("42"));
+        assert_eq!(decode_cursor::<i64>(&cursor, SortOrder::Asc)?, 42);
+        assert!(decode_cursor::<i64>(&cursor, SortOrder::Desc).is_err());
+        assert!(decode_cursor::<i64>("not a cursor", SortOrder::Asc).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_keyset_walk_sync() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        prepare(&db)?;
+        for order in [SortOrder::Asc, SortOrder::Desc] {
+            let mut ids: Vec<i64> = vec![];
+            let mut cursor: Option<String> = None;
+            loop {
+                let page = sample_rec_persistence::find_page(&db, cursor.as_deref(), 7, order)?;
+                assert!(page.items.len() <= 7);
+                ids.extend(page.items.iter().map(|rec| *rec.id()));
+                match page.next_cursor {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+            assert_walk(&ids, order);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_keyset_walk_async_matches_sync() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        prepare(&db)?;
+        for order in [SortOrder::Asc, SortOrder::Desc] {
+            let mut ids: Vec<i64> = vec![];
+            let mut cursor: Option<String> = None;
+            loop {
+                let page =
+                    sample_rec_persistence_async::find_page(&db, cursor.as_deref(), 7, order)
+                        .await?;
+                let sync_page =
+                    sample_rec_persistence::find_page(&db, cursor.as_deref(), 7, order)?;
+                let sync_ids = sync_page.items.iter().map(|rec| *rec.id());
+                assert!(page.items.iter().map(|rec| *rec.id()).eq(sync_ids));
+                assert_eq!(page.next_cursor, sync_page.next_cursor);
+                ids.extend(page.items.iter().map(|rec| *rec.id()));
+                match page.next_cursor {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+            assert_walk(&ids, order);
+        }
+        Ok(())
+    }
+}
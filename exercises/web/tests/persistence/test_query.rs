@@ -0,0 +1,231 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::TestDb;
+    use chrono::{NaiveDate, NaiveDateTime};
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::models::sample_rec::SampleRecord;
+    use web::models::user::User;
+    use web::persistence::page::SortOrder;
+    use web::persistence::query::{Filter, ListQuery};
+    use web::persistence::repository::{AsyncRepository, QueryRepository};
+    use web::persistence::sample_rec_persistence_async::{
+        SampleRecordField as F, SampleRecordRepository,
+    };
+    use web::persistence::user_persistence_async::UserField;
+    use web::persistence::{sample_rec_persistence, user_persistence};
+
+    /// ids 1..=10, odd ones available, named `rec 01`.. with `rec 10` last
+    async fn insert_recs(db: &TestDb) -> AppResult<()> {
+        let recs = (1..=10)
+            .map(|i| {
+                SampleRecord::new(
+                    i,
+                    format!("rec {:02}", i),
+                    i % 2 == 1,
+                    NaiveDateTime::default(),
+                    0,
+                    None,
+                )
+            })
+            .collect::<Vec<_>>();
+        SampleRecordRepository::insert_batch(db, &recs).await?;
+        Ok(())
+    }
+
+    fn ids(recs: &[SampleRecord]) -> Vec<i64> {
+        recs.iter().map(|rec| *rec.id()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_filters() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        insert_recs(&db).await?;
+        SampleRecordRepository::delete(&db, &9).await?;
+
+        let find = |filter: Filter<F>| {
+            let db = &db;
+            async move {
+                let query = ListQuery::new(0, 20)
+                    .filter(filter)
+                    .sort(F::Id, SortOrder::Asc);
+                SampleRecordRepository::find_by(db, &query)
+                    .await
+                    .map(|recs| ids(&recs))
+            }
+        };
+        assert_eq!(find(Filter::eq(F::Id, 3)).await?, [3]);
+        assert_eq!(find(Filter::lt(F::Id, 3)).await?, [1, 2]);
+        assert_eq!(find(Filter::gt(F::Id, 8)).await?, [10]);
+        assert_eq!(
+            find(Filter::ne(F::Available, true)).await?,
+            [2, 4, 6, 8, 10]
+        );
+        assert_eq!(find(Filter::like(F::Name, "rec 1%")).await?, [10]);
+        assert_eq!(find(Filter::is_in(F::Id, [2, 9, 4])).await?, [2, 4]);
+        assert_eq!(
+            find(Filter::is_in(F::Id, Vec::<i64>::new())).await?,
+            [] as [i64; 0]
+        );
+        assert_eq!(find(Filter::between(F::Id, 4, 6)).await?, [4, 5, 6]);
+        assert!(find(Filter::is_null(F::Name)).await?.is_empty());
+        let either = Filter::eq(F::Id, 1).or(Filter::eq(F::Id, 10));
+        assert_eq!(find(either.and(Filter::eq(F::Available, true))).await?, [1]);
+
+        // a value of the wrong type for its field never reaches the database
+        assert!(find(Filter::eq(F::Id, "one")).await.is_err());
+        assert!(find(Filter::like(F::Id, "1%")).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sort_page_and_project() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        insert_recs(&db).await?;
+
+        let query = ListQuery::new(1, 3)
+            .sort(F::Available, SortOrder::Desc)
+            .sort(F::Name, SortOrder::Desc);
+        let recs = SampleRecordRepository::find_by(&db, &query).await?;
+        // available first: 9 7 5 | 3 1 10 | ...
+        assert_eq!(ids(&recs), [3, 1, 10]);
+        // no sort is newest key first, like `find`
+        let recs = SampleRecordRepository::find_by(&db, &ListQuery::new(0, 2)).await?;
+        assert_eq!(ids(&recs), [10, 9]);
+
+        let query = ListQuery::new(0, 2)
+            .filter(Filter::gt(F::Id, 8))
+            .sort(F::Id, SortOrder::Asc)
+            .select(&[F::Id, F::Name]);
+        let rows = SampleRecordRepository::select(&db, &query).await?;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].len(), 2);
+        assert_eq!(rows[0]["id"], 9);
+        assert_eq!(rows[1]["name"], "rec 10");
+        let rows = SampleRecordRepository::select(&db, &ListQuery::new(0, 1)).await?;
+        assert_eq!(rows[0].len(), 5);
+        assert!(rows[0]["created_at"].is_string());
+        assert_eq!(rows[0]["available"], false);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parse() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        insert_recs(&db).await?;
+        let query = ListQuery::<F>::parse(
+            Some("available:eq:true,id:in:1;3;4|name:like:rec 1%,id:between:1;10"),
+            Some("-id"),
+            Some("id"),
+            0,
+            20,
+        )?;
+        let expected = Filter::eq(F::Available, true)
+            .and(Filter::is_in(F::Id, [1i64, 3, 4]).or(Filter::like(F::Name, "rec 1%")))
+            .and(Filter::between(F::Id, 1i64, 10i64));
+        assert_eq!(query.filter, Some(expected));
+        let rows = SampleRecordRepository::select(&db, &query).await?;
+        assert_eq!(
+            rows.iter()
+                .map(|row| row["id"].as_i64().unwrap())
+                .collect::<Vec<_>>(),
+            [3, 1]
+        );
+
+        let created = ListQuery::<F>::parse(
+            Some("created_at:lt:2000-01-01T00:00:00,version:is_null"),
+            None,
+            None,
+            0,
+            20,
+        )?;
+        assert!(SampleRecordRepository::find_by(&db, &created)
+            .await?
+            .is_empty());
+
+        for bad in [
+            "secret:eq:1",
+            "id:eq:one",
+            "id:between:1",
+            "id:near:1",
+            "id",
+        ] {
+            assert!(
+                ListQuery::<F>::parse(Some(bad), None, None, 0, 20).is_err(),
+                "{bad}"
+            );
+        }
+        assert!(ListQuery::<F>::parse(None, Some("-nothing"), None, 0, 20).is_err());
+        assert!(ListQuery::<F>::parse(None, None, Some("id,passwd"), 0, 20).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_find_by() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        for i in 1..=5 {
+            sample_rec_persistence::insert(
+                &db,
+                &SampleRecord::new(
+                    i,
+                    format!("rec {:02}", i),
+                    i % 2 == 1,
+                    NaiveDateTime::default(),
+                    0,
+                    None,
+                ),
+            )?;
+        }
+        sample_rec_persistence::delete(&db, 5)?;
+        let query = ListQuery::new(0, 10)
+            .filter(
+                Filter::eq(F::Available, true)
+                    .or(Filter::like(F::Name, "%02"))
+                    .and(Filter::between(F::Id, 1, 5)),
+            )
+            .sort(F::Name, SortOrder::Desc);
+        let recs = sample_rec_persistence::find_by(&db, &query)?;
+        assert_eq!(ids(&recs), [3, 2, 1]);
+        assert!(sample_rec_persistence::find_by(
+            &db,
+            &ListQuery::new(0, 10).filter(Filter::eq(F::Id, true))
+        )
+        .is_err());
+
+        for i in 1..=3 {
+            user_persistence::insert(
+                &db,
+                &User::new(
+                    i,
+                    NaiveDateTime::default(),
+                    NaiveDateTime::default(),
+                    NaiveDate::from_ymd_opt(1990, 1, i as u32).unwrap(),
+                    "secret".to_string(),
+                    "plain".to_string(),
+                    format!("screen {}", i),
+                    i as i16,
+                    format!("user_{}", i),
+                    1,
+                    "/1/".to_string(),
+                    0,
+                    None,
+                ),
+            )?;
+        }
+        let query = ListQuery::new(0, 10)
+            .filter(Filter::gt(UserField::Status, 1).and(Filter::lt(
+                UserField::Dob,
+                NaiveDate::from_ymd_opt(1990, 1, 3).unwrap(),
+            )))
+            .select(&[UserField::Username]);
+        let rows = user_persistence::select(&db, &query)?;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].len(), 1);
+        assert_eq!(rows[0]["username"], "user_2");
+        Ok(())
+    }
+}
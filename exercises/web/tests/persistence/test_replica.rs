@@ -0,0 +1,151 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::TestDb;
+    use chrono::Local;
+    use diesel::sql_types::Text;
+    use diesel::{sql_query, QueryableByName, RunQueryDsl};
+    use std::time::{Duration, Instant};
+    use tokio::net::TcpListener;
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::models::sample_rec::SampleRecord;
+    use web::persistence::db::Db;
+    use web::persistence::db_config::DbConfig;
+    use web::persistence::repository::AsyncRepository;
+    use web::persistence::sample_rec_persistence_async::SampleRecordRepository;
+
+    const APP_QUERY: &str = "select current_setting('application_name') as app";
+
+    /// The test server stands in for its own replica, told apart by application name.
+    fn replica_of(config: &DbConfig, name: &str) -> DbConfig {
+        let mut replica = config.clone();
+        replica.application_name = name.to_string();
+        replica.min_pool_size = 0;
+        replica
+    }
+
+    fn unreachable_replica(config: &DbConfig) -> DbConfig {
+        let mut replica = replica_of(config, "web_down");
+        replica.port = 1;
+        replica.connect_timeout = Duration::from_secs(1);
+        replica
+    }
+
+    /// Accepts connections and never answers, like a replica behind a partition.
+    async fn silent_replica(config: &DbConfig) -> AppResult<(DbConfig, TcpListener)> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let mut replica = replica_of(config, "web_silent");
+        replica.address = "127.0.0.1".to_string();
+        replica.port = listener.local_addr()?.port();
+        Ok((replica, listener))
+    }
+
+    async fn async_app(db: &Db, read: bool) -> AppResult<String> {
+        let conn = if read {
+            db.async_read_conn().await?
+        } else {
+            db.async_conn().await?
+        };
+        Ok(conn.query_one(APP_QUERY, &[]).await?.get(0))
+    }
+
+    #[derive(QueryableByName)]
+    struct App {
+        #[diesel(sql_type = Text)]
+        app: String,
+    }
+
+    #[tokio::test]
+    async fn test_read_write_routing() -> AppResult<()> {
+        init_logger();
+        let test_db = TestDb::new()?;
+        let config = test_db.config().clone();
+        let replicas = vec![
+            replica_of(&config, "web_replica_a"),
+            replica_of(&config, "web_replica_b"),
+        ];
+        let db = Db::with_replicas(config.clone(), replicas);
+
+        assert_eq!(async_app(&db, false).await?, config.application_name);
+        let mut read_by = vec![async_app(&db, true).await?, async_app(&db, true).await?];
+        read_by.sort();
+        assert_eq!(read_by, ["web_replica_a", "web_replica_b"]);
+        assert_eq!(
+            async_app(&db.primary(), true).await?,
+            config.application_name
+        );
+
+        let sync_app = sql_query(APP_QUERY).get_result::<App>(&mut db.read_conn()?)?;
+        assert!(sync_app.app.starts_with("web_replica_"));
+        let sync_app = sql_query(APP_QUERY).get_result::<App>(&mut db.primary().read_conn()?)?;
+        assert_eq!(sync_app.app, config.application_name);
+
+        // writes on the primary, reads through a replica of the same data
+        let rec = SampleRecord::new(
+            1,
+            "routed".to_string(),
+            false,
+            Local::now().naive_local(),
+            0,
+            None,
+        );
+        SampleRecordRepository::insert(&db, &rec).await?;
+        assert!(SampleRecordRepository::find_by_id(&db, &1).await?.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failover_to_primary() -> AppResult<()> {
+        init_logger();
+        let test_db = TestDb::new()?;
+        let mut config = test_db.config().clone();
+        config.replica_retry = Duration::from_secs(60);
+        let db = Db::with_replicas(config.clone(), vec![unreachable_replica(&config)]);
+        assert_eq!(db.replicas_up(), 1);
+
+        assert_eq!(async_app(&db, true).await?, config.application_name);
+        assert_eq!(db.replicas_up(), 0);
+        // left out of the rotation, no second wait for the dead replica
+        let started = Instant::now();
+        assert_eq!(async_app(&db, true).await?, config.application_name);
+        assert!(started.elapsed() < Duration::from_millis(500));
+
+        // one healthy replica keeps serving the reads
+        let db = Db::with_replicas(
+            config.clone(),
+            vec![
+                unreachable_replica(&config),
+                replica_of(&config, "web_replica"),
+            ],
+        );
+        for _ in 0..3 {
+            assert_eq!(async_app(&db, true).await?, "web_replica");
+        }
+        assert_eq!(db.replicas_up(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replica_timeout() -> AppResult<()> {
+        init_logger();
+        let test_db = TestDb::new()?;
+        let mut config = test_db.config().clone();
+        config.replica_timeout = Duration::from_millis(300);
+        // the listener's backlog completes the handshakes, nothing is accepted
+        let (replica, _listener) = silent_replica(&config).await?;
+        assert_eq!(replica.connect_timeout, config.connect_timeout);
+        let db = Db::with_replicas(config.clone(), vec![replica.clone()]);
+
+        let started = Instant::now();
+        assert_eq!(async_app(&db, true).await?, config.application_name);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(db.replicas_up(), 0);
+
+        let db = Db::with_replicas(config.clone(), vec![replica]);
+        let started = Instant::now();
+        let sync_app = sql_query(APP_QUERY).get_result::<App>(&mut db.read_conn()?)?;
+        assert_eq!(sync_app.app, config.application_name);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        Ok(())
+    }
+}
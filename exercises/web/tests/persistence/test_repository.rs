@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::TestDb;
+    use chrono::{Local, NaiveDateTime};
+    use tokio_postgres::Row;
+    use tracing::info;
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::models::sample_rec::SampleRecord;
+    use web::persistence::repository::{AsyncRepository, SqlParam};
+    use web::persistence::sample_rec_persistence_async::SampleRecordRepository;
+
+    #[tokio::test]
+    async fn test_crud_round_trip() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let mut rec = SampleRecord::new(
+            1001,
+            "name of 1001".to_string(),
+            false,
+            Local::now().naive_local(),
+            0,
+            None,
+        );
+        assert_eq!(SampleRecordRepository::insert(&db, &rec).await?, 1);
+
+        rec.set_name("renamed 1001".to_string());
+        rec.set_available(true);
+        assert_eq!(SampleRecordRepository::update(&db, &rec).await?, 1);
+
+        let found = SampleRecordRepository::find_by_id(&db, rec.id())
+            .await?
+            .expect("inserted record");
+        info!("Found: {:?}", found);
+        assert_eq!(found.name(), "renamed 1001");
+        assert!(found.available());
+
+        assert_eq!(SampleRecordRepository::delete(&db, rec.id()).await?, 1);
+        assert!(SampleRecordRepository::find_by_id(&db, rec.id())
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    /// A second entity over the same table only needs the declarations.
+    struct RecordName {
+        id: i64,
+        name: String,
+        created_at: NaiveDateTime,
+    }
+    struct RecordNameRepository;
+
+    impl AsyncRepository<RecordName, i64> for RecordNameRepository {
+        const TABLE_NAME: &'static str = "test_rec";
+        const KEY_COLUMN: &'static str = "id_";
+        const COLUMNS: &'static [&'static str] = &["id_", "name_", "created_at"];
+
+        fn from_row(row: &Row) -> AppResult<RecordName> {
+            Ok(RecordName {
+                id: row.try_get("id_")?,
+                name: row.try_get("name_")?,
+                created_at: row.try_get("created_at")?,
+            })
+        }
+
+        fn key(val: &RecordName) -> &i64 {
+            &val.id
+        }
+
+        fn to_params(val: &RecordName) -> Vec<SqlParam<'_>> {
+            vec![&val.id, &val.name, &val.created_at]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_with_custom_entity() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let recs = RecordNameRepository::find(&db, 0, 5).await?;
+        assert!(recs.len() <= 5);
+        for rec in recs {
+            info!("{} -> {} at {}", rec.id, rec.name, rec.created_at);
+        }
+        // the offset does not fit a u32
+        assert!(RecordNameRepository::find(&db, 5_000_000, 1000)
+            .await?
+            .is_empty());
+        Ok(())
+    }
+}
@@ -1,64 +1,53 @@
 #[cfg(test)]
 mod tests {
-    use chrono::Local;
-    use tokio::runtime::Runtime;
+    use crate::persistence::test_common::{new_rec, TestDb};
     use tracing::info;
+    use utils::error::app_error::AppResult;
     use utils::log::configuration::init_logger;
     use web::models::sample_rec::SampleRecord;
     use web::persistence::sample_rec_persistence::{find, find_by_id, insert_batch};
 
     #[test]
-    fn test_find() {
+    fn test_find() -> AppResult<()> {
         init_logger();
+        let db = TestDb::new()?;
         let n = 100;
         for i in 0..n {
-            let mut _test_recs = find(0, 10).unwrap();
+            let mut _test_recs = find(&db, 0, 10)?;
             for _test_rec in _test_recs.iter_mut() {
                 info!("{:?}", _test_rec);
             }
         }
+        Ok(())
     }
 
     #[test]
-    fn test_find_by_id() {
+    fn test_find_by_id() -> AppResult<()> {
         init_logger();
-        let test_rec = find_by_id(1i64).expect("TODO: panic message");
+        let db = TestDb::new()?;
+        let test_rec = find_by_id(&db, 1i64)?;
         if let Some(test_rec) = test_rec {
             info!("Found: {:?}", test_rec);
         } else {
             info!("no test rec found");
         }
+        Ok(())
     }
 
     #[test]
-    fn test_insert() {
+    fn test_insert() -> AppResult<()> {
         init_logger();
+        let db = TestDb::new()?;
         let mut ls_test_recs: Vec<SampleRecord> = vec![];
         for i in 1..=10 {
-            // let _val = insert(&TestRecord {
-            //     id: i,
-            //     name: format!("name of {}", i),
-            //     available: i % 2 == 0,
-            //     created_at: Local::now().naive_local(),
-            // })
-            // .expect(format!("insert failed at {} step", i).as_str());
-            let test_rec = SampleRecord::new(
-                i,
-                format!("name of {}", i),
-                false,
-                Local::now().naive_local(),
-            );
+            let test_rec = new_rec(i);
             ls_test_recs.push(test_rec);
         }
-        insert_batch(&ls_test_recs).unwrap();
-
-        let rt = Runtime::new().unwrap();
-
-        // let result = rt.block_on(async {
-        //     let conn = get_async_connection().await?;
-        //     ()
-        // });
+        insert_batch(&db, &ls_test_recs)?;
+        // the schema is fresh, so these are the only rows
+        assert_eq!(find(&db, 0, 100)?.len(), ls_test_recs.len());
 
         info!("Insert successful");
+        Ok(())
     }
 }
@@ -6,16 +6,25 @@ mod tests {
 
     use tracing::info;
 
+    // `AppResult` and `get_async_connection` are imported by `#[derive(Crud)]` below
+    use crate::persistence::test_common::{new_rec, TestDb};
+    use tokio_stream::StreamExt;
     use utils::log::configuration::init_logger;
     use web::models::sample_rec::SampleRecord;
-    use web::persistence::sample_rec_persistence_async::{find, find_by_id, insert_batch};
+    use web::persistence::db::Db;
+    use web::persistence::page::SortOrder;
+    use web::persistence::repository::CopyRowError;
+    use web::persistence::sample_rec_persistence_async::{
+        bulk_insert, find, find_by_id, insert_batch, stream_all,
+    };
 
     #[tokio::test]
     async fn test_find() {
         init_logger();
+        let db = TestDb::new().unwrap();
         let n = 5;
         for i in 0..n {
-            let mut _test_recs = find(i, 10).await.unwrap();
+            let mut _test_recs = find(&db, i, 10).await.unwrap();
             for _test_rec in _test_recs.iter_mut() {
                 info!("{:?}", _test_rec);
             }
@@ -25,7 +34,8 @@ mod tests {
     #[tokio::test]
     async fn test_find_by_id() {
         init_logger();
-        let test_rec = find_by_id(1i64).await.expect("TODO: panic message");
+        let db = TestDb::new().unwrap();
+        let test_rec = find_by_id(&db, 1i64).await.expect("TODO: panic message");
         if let Some(test_rec) = test_rec {
             info!("Found: {:?}", test_rec);
         } else {
@@ -36,23 +46,139 @@ mod tests {
     #[tokio::test]
     async fn test_insert_batch() {
         init_logger();
+        let db = TestDb::new().unwrap();
         let mut ls_test_recs: Vec<SampleRecord> = vec![];
         for i in 11..=20 {
-            let test_rec = SampleRecord::new(
-                i,
-                format!("name of {}", i),
-                false,
-                Local::now().naive_local(),
-            );
+            let test_rec = new_rec(i);
             ls_test_recs.push(test_rec);
         }
-        let exec = insert_batch(&ls_test_recs).await;
+        let exec = insert_batch(&db, &ls_test_recs).await;
         match exec {
             Ok(_) => info!("Insert successful"),
             Err(e) => info!("Insert failed:{}", e),
         }
     }
 
+    async fn count_between(db: &Db, from: i64, to: i64) -> AppResult<i64> {
+        let conn = db.async_conn().await?;
+        let row = conn
+            .query_one(
+                "select count(*) from test_rec where id_ between $1 and $2",
+                &[&from, &to],
+            )
+            .await?;
+        Ok(row.get(0))
+    }
+
+    #[tokio::test]
+    async fn test_bulk_insert() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let conn = db.async_conn().await?;
+        conn.execute(
+            "delete from test_rec where id_ between 100000 and 119999",
+            &[],
+        )
+        .await?;
+        let recs = (100000..120000)
+            .map(|i| {
+                SampleRecord::new(
+                    i,
+                    format!("bulk {}", i),
+                    i % 2 == 0,
+                    Local::now().naive_local(),
+                    0,
+                    None,
+                )
+            })
+            .collect::<Vec<SampleRecord>>();
+        assert_eq!(bulk_insert(&db, &recs).await?, 20000);
+        assert_eq!(count_between(&db, 100000, 119999).await?, 20000);
+        let found = find_by_id(&db, 100042)
+            .await?
+            .expect("bulk inserted record");
+        assert_eq!(found.name(), "bulk 100042");
+        conn.execute(
+            "delete from test_rec where id_ between 100000 and 119999",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bulk_insert_row_error() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let conn = db.async_conn().await?;
+        conn.execute(
+            "delete from test_rec where id_ between 120000 and 120099",
+            &[],
+        )
+        .await?;
+        let mut recs = (120000..120100)
+            .map(|i| {
+                SampleRecord::new(
+                    i,
+                    format!("bulk {}", i),
+                    false,
+                    Local::now().naive_local(),
+                    0,
+                    None,
+                )
+            })
+            .collect::<Vec<SampleRecord>>();
+        // row 42 repeats the key of row 10
+        recs[42].set_id(120010);
+        let err = bulk_insert(&db, &recs).await.expect_err("duplicate key");
+        info!("Bulk insert failed: {}", err);
+        let copy_err = err.downcast_ref::<CopyRowError>().expect("row level error");
+        assert_eq!(copy_err.row, Some(42));
+        assert_eq!(count_between(&db, 120000, 120099).await?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_all() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let recs = (1..=50).map(new_rec).collect::<Vec<SampleRecord>>();
+        insert_batch(&db, &recs).await?;
+        let conn = db.async_conn().await?;
+        let total: i64 = conn
+            .query_one("select count(*) from test_rec", &[])
+            .await?
+            .get(0);
+        drop(conn);
+
+        let rows = stream_all(&db, SortOrder::Asc).await?;
+        tokio::pin!(rows);
+        let mut count = 0i64;
+        let mut last_id = i64::MIN;
+        while let Some(rec) = rows.next().await {
+            let rec = rec?;
+            assert!(*rec.id() > last_id);
+            last_id = *rec.id();
+            count += 1;
+        }
+        assert_eq!(count, total);
+        assert_eq!(total, 50);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_drop_releases_connection() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        // more streams than the pool has connections, each dropped early
+        for _ in 0..20 {
+            let rows = stream_all(&db, SortOrder::Desc).await?;
+            tokio::pin!(rows);
+            let _first = rows.next().await.transpose()?;
+        }
+        Ok(())
+    }
+
     // record! {
     // #[derive(Crud(table_name="test_rec"))]
     #[derive(Crud)]
@@ -0,0 +1,84 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::{new_rec, new_user, TestDb};
+    use chrono::NaiveDateTime;
+    use tokio_stream::StreamExt;
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::persistence::page::SortOrder;
+    use web::persistence::repository::AsyncRepository;
+    use web::persistence::sample_rec_persistence_async::SampleRecordRepository;
+    use web::persistence::user_persistence;
+
+    #[tokio::test]
+    async fn test_delete_restore_purge() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        SampleRecordRepository::insert_batch(&db, &[new_rec(6001), new_rec(6002)]).await?;
+        let rec = SampleRecordRepository::find_by_id(&db, &6001)
+            .await?
+            .unwrap();
+        assert!(rec.deleted_at().is_none());
+
+        assert_eq!(SampleRecordRepository::delete(&db, &6001).await?, 1);
+        assert_eq!(SampleRecordRepository::delete(&db, &6001).await?, 0);
+        // still there, only marked
+        let conn = db.async_conn().await?;
+        let row = conn
+            .query_one("select deleted_at from test_rec where id_ = 6001", &[])
+            .await?;
+        assert!(row.get::<_, Option<NaiveDateTime>>(0).is_some());
+
+        assert!(SampleRecordRepository::find_by_id(&db, &6001)
+            .await?
+            .is_none());
+        let listed = SampleRecordRepository::find(&db, 0, 10).await?;
+        assert_eq!(listed.iter().map(|r| *r.id()).collect::<Vec<_>>(), [6002]);
+        let page = SampleRecordRepository::find_page(&db, None, 10, SortOrder::Asc).await?;
+        assert_eq!(page.items.len(), 1);
+        let streamed = SampleRecordRepository::stream(&db, SortOrder::Asc)
+            .await?
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(streamed.len(), 1);
+        // a deleted row is not updated, and that is no conflict either
+        assert_eq!(SampleRecordRepository::update(&db, &rec).await?, 0);
+
+        assert_eq!(SampleRecordRepository::restore(&db, &6001).await?, 1);
+        assert_eq!(SampleRecordRepository::restore(&db, &6001).await?, 0);
+        assert_eq!(SampleRecordRepository::find(&db, 0, 10).await?.len(), 2);
+
+        SampleRecordRepository::delete(&db, &6001).await?;
+        assert_eq!(SampleRecordRepository::purge(&db, &6001).await?, 1);
+        assert_eq!(SampleRecordRepository::restore(&db, &6001).await?, 0);
+        let row = conn
+            .query_opt("select 1 from test_rec where id_ = 6001", &[])
+            .await?;
+        assert!(row.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_delete_restore_purge() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        user_persistence::insert(&db, &new_user(6011))?;
+        assert!(user_persistence::delete(&db, 6011)?);
+        assert!(!user_persistence::delete(&db, 6011)?);
+        assert!(user_persistence::find_by_id(&db, 6011)?.is_none());
+        assert!(user_persistence::find(&db, 0, 10)?.is_empty());
+        assert!(user_persistence::find_page(&db, None, 10, SortOrder::Desc)?
+            .items
+            .is_empty());
+        assert!(!user_persistence::update(&db, &new_user(6011))?);
+
+        assert!(user_persistence::restore(&db, 6011)?);
+        assert!(!user_persistence::restore(&db, 6011)?);
+        assert!(user_persistence::find_by_id(&db, 6011)?.is_some());
+
+        assert!(user_persistence::purge(&db, 6011)?);
+        assert!(!user_persistence::restore(&db, 6011)?);
+        assert!(!user_persistence::purge(&db, 6011)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+/// Runs against a local Postgres with `ssl = on` and a self-signed certificate
+/// for `localhost`, e.g. the Debian snakeoil one. Point `DB_TEST_SSL_ROOT_CERT`
+/// at the server certificate when it lives elsewhere.
+#[cfg(test)]
+mod tests {
+    use diesel::sql_types::Bool;
+    use diesel::{sql_query, QueryableByName, RunQueryDsl};
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tracing::info;
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::persistence::common::{create_async_conn_pool, create_conn_pool};
+    use web::persistence::db_config::{DbConfig, SslMode};
+
+    const SSL_QUERY: &str = "select ssl from pg_stat_ssl where pid = pg_backend_pid()";
+
+    fn server_cert() -> Option<PathBuf> {
+        let path = std::env::var("DB_TEST_SSL_ROOT_CERT")
+            .unwrap_or_else(|_| "/etc/ssl/certs/ssl-cert-snakeoil.pem".to_string());
+        let path = PathBuf::from(path);
+        if path.exists() {
+            Some(path)
+        } else {
+            info!("No server certificate at {:?}, skip TLS test", path);
+            None
+        }
+    }
+
+    fn config_with(ssl_mode: SslMode, root_cert: Option<PathBuf>) -> AppResult<DbConfig> {
+        let mut config = DbConfig::load()?;
+        config.address = "localhost".to_string();
+        config.ssl_mode = ssl_mode;
+        config.ssl_root_cert = root_cert;
+        config.min_pool_size = 0;
+        config.max_pool_size = 1;
+        // the pool retries a failed handshake until this runs out
+        config.connect_timeout = Duration::from_secs(2);
+        Ok(config)
+    }
+
+    async fn async_ssl_in_use(config: &DbConfig) -> AppResult<bool> {
+        let pool = create_async_conn_pool(config).await?;
+        let conn = pool.get().await?;
+        Ok(conn.query_one(SSL_QUERY, &[]).await?.get(0))
+    }
+
+    #[derive(QueryableByName)]
+    struct SslInUse {
+        #[diesel(sql_type = Bool)]
+        ssl: bool,
+    }
+
+    fn sync_ssl_in_use(config: &DbConfig) -> AppResult<bool> {
+        let pool = create_conn_pool(config)?;
+        let mut conn = pool.get()?;
+        Ok(sql_query(SSL_QUERY).get_result::<SslInUse>(&mut conn)?.ssl)
+    }
+
+    #[tokio::test]
+    async fn test_async_ssl_modes() -> AppResult<()> {
+        init_logger();
+        let Some(cert) = server_cert() else {
+            return Ok(());
+        };
+        assert!(!async_ssl_in_use(&config_with(SslMode::Disable, None)?).await?);
+        // a stale root certificate does not matter without TLS
+        let stale = Some(PathBuf::from("/nonexistent/root.crt"));
+        assert!(!async_ssl_in_use(&config_with(SslMode::Disable, stale)?).await?);
+        assert!(async_ssl_in_use(&config_with(SslMode::Prefer, None)?).await?);
+        assert!(async_ssl_in_use(&config_with(SslMode::Require, None)?).await?);
+        assert!(async_ssl_in_use(&config_with(SslMode::VerifyFull, Some(cert.clone()))?).await?);
+
+        // a bundle without the self-signed certificate
+        let other_ca = PathBuf::from("/etc/ssl/certs/ca-certificates.crt");
+        if other_ca.exists() {
+            let untrusted = config_with(SslMode::VerifyFull, Some(other_ca))?;
+            assert!(async_ssl_in_use(&untrusted).await.is_err());
+        }
+        // the certificate is for localhost only
+        let mut wrong_host = config_with(SslMode::VerifyFull, Some(cert))?;
+        wrong_host.address = "127.0.0.1".to_string();
+        assert!(async_ssl_in_use(&wrong_host).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_ssl_modes() -> AppResult<()> {
+        init_logger();
+        let Some(cert) = server_cert() else {
+            return Ok(());
+        };
+        assert!(!sync_ssl_in_use(&config_with(SslMode::Disable, None)?)?);
+        let stale = Some(PathBuf::from("/nonexistent/root.crt"));
+        assert!(!sync_ssl_in_use(&config_with(SslMode::Disable, stale)?)?);
+        assert!(sync_ssl_in_use(&config_with(SslMode::Require, None)?)?);
+        assert!(sync_ssl_in_use(&config_with(
+            SslMode::VerifyFull,
+            Some(cert)
+        )?)?);
+        Ok(())
+    }
+}
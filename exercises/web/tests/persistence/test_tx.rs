@@ -0,0 +1,184 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::{new_rec, new_user, TestDb};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::persistence::repository::AsyncRepository;
+    use web::persistence::sample_rec_persistence_async::SampleRecordRepository;
+    use web::persistence::tx::retry_serializable;
+    use web::persistence::user_persistence_async::UserRepository;
+    use web::persistence::{sample_rec_persistence, user_persistence};
+
+    #[tokio::test]
+    async fn test_commit_across_tables() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        SampleRecordRepository::delete(&db, &4001).await?;
+        UserRepository::delete(&db, &4001).await?;
+
+        let tx = db.begin().await?;
+        SampleRecordRepository::insert_with(tx.client(), &new_rec(4001)).await?;
+        UserRepository::insert_with(tx.client(), &new_user(4001)).await?;
+        // not visible outside before commit
+        assert!(SampleRecordRepository::find_by_id(&db, &4001)
+            .await?
+            .is_none());
+        tx.commit().await?;
+
+        assert!(SampleRecordRepository::find_by_id(&db, &4001)
+            .await?
+            .is_some());
+        assert!(UserRepository::find_by_id(&db, &4001).await?.is_some());
+        SampleRecordRepository::delete(&db, &4001).await?;
+        UserRepository::delete(&db, &4001).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rollback_and_drop() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        SampleRecordRepository::delete(&db, &4002).await?;
+
+        let tx = db.begin().await?;
+        SampleRecordRepository::insert_with(tx.client(), &new_rec(4002)).await?;
+        tx.rollback().await?;
+        assert!(SampleRecordRepository::find_by_id(&db, &4002)
+            .await?
+            .is_none());
+
+        {
+            let tx = db.begin().await?;
+            SampleRecordRepository::insert_with(tx.client(), &new_rec(4002)).await?;
+        }
+        tokio::task::yield_now().await;
+        assert!(SampleRecordRepository::find_by_id(&db, &4002)
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_finish() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+
+        let tx = db.begin().await?;
+        SampleRecordRepository::insert_with(tx.client(), &new_rec(4005)).await?;
+        let rs: AppResult<()> = Err(anyhow::anyhow!("failed later"));
+        let err = tx.finish(rs).await.unwrap_err();
+        assert_eq!(err.to_string(), "failed later");
+        // rolled back already, no yield needed unlike a dropped `Tx`
+        assert!(SampleRecordRepository::find_by_id(&db, &4005)
+            .await?
+            .is_none());
+
+        let tx = db.begin().await?;
+        let count = SampleRecordRepository::insert_with(tx.client(), &new_rec(4005)).await;
+        assert_eq!(tx.finish(count).await?, 1);
+        assert!(SampleRecordRepository::find_by_id(&db, &4005)
+            .await?
+            .is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_savepoint() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        SampleRecordRepository::delete(&db, &4003).await?;
+        SampleRecordRepository::delete(&db, &4004).await?;
+
+        let tx = db.begin().await?;
+        SampleRecordRepository::insert_with(tx.client(), &new_rec(4003)).await?;
+        tx.savepoint("before_second").await?;
+        SampleRecordRepository::insert_with(tx.client(), &new_rec(4004)).await?;
+        // a failing statement aborts only up to the savepoint
+        assert!(
+            SampleRecordRepository::insert_with(tx.client(), &new_rec(4004))
+                .await
+                .is_err()
+        );
+        tx.rollback_to("before_second").await?;
+        assert!(tx.savepoint("bad name;").await.is_err());
+        tx.commit().await?;
+
+        assert!(SampleRecordRepository::find_by_id(&db, &4003)
+            .await?
+            .is_some());
+        assert!(SampleRecordRepository::find_by_id(&db, &4004)
+            .await?
+            .is_none());
+        SampleRecordRepository::delete(&db, &4003).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_serializable() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        SampleRecordRepository::delete(&db, &4005).await?;
+        SampleRecordRepository::insert(&db, &new_rec(4005)).await?;
+
+        let attempts = AtomicU32::new(0);
+        retry_serializable(3, || async {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            let tx = db.begin_serializable().await?;
+            let mut rec = SampleRecordRepository::find_by_id_with(tx.client(), &4005)
+                .await?
+                .expect("inserted record");
+            if attempt == 1 {
+                // a concurrent writer commits after our snapshot was taken
+                let other = db.async_conn().await?;
+                other
+                    .execute("update test_rec set name_ = 'other' where id_ = 4005", &[])
+                    .await?;
+            }
+            rec.set_name(format!("attempt {}", attempt));
+            SampleRecordRepository::update_with(tx.client(), &rec).await?;
+            tx.commit().await
+        })
+        .await?;
+
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        let rec = SampleRecordRepository::find_by_id(&db, &4005)
+            .await?
+            .unwrap();
+        assert_eq!(rec.name(), "attempt 2");
+        SampleRecordRepository::delete(&db, &4005).await?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_with_tx() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        sample_rec_persistence::delete(&db, 4011)?;
+        user_persistence::delete(&db, 4011)?;
+
+        let rs: AppResult<()> = db.with_tx(|conn| {
+            sample_rec_persistence::insert_with(conn, &new_rec(4011))?;
+            user_persistence::insert_with(conn, &new_user(4011))?;
+            Err(anyhow::anyhow!("abort the unit of work"))
+        });
+        assert!(rs.is_err());
+        assert!(sample_rec_persistence::find_by_id(&db, 4011)?.is_none());
+        assert!(user_persistence::find_by_id(&db, 4011)?.is_none());
+
+        let attempts = AtomicU32::new(0);
+        db.with_serializable_tx(3, |conn| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            sample_rec_persistence::insert_with(conn, &new_rec(4011))?;
+            user_persistence::insert_with(conn, &new_user(4011))?;
+            Ok(())
+        })?;
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert!(sample_rec_persistence::find_by_id(&db, 4011)?.is_some());
+        assert!(user_persistence::find_by_id(&db, 4011)?.is_some());
+
+        sample_rec_persistence::delete(&db, 4011)?;
+        user_persistence::delete(&db, 4011)?;
+        Ok(())
+    }
+}
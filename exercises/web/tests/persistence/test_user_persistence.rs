@@ -1,11 +1,55 @@
 #[cfg(test)]
 mod tests {
+    use crate::persistence::test_common::{new_user, TestDb};
     use tracing::info;
+    use utils::error::app_error::AppResult;
     use utils::log::configuration::init_logger;
+    use web::models::user::User;
+    use web::persistence::user_persistence::{
+        delete, find, find_by_id, insert, insert_batch, update,
+    };
 
     #[test]
-    fn test_find_user() {
+    fn test_find_user() -> AppResult<()> {
         init_logger();
+        let db = TestDb::new()?;
+        let users = find(&db, 0, 10)?;
+        for user in users.iter() {
+            info!("{:?}", user);
+        }
         info!("Test user persistence success!");
+        Ok(())
+    }
+
+    #[test]
+    fn test_user_crud() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let mut user = new_user(101);
+        assert!(insert(&db, &user)?);
+
+        user.set_screenname("renamed 101".to_string());
+        assert!(update(&db, &user)?);
+        let found = find_by_id(&db, *user.id())?.expect("inserted user");
+        assert_eq!(found.screenname(), "renamed 101");
+
+        assert!(delete(&db, *user.id())?);
+        assert!(!delete(&db, *user.id())?);
+        assert!(find_by_id(&db, *user.id())?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_batch_user() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let ls_users = (111..=115).map(new_user).collect::<Vec<User>>();
+        insert_batch(&db, &ls_users)?;
+        let page = find(&db, 0, 100)?;
+        assert_eq!(page.len(), ls_users.len());
+        assert!(ls_users
+            .iter()
+            .all(|u| page.iter().any(|p| p.id() == u.id())));
+        Ok(())
     }
 }
@@ -0,0 +1,50 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::TestDb;
+    use chrono::{Local, NaiveDate};
+    use tracing::info;
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::models::user::User;
+    use web::persistence::repository::AsyncRepository;
+    use web::persistence::user_persistence_async::UserRepository;
+
+    #[tokio::test]
+    async fn test_user_crud() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let now = Local::now().naive_local();
+        let mut user = User::new(
+            201,
+            now,
+            now,
+            NaiveDate::from_ymd_opt(1991, 2, 3).unwrap(),
+            "secret".to_string(),
+            "plain".to_string(),
+            "screen 201".to_string(),
+            1,
+            "user_201".to_string(),
+            1,
+            "/1/".to_string(),
+            0,
+            None,
+        );
+        assert_eq!(UserRepository::insert(&db, &user).await?, 1);
+
+        user.set_status(2);
+        assert_eq!(UserRepository::update(&db, &user).await?, 1);
+        let found = UserRepository::find_by_id(&db, user.id())
+            .await?
+            .expect("inserted user");
+        info!("Found: {:?}", found);
+        assert_eq!(*found.status(), 2);
+        assert_eq!(found.dob(), user.dob());
+
+        let page = UserRepository::find(&db, 0, 10).await?;
+        assert!(page.iter().any(|u| u.id() == user.id()));
+
+        assert!(UserRepository::delete(&db, user.id()).await? == 1);
+        assert!(UserRepository::find_by_id(&db, user.id()).await?.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::{new_rec, new_user, TestDb};
+    use chrono::{Local, NaiveDateTime};
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::persistence::repository::{AsyncRepository, VersionConflict};
+    use web::persistence::sample_rec_persistence_async::SampleRecordRepository;
+    use web::persistence::user_persistence_async::UserRepository;
+    use web::persistence::{sample_rec_persistence, user_persistence};
+
+    #[tokio::test]
+    async fn test_stale_update_conflicts() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        SampleRecordRepository::insert(&db, &new_rec(5001)).await?;
+        let mut first = SampleRecordRepository::find_by_id(&db, &5001)
+            .await?
+            .unwrap();
+        let mut second = first.clone();
+
+        first.set_name("first".to_string());
+        assert_eq!(SampleRecordRepository::update(&db, &first).await?, 1);
+        second.set_name("second".to_string());
+        let err = SampleRecordRepository::update(&db, &second)
+            .await
+            .unwrap_err();
+        let conflict = err.downcast_ref::<VersionConflict>().unwrap();
+        assert_eq!(conflict.table, "test_rec");
+        assert_eq!(conflict.key, "5001");
+
+        let stored = SampleRecordRepository::find_by_id(&db, &5001)
+            .await?
+            .unwrap();
+        assert_eq!(stored.name(), "first");
+        assert_eq!(*stored.version(), 1);
+        // a missing row is still a plain miss
+        assert_eq!(
+            SampleRecordRepository::update(&db, &new_rec(5002)).await?,
+            0
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_audit_timestamps() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let before = Local::now().naive_local();
+        // whatever the caller sends, the database sets the timestamps
+        UserRepository::insert(&db, &new_user(5011)).await?;
+        let mut user = UserRepository::find_by_id(&db, &5011).await?.unwrap();
+        assert!(*user.created_date() > NaiveDateTime::default());
+        assert_eq!(user.created_date(), user.modified_date());
+        assert_eq!(*user.version(), 0);
+
+        let created = *user.created_date();
+        user.set_created_date(before);
+        user.set_status(2);
+        UserRepository::update(&db, &user).await?;
+        let updated = UserRepository::find_by_id(&db, &5011).await?.unwrap();
+        assert_eq!(*updated.created_date(), created);
+        assert!(*updated.modified_date() > created);
+        assert_eq!(*updated.version(), 1);
+
+        SampleRecordRepository::insert(&db, &new_rec(5012)).await?;
+        let rec = SampleRecordRepository::find_by_id(&db, &5012)
+            .await?
+            .unwrap();
+        assert!(*rec.created_at() > NaiveDateTime::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_stale_update_conflicts() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        sample_rec_persistence::insert(&db, &new_rec(5021))?;
+        let rec = sample_rec_persistence::find_by_id(&db, 5021)?.unwrap();
+        sample_rec_persistence::update(&db, &rec)?;
+        let err = sample_rec_persistence::update(&db, &rec).unwrap_err();
+        assert!(err.downcast_ref::<VersionConflict>().is_some());
+
+        user_persistence::insert(&db, &new_user(5021))?;
+        let user = user_persistence::find_by_id(&db, 5021)?.unwrap();
+        assert!(user_persistence::update(&db, &user)?);
+        let updated = user_persistence::find_by_id(&db, 5021)?.unwrap();
+        assert_eq!(*updated.version(), 1);
+        assert!(updated.modified_date() > user.modified_date());
+        let err = user_persistence::update(&db, &user).unwrap_err();
+        assert!(err.downcast_ref::<VersionConflict>().is_some());
+        assert!(!user_persistence::update(&db, &new_user(5022))?);
+        Ok(())
+    }
+}
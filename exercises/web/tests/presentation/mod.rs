@@ -0,0 +1,94 @@
+use crate::persistence::test_common::new_user;
+use axum::body::{Body, Bytes};
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use serde_json::Value;
+use tower::ServiceExt;
+use web::models::user_status::UserStatus;
+use web::persistence::user_persistence_async;
+use web::presentation::router;
+use web::services::token_service::{Principal, TokenUse};
+use web::utils::app_context::AppContext;
+
+mod test_auth_handler;
+mod test_health_handler;
+mod test_openapi;
+mod test_sample_rec_handler;
+mod test_user_handler;
+
+/// An access token of `ctx` for the user `user_id`, stored with `status`
+/// first: `require_auth` goes by the status in the database.
+pub(crate) async fn token_for(ctx: &AppContext, user_id: i64, status: UserStatus) -> String {
+    let conn = ctx.db.async_conn().await.unwrap();
+    let updated = conn
+        .execute(
+            "update user_ set status_ = $2 where id_ = $1",
+            &[&user_id, &status.code()],
+        )
+        .await
+        .unwrap();
+    drop(conn);
+    if updated == 0 {
+        let mut user = new_user(user_id);
+        user.set_status(status.code());
+        user_persistence_async::insert(&ctx.db, &user)
+            .await
+            .unwrap();
+    }
+    let principal = Principal {
+        user_id,
+        username: format!("user_{user_id}"),
+        status,
+    };
+    let claims = ctx.tokens.claims(&principal, TokenUse::Access).unwrap();
+    ctx.tokens.sign(&claims).unwrap()
+}
+
+/// Sends one request through the full router, in process, as an admin and
+/// decodes the JSON body.
+pub(crate) async fn send(
+    ctx: &AppContext,
+    method: &str,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let token = token_for(ctx, 0, UserStatus::Admin).await;
+    send_as(ctx, Some(&token), method, uri, body).await
+}
+
+pub(crate) async fn send_as(
+    ctx: &AppContext,
+    token: Option<&str>,
+    method: &str,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let (status, bytes) = send_raw(ctx, token, method, uri, body).await;
+    let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+    (status, body)
+}
+
+pub(crate) async fn send_raw(
+    ctx: &AppContext,
+    token: Option<&str>,
+    method: &str,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, Bytes) {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json");
+    if let Some(token) = token {
+        request = request.header("authorization", format!("Bearer {token}"));
+    }
+    let request = match body {
+        Some(body) => request.body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+    let response = router(ctx.clone()).oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (status, bytes)
+}
@@ -0,0 +1,160 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::{new_user, TestDb};
+    use crate::presentation::{send_as, send_raw};
+    use axum::http::StatusCode;
+    use serde_json::{json, Value};
+    use utils::log::configuration::init_logger;
+    use web::models::user::User;
+    use web::models::user_status::UserStatus;
+    use web::persistence::user_persistence_async;
+    use web::services::password_hasher::{HashMethod, PasswordHasher};
+    use web::services::user_service;
+    use web::utils::app_context::AppContext;
+
+    async fn create_user(ctx: &AppContext, _id: i64, status: UserStatus) -> User {
+        let mut user = new_user(_id);
+        user.set_status(status.code());
+        user_service::create(&ctx.db, &ctx.hasher, &user)
+            .await
+            .unwrap()
+    }
+
+    async fn login(ctx: &AppContext, username: &str, passwd: &str) -> (StatusCode, Value) {
+        let body = json!({ "username": username, "passwd": passwd });
+        send_as(ctx, None, "POST", "/auth/login", Some(body)).await
+    }
+
+    fn text(val: &Value) -> &str {
+        val.as_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_login_refresh_logout() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let hasher = PasswordHasher::new(HashMethod::Pbkdf2Sha256 { iterations: 1000 }).unwrap();
+        let ctx = AppContext::new(db.clone()).unwrap().with_hasher(hasher);
+        create_user(&ctx, 1, UserStatus::Active).await;
+        create_user(&ctx, 2, UserStatus::Disabled).await;
+
+        let (status, _) = send_raw(&ctx, None, "POST", "/auth/login", Some(json!({}))).await;
+        assert!(status.is_client_error());
+        let (status, _) = login(&ctx, "user_1", "wrong").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = login(&ctx, "user_2", "secret").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, pair) = login(&ctx, "user_1", "secret").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(pair["token_type"], "Bearer");
+        assert_eq!(pair["expires_in"], 900);
+        let access = text(&pair["access_token"]);
+        let (status, me) = send_as(&ctx, Some(access), "GET", "/auth/me", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            me,
+            json!({ "user_id": 1, "username": "user_1", "status": "active" })
+        );
+
+        // refresh tokens are single use
+        let refresh = json!({ "refresh_token": pair["refresh_token"] });
+        let (status, next) =
+            send_as(&ctx, None, "POST", "/auth/refresh", Some(refresh.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(next["refresh_token"], pair["refresh_token"]);
+        let (status, _) = send_as(&ctx, None, "POST", "/auth/refresh", Some(refresh)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let refresh = json!({ "refresh_token": next["refresh_token"] });
+        let (status, _) = send_as(&ctx, None, "POST", "/auth/logout", Some(refresh.clone())).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send_as(&ctx, None, "POST", "/auth/refresh", Some(refresh)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // a user disabled since login gets no new tokens, and the access
+        // token at hand stops working at once
+        let (_, pair) = login(&ctx, "user_1", "secret").await;
+        let access = text(&pair["access_token"]);
+        let (status, _) = send_as(&ctx, Some(access), "GET", "/auth/me", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let mut user = user_persistence_async::find_by_id(&db, 1)
+            .await
+            .unwrap()
+            .unwrap();
+        user.set_status(UserStatus::Disabled.code());
+        user_persistence_async::update(&db, &user).await.unwrap();
+        let refresh = json!({ "refresh_token": pair["refresh_token"] });
+        let (status, _) = send_as(&ctx, None, "POST", "/auth/refresh", Some(refresh)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send_as(&ctx, Some(access), "GET", "/auth/me", None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_protected_routes() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let hasher = PasswordHasher::new(HashMethod::Pbkdf2Sha256 { iterations: 1000 }).unwrap();
+        let ctx = AppContext::new(db.clone()).unwrap().with_hasher(hasher);
+        let user = create_user(&ctx, 1, UserStatus::Active).await;
+        create_user(&ctx, 3, UserStatus::Admin).await;
+
+        let (status, bytes) = send_raw(&ctx, None, "GET", "/users", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(!bytes.is_empty());
+        let (status, _) = send_as(&ctx, Some("not.a.token"), "GET", "/users", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (_, pair) = login(&ctx, "user_1", "secret").await;
+        let access = text(&pair["access_token"]);
+        // a refresh token is no access token
+        let refresh = text(&pair["refresh_token"]);
+        let (status, _) = send_as(&ctx, Some(refresh), "GET", "/users", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        // nor is one signed by another key
+        let other = AppContext::new(db.clone()).unwrap();
+        let (status, _) = send_as(&other, Some(access), "GET", "/users", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = send_as(&ctx, Some(access), "GET", "/users/3", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send_as(&ctx, Some(access), "DELETE", "/users/3", None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (_, admin) = login(&ctx, "user_3", "secret").await;
+        let admin = text(&admin["access_token"]);
+
+        let passwd = json!({ "version": user.version(), "current": "secret", "new": "changed" });
+        let (status, _) = send_as(
+            &ctx,
+            Some(admin),
+            "PUT",
+            "/users/1/passwd",
+            Some(passwd.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send_as(&ctx, Some(access), "PUT", "/users/1/passwd", Some(passwd)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = login(&ctx, "user_1", "changed").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = send_as(&ctx, Some(admin), "DELETE", "/users/1", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = login(&ctx, "user_1", "changed").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        // nor do the tokens of a deleted account work
+        let (status, _) = send_as(&ctx, Some(access), "GET", "/users/3", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // a demoted admin's token carries no admin rights anymore
+        let mut demoted = user_persistence_async::find_by_id(&db, 3)
+            .await
+            .unwrap()
+            .unwrap();
+        demoted.set_status(UserStatus::Active.code());
+        user_persistence_async::update(&db, &demoted).await.unwrap();
+        let (status, _) = send_as(&ctx, Some(admin), "DELETE", "/users/3", None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+}
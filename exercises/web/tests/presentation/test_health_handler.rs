@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::TestDb;
+    use crate::presentation::{send, send_as, send_raw};
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use utils::log::configuration::init_logger;
+    use web::persistence::migration::migrate_down;
+    use web::utils::app_context::AppContext;
+
+    #[tokio::test]
+    async fn test_live() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let ctx = AppContext::new(db.clone()).unwrap();
+
+        let (status, body) = send_as(&ctx, None, "GET", "/health/live", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "status": "up", "checks": {} }));
+    }
+
+    #[tokio::test]
+    async fn test_ready() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let ctx = AppContext::new(db.clone()).unwrap();
+
+        let (status, body) = send_as(&ctx, None, "GET", "/health/ready", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!({
+                "status": "up",
+                "checks": { "database": "up", "migrations": "up to date" },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_not_ready_with_pending_migrations() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let mut conn = db.async_conn().await.unwrap();
+        migrate_down(&mut conn, 1).await.unwrap();
+        drop(conn);
+        let ctx = AppContext::new(db.clone()).unwrap();
+
+        let (status, body) = send_as(&ctx, None, "GET", "/health/ready", None).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "down");
+        assert_eq!(body["checks"]["database"], "up");
+        let migrations = body["checks"]["migrations"].as_str().unwrap();
+        assert!(migrations.starts_with("pending"), "{migrations}");
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let ctx = AppContext::new(db.clone()).unwrap();
+        send_as(&ctx, None, "GET", "/health/live", None).await;
+        let (status, _) = send(&ctx, "GET", "/sample-records/987654321", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        send_as(&ctx, None, "GET", "/no/such/route", None).await;
+
+        let (status, bytes) = send_raw(&ctx, None, "GET", "/metrics", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        // other tests share the process wide metrics, so only check for lines
+        for expected in [
+            "# TYPE http_requests_total counter",
+            r#"http_requests_total{method="GET",route="/health/live",status="200"} "#,
+            // the matched route, not the id
+            r#"http_requests_total{method="GET",route="/sample-records/{id}",status="404"} "#,
+            r#"http_requests_total{method="GET",route="unmatched",status="404"} "#,
+            r#"http_request_duration_seconds_bucket{method="GET",route="/health/live",le="+Inf"} "#,
+            r#"db_query_duration_seconds_count{table="test_rec",op="find_by_id"} "#,
+            "# TYPE db_pool_connections gauge",
+            r#"db_pool_max_connections{server="#,
+            r#"pool="async"} 4"#,
+            "# TYPE db_pool_checkouts_timed_out_total counter",
+        ] {
+            assert!(text.contains(expected), "{expected} missing from\n{text}");
+        }
+        assert!(!text.contains("987654321"));
+    }
+}
@@ -0,0 +1,113 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::TestDb;
+    use crate::presentation::{send_raw, token_for};
+    use axum::http::StatusCode;
+    use chrono::{Duration, NaiveDateTime};
+    use serde_json::{json, Value};
+    use std::env;
+    use std::fs;
+    use utils::log::configuration::init_logger;
+    use utils::openapi::{ApiSchema, Components};
+    use utils::validation::Regex;
+    use web::dto::user_dto::NewUser;
+    use web::models::sample_rec::SampleRecord;
+    use web::models::user_status::UserStatus;
+    use web::presentation::document;
+    use web::utils::app_context::AppContext;
+
+    const SPEC_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+
+    /// The committed `openapi.json` is what the code generates; rewrite it with
+    /// `UPDATE_OPENAPI=1 cargo test -p web --test lib openapi`.
+    #[test]
+    fn test_openapi_spec_is_current() {
+        let generated = document();
+        if env::var("UPDATE_OPENAPI").is_ok() {
+            let text = serde_json::to_string_pretty(&generated).unwrap();
+            fs::write(SPEC_FILE, text + "\n").unwrap();
+        }
+        let committed: Value = serde_json::from_str(&fs::read_to_string(SPEC_FILE).unwrap())
+            .expect("openapi.json is not JSON");
+        assert!(
+            committed == generated,
+            "openapi.json is out of date, run UPDATE_OPENAPI=1 cargo test -p web --test lib openapi"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_documented_routes_are_served() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let ctx = AppContext::new(db.clone()).unwrap();
+        let token = token_for(&ctx, 0, UserStatus::Admin).await;
+        let spec = document();
+        for (path, operations) in spec["paths"].as_object().unwrap() {
+            // an id nothing has, so nothing is changed
+            let uri = path.replace("{id}", "987654321");
+            for method in operations.as_object().unwrap().keys() {
+                let method = method.to_uppercase();
+                let (status, body) = send_raw(&ctx, Some(&token), &method, &uri, None).await;
+                assert_ne!(status, StatusCode::METHOD_NOT_ALLOWED, "{method} {path}");
+                // the router's own 404 has no body, the handlers' ones do
+                assert!(
+                    status != StatusCode::NOT_FOUND || !body.is_empty(),
+                    "{method} {path} is not routed"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spec_and_docs_are_served() {
+        let db = TestDb::new().unwrap();
+        let ctx = AppContext::new(db.clone()).unwrap();
+        let (status, body) = send_raw(&ctx, None, "GET", "/openapi.json", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), document());
+
+        let (status, body) = send_raw(&ctx, None, "GET", "/docs", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(String::from_utf8_lossy(&body).contains("/openapi.json"));
+    }
+
+    #[test]
+    fn test_schemas() {
+        let mut components = Components::new();
+        let schema = SampleRecord::schema(&mut components);
+        assert_eq!(
+            schema["required"],
+            json!(["id", "name", "available", "created_at", "version"])
+        );
+        // what chrono writes, without an offset, matches
+        let created_at = &schema["properties"]["created_at"];
+        assert!(created_at.get("format").is_none());
+        let pattern = Regex::new(created_at["pattern"].as_str().unwrap()).unwrap();
+        for val in [
+            NaiveDateTime::default(),
+            NaiveDateTime::default() + Duration::milliseconds(1500),
+        ] {
+            let text = serde_json::to_value(val).unwrap();
+            assert!(pattern.is_match(text.as_str().unwrap()), "{text}");
+        }
+
+        let schema = NewUser::schema(&mut components);
+        assert_eq!(schema["properties"]["passwd"]["minLength"], 6);
+        assert_eq!(schema["properties"]["passwd"]["maxLength"], 128);
+        assert_eq!(schema["properties"]["status"]["maximum"], 2);
+        assert_eq!(
+            schema["properties"]["org_treepath"]["pattern"],
+            "^(/[0-9]+)+/$"
+        );
+
+        let reference = UserStatus::reference(&mut components);
+        assert_eq!(
+            reference,
+            json!({ "$ref": "#/components/schemas/UserStatus" })
+        );
+        assert_eq!(
+            components["UserStatus"]["enum"],
+            json!(["disabled", "active", "admin"])
+        );
+    }
+}
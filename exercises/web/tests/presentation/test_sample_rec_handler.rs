@@ -0,0 +1,131 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::TestDb;
+    use crate::presentation::{send, send_raw, token_for};
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use utils::log::configuration::init_logger;
+    use web::models::user_status::UserStatus;
+    use web::utils::app_context::AppContext;
+
+    #[tokio::test]
+    async fn test_sample_record_crud() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let ctx = AppContext::new(db.clone()).unwrap();
+        let _ = send(&ctx, "DELETE", "/sample-records/3001", None).await;
+        let rec = json!({
+            "id": 3001,
+            "name": "name of 3001",
+            "available": false,
+            "created_at": "2024-01-02T03:04:05"
+        });
+        let (status, body) = send(&ctx, "POST", "/sample-records", Some(rec.clone())).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["name"], "name of 3001");
+        assert_eq!(body["version"], 0);
+
+        let (status, _) = send(&ctx, "POST", "/sample-records", Some(rec.clone())).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let mut changed = rec.clone();
+        changed["available"] = json!(true);
+        let (status, body) = send(&ctx, "PUT", "/sample-records/3001", Some(changed.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["version"], 1);
+        // still carries the version read before the update
+        let (status, _) = send(&ctx, "PUT", "/sample-records/3001", Some(changed)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, body) = send(&ctx, "GET", "/sample-records/3001", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["available"], true);
+
+        let (status, body) = send(&ctx, "GET", "/sample-records?page_no=0&page_size=5", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.as_array().unwrap().len() <= 5);
+
+        let (status, _) = send(&ctx, "DELETE", "/sample-records/3001", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&ctx, "GET", "/sample-records/3001", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        // the soft deleted row still holds the id
+        let (status, body) = send(&ctx, "POST", "/sample-records", Some(rec)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body["error"].as_str().unwrap().contains("restore"));
+    }
+
+    #[tokio::test]
+    async fn test_sample_record_validation() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let ctx = AppContext::new(db.clone()).unwrap();
+        let rec = json!({
+            "id": 3002,
+            "name": " ",
+            "available": false,
+            "created_at": "2024-01-02T03:04:05"
+        });
+        let (status, body) = send(&ctx, "POST", "/sample-records", Some(rec)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body["error"].as_str().unwrap().contains("name"));
+
+        let (status, _) = send(&ctx, "GET", "/sample-records?page_size=0", None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, _) = send(&ctx, "DELETE", "/sample-records/3002", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_sample_record_list_query() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let ctx = AppContext::new(db.clone()).unwrap();
+        for (id, available) in [(3011, true), (3012, false), (3013, true)] {
+            let rec = json!({ "id": id, "name": format!("listed {id}"), "available": available });
+            let (status, _) = send(&ctx, "POST", "/sample-records", Some(rec)).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+        let uri = "/sample-records?filter=available:eq:true,name:like:listed%25&sort=id&fields=id";
+        let (status, body) = send(&ctx, "GET", uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!([{ "id": 3011 }, { "id": 3013 }]));
+
+        let (status, body) = send(&ctx, "GET", "/sample-records?filter=id:eq:x", None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body["error"].as_str().unwrap().contains("x"));
+        let (status, _) = send(&ctx, "GET", "/sample-records?sort=secret", None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_sample_record_export() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let ctx = AppContext::new(db.clone()).unwrap();
+        let token = token_for(&ctx, 0, UserStatus::Active).await;
+        let (status, body) = send_raw(
+            &ctx,
+            Some(&token),
+            "GET",
+            "/sample-records/export?order=asc",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let ids = std::str::from_utf8(&body)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                serde_json::from_str::<serde_json::Value>(line).unwrap()["id"]
+                    .as_i64()
+                    .unwrap()
+            })
+            .collect::<Vec<i64>>();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+
+        let (status, _) = send(&ctx, "GET", "/sample-records/export?order=sideways", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
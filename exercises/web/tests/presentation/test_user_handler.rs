@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::TestDb;
+    use crate::presentation::send;
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use utils::log::configuration::init_logger;
+    use web::persistence::user_persistence_async;
+    use web::utils::app_context::AppContext;
+
+    #[tokio::test]
+    async fn test_user_crud() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let ctx = AppContext::new(db.clone()).unwrap();
+        let _ = send(&ctx, "DELETE", "/users/3101", None).await;
+        let user = json!({
+            "id": 3101,
+            "created_date": "2024-01-02T03:04:05",
+            "modified_date": "2024-01-02T03:04:05",
+            "dob": "1990-05-06",
+            "passwd": "secret",
+            "passwd_enc_method": "plain",
+            "screenname": "screen 3101",
+            "status": 1,
+            "username": "user_3101",
+            "org_id": 1,
+            "org_treepath": "/1/"
+        });
+        let (status, body) = send(&ctx, "POST", "/users", Some(user.clone())).await;
+        assert_eq!(status, StatusCode::CREATED);
+        // the password is stored hashed and never shown
+        assert!(body.get("passwd").is_none());
+        assert!(body.get("passwd_enc_method").is_none());
+        let stored = user_persistence_async::find_by_id(&db, 3101)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.passwd_enc_method(), &ctx.hasher.method().to_string());
+
+        let mut changed = user.clone();
+        changed["screenname"] = json!("renamed 3101");
+        let (status, _) = send(&ctx, "PUT", "/users/3101", Some(changed)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = send(&ctx, "GET", "/users/3101", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["screenname"], "renamed 3101");
+
+        let (status, _) = send(&ctx, "DELETE", "/users/3101", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&ctx, "PUT", "/users/3101", Some(user.clone())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        // a deleted user gives up the name
+        let mut same_name = user.clone();
+        same_name["id"] = json!(3102);
+        let (status, _) = send(&ctx, "POST", "/users", Some(same_name.clone())).await;
+        assert_eq!(status, StatusCode::CREATED);
+        same_name["id"] = json!(3103);
+        let (status, body) = send(&ctx, "POST", "/users", Some(same_name)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body["error"].as_str().unwrap().contains("user_3101"));
+        let (status, body) = send(&ctx, "POST", "/users", Some(user)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body["error"].as_str().unwrap().contains("restore"));
+    }
+
+    #[tokio::test]
+    async fn test_user_validation() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let ctx = AppContext::new(db.clone()).unwrap();
+        let (status, _) = send(&ctx, "POST", "/users", Some(json!({ "id": 3102 }))).await;
+        assert!(status.is_client_error());
+
+        let (status, _) = send(&ctx, "GET", "/users/not-a-number", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // every failed check, by field
+        let user = json!({
+            "id": 0,
+            "dob": "1890-05-06",
+            "passwd": "short",
+            "screenname": " ",
+            "status": 7,
+            "username": "no spaces allowed",
+            "org_id": 1,
+            "org_treepath": "/1/"
+        });
+        let (status, body) = send(&ctx, "POST", "/users", Some(user)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let fields = body["fields"].as_object().unwrap();
+        assert_eq!(
+            fields.keys().collect::<Vec<_>>(),
+            ["dob", "id", "passwd", "screenname", "status", "username"]
+        );
+        assert_eq!(body["fields"]["passwd"], json!(["must be 6 to 128 long"]));
+        assert_eq!(body["fields"]["status"], json!(["must be between 0 and 2"]));
+    }
+}
@@ -0,0 +1,3 @@
+mod test_credential_service;
+mod test_outbox_dispatcher;
+mod test_token_service;
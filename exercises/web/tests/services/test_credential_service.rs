@@ -0,0 +1,214 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::{new_user, TestDb};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::models::user::User;
+    use web::persistence::user_persistence_async;
+    use web::services::credential_service::{change_password, login, set_password};
+    use web::services::error::ServiceError;
+    use web::services::password_hasher::{HashMethod, PasswordHasher};
+    use web::services::user_service;
+
+    /// cheap enough for tests, never for production
+    fn pbkdf2() -> PasswordHasher {
+        PasswordHasher::new(HashMethod::Pbkdf2Sha256 { iterations: 1000 }).unwrap()
+    }
+
+    fn argon2id() -> PasswordHasher {
+        PasswordHasher::new(HashMethod::Argon2id {
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        })
+        .unwrap()
+    }
+
+    fn user_with(_id: i64, passwd: &str, method: &str) -> User {
+        let mut user = new_user(_id);
+        user.set_passwd(passwd.to_string());
+        user.set_passwd_enc_method(method.to_string());
+        user
+    }
+
+    fn is_unauthorized(result: AppResult<User>) -> bool {
+        matches!(
+            result.map_err(|err| err.downcast::<ServiceError>()),
+            Err(Ok(ServiceError::Unauthorized(_)))
+        )
+    }
+
+    #[test]
+    fn test_hash_and_verify() -> AppResult<()> {
+        for hasher in [pbkdf2(), argon2id()] {
+            let (passwd, method) = hasher.hash("secret")?;
+            assert_eq!(method, hasher.method().to_string());
+            assert!(hasher.verify(&passwd, &method, "secret")?);
+            assert!(!hasher.verify(&passwd, &method, "Secret")?);
+            assert!(!hasher.verify(&passwd, &method, "")?);
+            assert!(!hasher.needs_rehash(&method));
+            // salted, the same password never hashes the same twice
+            assert_ne!(hasher.hash("secret")?.0, passwd);
+        }
+        let (passwd, method) = pbkdf2().hash("secret")?;
+        assert_eq!(method, "pbkdf2-sha256$i=1000");
+        // verified with the method stored, whatever the configured one
+        assert!(argon2id().verify(&passwd, &method, "secret")?);
+        assert!(argon2id().needs_rehash(&method));
+        assert!(pbkdf2().needs_rehash("pbkdf2-sha256$i=999"));
+
+        assert!(pbkdf2().verify("secret", "plain", "secret")?);
+        assert!(!pbkdf2().verify("secret", "plain", "secret!")?);
+        assert!(pbkdf2().needs_rehash("plain"));
+        assert!(pbkdf2().needs_rehash("md5"));
+        assert!(pbkdf2().verify("secret", "md5", "secret").is_err());
+        assert!(pbkdf2().verify("no-salt", &method, "secret").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_methods() -> AppResult<()> {
+        for text in [
+            "plain",
+            "pbkdf2-sha256$i=600000",
+            "argon2id$v=19$m=19456,t=2,p=1",
+        ] {
+            assert_eq!(text.parse::<HashMethod>()?.to_string(), text);
+        }
+        for bad in [
+            "",
+            "pbkdf2-sha256",
+            "pbkdf2-sha256$i=0",
+            "pbkdf2-sha256$i=x",
+            "argon2id$m=19456,t=2,p=1",
+            "argon2id$v=16$m=19456,t=2,p=1",
+            "argon2id$v=19$m=19456,t=2",
+            "argon2id$v=19$m=1,t=2,p=1",
+        ] {
+            assert!(bad.parse::<HashMethod>().is_err(), "{bad}");
+        }
+        assert!(PasswordHasher::new(HashMethod::Plain).is_err());
+
+        let lookup = |vars: &[(&str, &str)]| {
+            let vars = vars
+                .iter()
+                .map(|(key, val)| (key.to_string(), val.to_string()))
+                .collect::<HashMap<_, _>>();
+            PasswordHasher::from_lookup(move |key| vars.get(key).cloned())
+        };
+        assert_eq!(lookup(&[])?, PasswordHasher::default());
+        assert_eq!(
+            lookup(&[
+                ("PASSWD_HASH_METHOD", "pbkdf2-sha256"),
+                ("PASSWD_PBKDF2_ITERATIONS", "1000")
+            ])?,
+            pbkdf2()
+        );
+        assert!(lookup(&[("PASSWD_HASH_METHOD", "bcrypt")]).is_err());
+        assert!(lookup(&[("PASSWD_ARGON2_MEMORY_KIB", "lots")]).is_err());
+        Ok(())
+    }
+
+    /// On the one thread of the test runtime, a ticker only runs if the
+    /// hashing is done elsewhere.
+    #[tokio::test]
+    async fn test_hashing_leaves_the_runtime_free() -> AppResult<()> {
+        let hasher = PasswordHasher::new(HashMethod::Pbkdf2Sha256 {
+            iterations: 100_000,
+        })?;
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let ticker = {
+            let ticks = ticks.clone();
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                    ticks.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+        };
+        let mut user = new_user(1);
+        set_password(&hasher, &mut user, "secret").await?;
+        ticker.abort();
+        assert!(ticks.load(Ordering::SeqCst) > 0);
+        assert!(hasher.verify(user.passwd(), user.passwd_enc_method(), "secret")?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_login() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let hasher = pbkdf2();
+        user_persistence_async::insert(&db, &user_with(1, "legacy", "plain")).await?;
+        user_service::create(&db, &hasher, &user_with(2, "secret", "ignored")).await?;
+
+        let user = login(&db, &hasher, "user_2", "secret").await?;
+        assert_eq!(*user.id(), 2);
+        assert_eq!(user.passwd_enc_method(), "pbkdf2-sha256$i=1000");
+        assert_ne!(user.passwd(), "secret");
+        assert_eq!(*user.version(), 0);
+
+        assert!(is_unauthorized(
+            login(&db, &hasher, "user_2", "wrong").await
+        ));
+        assert!(is_unauthorized(
+            login(&db, &hasher, "nobody", "secret").await
+        ));
+
+        // the outdated one is rehashed on the way in
+        let user = login(&db, &hasher, "user_1", "legacy").await?;
+        assert_eq!(user.passwd_enc_method(), "pbkdf2-sha256$i=1000");
+        assert_eq!(*user.version(), 1);
+        let user = login(&db, &argon2id(), "user_1", "legacy").await?;
+        assert!(user.passwd_enc_method().starts_with("argon2id$"));
+        assert!(login(&db, &hasher, "user_1", "legacy").await.is_ok());
+
+        user_persistence_async::delete(&db, 2).await?;
+        assert!(is_unauthorized(
+            login(&db, &hasher, "user_2", "secret").await
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_change_password() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let hasher = pbkdf2();
+        let user = user_service::create(&db, &hasher, &user_with(1, "secret", "")).await?;
+
+        assert!(is_unauthorized(
+            change_password(&db, &hasher, 1, *user.version(), "wrong", "changed").await
+        ));
+        assert!(
+            change_password(&db, &hasher, 1, *user.version(), "secret", "")
+                .await
+                .is_err()
+        );
+        let changed =
+            change_password(&db, &hasher, 1, *user.version(), "secret", "changed").await?;
+        assert_eq!(*changed.version(), 1);
+        assert!(is_unauthorized(
+            login(&db, &hasher, "user_1", "secret").await
+        ));
+        assert!(login(&db, &hasher, "user_1", "changed").await.is_ok());
+        // a stale version does not overwrite the change
+        assert!(
+            change_password(&db, &hasher, 1, *user.version(), "changed", "again")
+                .await
+                .is_err()
+        );
+
+        // a plain update keeps the password
+        let mut renamed = changed.clone();
+        renamed.set_screenname("renamed".to_string());
+        renamed.set_passwd("overwritten".to_string());
+        user_service::update(&db, &renamed).await?;
+        assert!(login(&db, &hasher, "user_1", "changed").await.is_ok());
+        Ok(())
+    }
+}
@@ -0,0 +1,457 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::{new_rec, TestDb};
+    use chrono::{NaiveDate, NaiveDateTime};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+    use utils::error::app_error::AppResult;
+    use utils::log::configuration::init_logger;
+    use web::models::domain_event::{EventType, OutboxEvent};
+    use web::models::user::User;
+    use web::persistence::db::Db;
+    use web::persistence::outbox_persistence::{
+        claim_with, count_pending, find_dead, mark_delivered_with, mark_failed_with, requeue,
+    };
+    use web::services::event_sink::{
+        parse_sinks, ChannelSink, EventSink, FileSink, SinkFuture, TcpSink,
+    };
+    use web::services::outbox_dispatcher::{Dispatched, OutboxDispatcher};
+    use web::services::password_hasher::{HashMethod, PasswordHasher};
+    use web::services::{sample_rec_service, user_service};
+    use web::utils::app_context::AppContext;
+    use web::utils::lifecycle::Lifecycle;
+
+    /// Fails the first `failures` deliveries.
+    struct FlakySink {
+        failures: usize,
+        calls: AtomicUsize,
+    }
+
+    impl EventSink for FlakySink {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        fn deliver<'a>(&'a self, _event: &'a OutboxEvent) -> SinkFuture<'a> {
+            Box::pin(async move {
+                match self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                    true => Err(anyhow::anyhow!("refused")),
+                    false => Ok(()),
+                }
+            })
+        }
+    }
+
+    fn flaky(failures: usize) -> Arc<dyn EventSink> {
+        Arc::new(FlakySink {
+            failures,
+            calls: AtomicUsize::new(0),
+        })
+    }
+
+    /// Looks at the outbox while an event is being delivered: how many events
+    /// another dispatcher could claim, and whether the event's row is free.
+    struct ProbeSink {
+        db: Db,
+        delay: Duration,
+        seen: Mutex<Vec<(usize, u64)>>,
+    }
+
+    impl EventSink for ProbeSink {
+        fn name(&self) -> &str {
+            "probe"
+        }
+
+        fn deliver<'a>(&'a self, event: &'a OutboxEvent) -> SinkFuture<'a> {
+            Box::pin(async move {
+                let conn = self.db.async_conn().await?;
+                let claimed = claim_with(&*conn, 10, Duration::from_secs(60)).await?;
+                let updated = tokio::time::timeout(
+                    Duration::from_secs(5),
+                    conn.execute(
+                        "update outbox set payload = payload where id_ = $1",
+                        &[&event.id],
+                    ),
+                )
+                .await??;
+                self.seen.lock().unwrap().push((claimed.len(), updated));
+                tokio::time::sleep(self.delay).await;
+                Ok(())
+            })
+        }
+    }
+
+    fn probe(db: &TestDb, delay: Duration) -> Arc<ProbeSink> {
+        Arc::new(ProbeSink {
+            db: Db::new(db.config().clone()),
+            delay,
+            seen: Mutex::new(vec![]),
+        })
+    }
+
+    /// Failed events are due again at once.
+    fn dispatcher(sinks: Vec<Arc<dyn EventSink>>) -> OutboxDispatcher {
+        let mut dispatcher = OutboxDispatcher::default().with_sinks(sinks);
+        dispatcher.retry_base = Duration::ZERO;
+        dispatcher.max_attempts = 3;
+        dispatcher
+    }
+
+    fn channel() -> (Arc<dyn EventSink>, mpsc::Receiver<OutboxEvent>) {
+        let (tx, rx) = mpsc::channel(16);
+        (Arc::new(ChannelSink::new(tx)), rx)
+    }
+
+    async fn create_recs(db: &Db, ids: &[i64]) -> AppResult<()> {
+        for _id in ids {
+            sample_rec_service::create(db, &new_rec(*_id)).await?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_writes_publish_in_their_transaction() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let stored = sample_rec_service::create(&db, &new_rec(1)).await?;
+        let mut update = stored.clone();
+        update.set_name("renamed".to_string());
+        sample_rec_service::update(&db, &update).await?;
+        sample_rec_service::delete(&db, 1).await?;
+        // failed writes publish nothing
+        sample_rec_service::create(&db, &new_rec(2)).await?;
+        assert!(sample_rec_service::create(&db, &new_rec(2)).await.is_err());
+        assert!(sample_rec_service::delete(&db, 99).await.is_err());
+        assert!(sample_rec_service::update(&db, &update).await.is_err());
+        assert_eq!(count_pending(&db).await?, 4);
+
+        let (sink, mut rx) = channel();
+        let dispatched = dispatcher(vec![sink]).dispatch(&db).await?;
+        assert_eq!(dispatched.delivered, 4);
+        let mut events = vec![];
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+        let kinds = events
+            .iter()
+            .map(|event| (event.event.aggregate_id, event.event.event_type))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                (1, EventType::Created),
+                (1, EventType::Updated),
+                (1, EventType::Deleted),
+                (2, EventType::Created),
+            ]
+        );
+        assert!(events.windows(2).all(|pair| pair[0].id < pair[1].id));
+        assert_eq!(events[0].event.aggregate, sample_rec_service::AGGREGATE);
+        assert_eq!(events[0].event.payload["name"], "name of 1");
+        assert_eq!(events[0].event.payload["version"], 0);
+        assert_eq!(events[1].event.payload["name"], "renamed");
+        assert_eq!(events[1].event.payload["version"], 1);
+        assert_eq!(events[2].event.payload, json!({ "id": 1 }));
+
+        // delivered ones are not claimed again
+        assert_eq!(count_pending(&db).await?, 0);
+        assert_eq!(
+            dispatcher(vec![]).dispatch(&db).await?,
+            Dispatched::default()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_user_events_carry_no_password() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let hasher = PasswordHasher::new(HashMethod::Pbkdf2Sha256 { iterations: 1000 })?;
+        let user = User::new(
+            7,
+            NaiveDateTime::default(),
+            NaiveDateTime::default(),
+            NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            "secret".to_string(),
+            String::new(),
+            "screen 7".to_string(),
+            1,
+            "user_7".to_string(),
+            1,
+            "/1/".to_string(),
+            0,
+            None,
+        );
+        user_service::create(&db, &hasher, &user).await?;
+
+        let (sink, mut rx) = channel();
+        dispatcher(vec![sink]).dispatch(&db).await?;
+        let event = rx.try_recv().unwrap();
+        assert_eq!(event.event.aggregate, user_service::AGGREGATE);
+        assert_eq!(event.event.payload["username"], "user_7");
+        let payload = event.event.payload.to_string();
+        assert!(!payload.contains("passwd"), "{payload}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retries_then_dead_letters() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        create_recs(&db, &[1]).await?;
+
+        // the first sink takes it every time, the second fails twice
+        let (sink, mut rx) = channel();
+        let dispatcher = dispatcher(vec![sink, flaky(2)]);
+        let retried = Dispatched {
+            retried: 1,
+            ..Dispatched::default()
+        };
+        assert_eq!(dispatcher.dispatch(&db).await?, retried);
+        assert_eq!(dispatcher.dispatch(&db).await?, retried);
+        let delivered = Dispatched {
+            delivered: 1,
+            ..Dispatched::default()
+        };
+        assert_eq!(dispatcher.dispatch(&db).await?, delivered);
+        // at least once: every attempt reached the first sink
+        let mut seen = vec![];
+        while let Ok(event) = rx.try_recv() {
+            seen.push((event.id, event.attempts));
+        }
+        assert_eq!(seen.len(), 3);
+        assert!(seen.iter().all(|(id, _)| *id == seen[0].0));
+        assert_eq!(
+            seen.iter()
+                .map(|(_, attempts)| *attempts)
+                .collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+
+        // a sink that never takes it: dead at the third failure
+        create_recs(&db, &[2]).await?;
+        let dispatcher = self::dispatcher(vec![flaky(usize::MAX)]);
+        assert_eq!(dispatcher.dispatch(&db).await?.retried, 1);
+        assert_eq!(dispatcher.dispatch(&db).await?.retried, 1);
+        assert_eq!(dispatcher.dispatch(&db).await?.dead, 1);
+        assert_eq!(dispatcher.dispatch(&db).await?, Dispatched::default());
+        let dead = find_dead(&db, 10).await?;
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].event.aggregate_id, 2);
+        assert_eq!(dead[0].attempts, 3);
+        assert_eq!(dead[0].last_error.as_deref(), Some("sink flaky: refused"));
+
+        assert!(requeue(&db, dead[0].id).await?);
+        assert!(!requeue(&db, dead[0].id).await?);
+        let (sink, mut rx) = channel();
+        assert_eq!(self::dispatcher(vec![sink]).dispatch(&db).await?, delivered);
+        assert_eq!(rx.try_recv().unwrap().attempts, 0);
+        assert!(find_dead(&db, 10).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delivers_leased_events_outside_a_transaction() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        create_recs(&db, &[1, 2]).await?;
+
+        // while delivering, the batch is leased but no row is locked
+        let sink = probe(&db, Duration::ZERO);
+        let dispatched = dispatcher(vec![sink.clone()]).dispatch(&db).await?;
+        assert_eq!(dispatched.delivered, 2);
+        assert_eq!(*sink.seen.lock().unwrap(), [(0, 1), (0, 1)]);
+
+        // a round stops when its lease is over, the rest are due again then
+        create_recs(&db, &[3, 4]).await?;
+        let mut slow = dispatcher(vec![probe(&db, Duration::from_millis(300))]);
+        slow.lease = Duration::from_millis(200);
+        assert_eq!(slow.dispatch(&db).await?.delivered, 1);
+        assert_eq!(count_pending(&db).await?, 1);
+        let (sink, mut rx) = channel();
+        assert_eq!(dispatcher(vec![sink]).dispatch(&db).await?.delivered, 1);
+        assert_eq!(rx.try_recv().unwrap().event.aggregate_id, 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_late_outcomes_are_ignored() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        create_recs(&db, &[1, 2]).await?;
+        let conn = db.async_conn().await?;
+        let events = claim_with(&*conn, 10, Duration::ZERO).await?;
+        let (delivered, dead) = (events[0].id, events[1].id);
+        mark_delivered_with(&*conn, delivered).await?;
+        mark_failed_with(&*conn, dead, "refused", None).await?;
+
+        // what a dispatcher whose lease ran out would write after another one
+        mark_failed_with(&*conn, delivered, "late", None).await?;
+        mark_failed_with(&*conn, dead, "late", Some(Duration::ZERO)).await?;
+        mark_delivered_with(&*conn, dead).await?;
+        drop(conn);
+        assert_eq!(count_pending(&db).await?, 0);
+        let found = find_dead(&db, 10).await?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, dead);
+        assert_eq!(found[0].attempts, 1);
+        assert_eq!(found[0].last_error.as_deref(), Some("refused"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stops_between_events() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        create_recs(&db, &[1, 2, 3]).await?;
+
+        let sink = Arc::new(FlakySink {
+            failures: 0,
+            calls: AtomicUsize::new(0),
+        });
+        let dispatched = dispatcher(vec![sink.clone()])
+            .dispatch_until(&db, || sink.calls.load(Ordering::SeqCst) > 0)
+            .await?;
+        assert_eq!(dispatched.delivered, 1);
+        // the rest are released, not left to their lease
+        assert_eq!(count_pending(&db).await?, 2);
+        let (sink, mut rx) = channel();
+        assert_eq!(dispatcher(vec![sink]).dispatch(&db).await?.delivered, 2);
+        assert_eq!(rx.try_recv().unwrap().event.aggregate_id, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_backoff() {
+        let mut dispatcher = OutboxDispatcher::default();
+        dispatcher.retry_base = Duration::from_secs(1);
+        dispatcher.retry_max = Duration::from_secs(60);
+        let waits = (0..8)
+            .map(|attempts| dispatcher.retry_in(attempts).as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(waits, [1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(dispatcher.retry_in(i32::MAX), Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_file_and_tcp_sinks() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        create_recs(&db, &[1, 2]).await?;
+
+        let path = std::env::temp_dir().join(format!("outbox_{}.ndjson", db.schema()));
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?.to_string();
+        let received = tokio::spawn(async move {
+            // the first connection goes without an answer
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut unanswered = vec![0; 1];
+            stream.read_exact(&mut unanswered).await.unwrap();
+            drop(stream);
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut text = String::new();
+            let mut line = String::new();
+            while stream.read_line(&mut line).await.unwrap() > 0 {
+                stream.get_mut().write_all(b"ok\n").await.unwrap();
+                text.push_str(&line);
+                line.clear();
+            }
+            text
+        });
+
+        let sinks: Vec<Arc<dyn EventSink>> = vec![
+            Arc::new(FileSink::new(&path)),
+            Arc::new(TcpSink::new(&address)),
+        ];
+        let dispatcher = dispatcher(sinks);
+        let dispatched = dispatcher.dispatch(&db).await?;
+        assert_eq!((dispatched.delivered, dispatched.retried), (1, 1));
+        assert_eq!(dispatcher.dispatch(&db).await?.delivered, 1);
+        drop(dispatcher);
+
+        // the file took the unanswered event twice, the peer after the other one
+        let written = std::fs::read_to_string(&path)?;
+        std::fs::remove_file(&path)?;
+        let written = written.lines().skip(1).collect::<Vec<_>>().join("\n");
+        // the dispatcher and its connection are gone, the peer read to the end
+        let sent = received.await?;
+        for text in [written, sent] {
+            let events = text
+                .lines()
+                .map(serde_json::from_str::<OutboxEvent>)
+                .collect::<Result<Vec<_>, _>>()?;
+            let ids = events
+                .iter()
+                .map(|event| event.event.aggregate_id)
+                .collect::<Vec<_>>();
+            assert_eq!(ids, [2, 1]);
+            assert!(events
+                .iter()
+                .all(|event| event.event.event_type == EventType::Created));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_config() -> AppResult<()> {
+        let dispatcher = OutboxDispatcher::from_lookup(|key| match key {
+            "OUTBOX_SINKS" => Some("log, file:/tmp/events.ndjson,tcp:localhost:9400".to_string()),
+            "OUTBOX_MAX_ATTEMPTS" => Some("5".to_string()),
+            _ => None,
+        })?;
+        assert_eq!(dispatcher.max_attempts, 5);
+        assert_eq!(dispatcher.batch_size, 100);
+        assert_eq!(dispatcher.lease, Duration::from_secs(60));
+        let sinks = parse_sinks("log, file:/tmp/events.ndjson,tcp:localhost:9400")?;
+        let names = sinks.iter().map(|sink| sink.name()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["log", "file:/tmp/events.ndjson", "tcp:localhost:9400"]
+        );
+
+        for spec in ["kafka", "file:", "tcp:localhost"] {
+            assert!(parse_sinks(spec).is_err(), "{spec}");
+        }
+        let zero = OutboxDispatcher::from_lookup(|key| {
+            (key == "OUTBOX_MAX_ATTEMPTS").then(|| "0".to_string())
+        });
+        assert!(zero.is_err());
+        let zero = OutboxDispatcher::from_lookup(|key| {
+            (key == "OUTBOX_LEASE_SECS").then(|| "0".to_string())
+        });
+        assert!(zero.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_runs_as_worker() -> AppResult<()> {
+        init_logger();
+        let db = TestDb::new()?;
+        let (sink, mut rx) = channel();
+        let mut outbox = dispatcher(vec![sink]);
+        outbox.poll_interval = Duration::from_millis(20);
+        let mut lifecycle = Lifecycle::default();
+        lifecycle.http_address = "127.0.0.1:0".to_string();
+        let running = lifecycle
+            .worker("outbox", move |ctx, shutdown| outbox.run(ctx, shutdown))
+            .start(AppContext::new(Db::new(db.config().clone()))?)
+            .await?;
+
+        create_recs(&db, &[1]).await?;
+        let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await?
+            .unwrap();
+        assert_eq!(event.event.aggregate_id, 1);
+
+        let stopped = running.stop().await;
+        assert_eq!(stopped.workers_stopped, ["outbox"]);
+        assert_eq!(count_pending(&db).await?, 0);
+        Ok(())
+    }
+}
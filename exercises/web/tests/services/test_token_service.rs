@@ -0,0 +1,128 @@
+#[cfg(test)]
+mod tests {
+    use openssl::base64::encode_block;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::nid::Nid;
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use utils::error::app_error::AppResult;
+    use web::models::user_status::UserStatus;
+    use web::services::token_service::{Principal, TokenService, TokenUse};
+
+    fn principal(status: UserStatus) -> Principal {
+        Principal {
+            user_id: 7,
+            username: "user_7".to_string(),
+            status,
+        }
+    }
+
+    fn token(tokens: &TokenService, token_use: TokenUse) -> String {
+        let claims = tokens
+            .claims(&principal(UserStatus::Active), token_use)
+            .unwrap();
+        tokens.sign(&claims).unwrap()
+    }
+
+    #[test]
+    fn test_sign_and_verify() -> AppResult<()> {
+        let tokens = TokenService::generate()?;
+        let access = token(&tokens, TokenUse::Access);
+        assert_eq!(access.split('.').count(), 3);
+        assert!(!access.contains(['+', '/', '=']));
+        assert_eq!(tokens.authenticate(&access)?, principal(UserStatus::Active));
+        let claims = tokens.verify(&token(&tokens, TokenUse::Refresh), TokenUse::Refresh)?;
+        assert_eq!(claims.exp - claims.iat, 14 * 24 * 3600);
+        assert_ne!(
+            claims.jti,
+            tokens
+                .verify(&token(&tokens, TokenUse::Refresh), TokenUse::Refresh)?
+                .jti
+        );
+
+        assert!(tokens.verify(&access, TokenUse::Refresh).is_err());
+        assert!(TokenService::generate()?.authenticate(&access).is_err());
+        let (input, sig) = access.rsplit_once('.').unwrap();
+        let (header, _) = input.split_once('.').unwrap();
+        // claims swapped in under the same signature
+        let other = token(&tokens, TokenUse::Refresh);
+        let payload = other.split('.').nth(1).unwrap();
+        assert!(tokens
+            .authenticate(&format!("{header}.{payload}.{sig}"))
+            .is_err());
+        let none = encode_block(br#"{"alg":"none","typ":"JWT"}"#);
+        let none = none.trim_end_matches('=');
+        let payload = access.split('.').nth(1).unwrap();
+        assert!(tokens.authenticate(&format!("{none}.{payload}.")).is_err());
+        assert!(tokens.authenticate("").is_err());
+        assert!(tokens.authenticate(&access[..access.len() - 2]).is_err());
+
+        let mut other_audience = tokens.clone();
+        other_audience.audience = "other".to_string();
+        assert!(other_audience.authenticate(&access).is_err());
+        let mut expired = tokens.clone();
+        expired.access_ttl = Duration::ZERO;
+        assert!(tokens
+            .authenticate(&token(&expired, TokenUse::Access))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_keys() -> AppResult<()> {
+        let p256 = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        let key = EcKey::generate(&p256)?;
+        let tokens = TokenService::from_pem(&key.private_key_to_pem()?)?;
+        let again = TokenService::from_pem(&key.private_key_to_pem()?)?;
+        assert!(again
+            .authenticate(&token(&tokens, TokenUse::Access))
+            .is_ok());
+        let secp256k1 = EcGroup::from_curve_name(Nid::SECP256K1)?;
+        let k256 = EcKey::generate(&secp256k1)?;
+        assert!(TokenService::from_pem(&k256.private_key_to_pem()?).is_err());
+
+        let path = std::env::temp_dir().join(format!("jwt_{}.pem", std::process::id()));
+        std::fs::write(&path, key.private_key_to_pem()?)?;
+        let vars = HashMap::from([
+            ("JWT_PRIVATE_KEY_FILE", path.display().to_string()),
+            ("JWT_AUDIENCE", "app".to_string()),
+            ("JWT_ACCESS_TTL_SECS", "60".to_string()),
+        ]);
+        let loaded = TokenService::from_lookup(|key| vars.get(key).cloned());
+        std::fs::remove_file(&path)?;
+        let loaded = loaded?;
+        assert_eq!(loaded.issuer, "web");
+        assert_eq!(loaded.audience, "app");
+        assert_eq!(loaded.access_ttl, Duration::from_secs(60));
+        assert!(loaded
+            .verify(&token(&tokens, TokenUse::Access), TokenUse::Access)
+            .is_err());
+        let claims = loaded.claims(&principal(UserStatus::Active), TokenUse::Access)?;
+        assert!(again
+            .verify(&loaded.sign(&claims)?, TokenUse::Access)
+            .is_err());
+        assert!(TokenService::from_lookup(|key| {
+            (key == "JWT_PRIVATE_KEY_FILE").then(|| "/nowhere.pem".to_string())
+        })
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_require() {
+        assert!(principal(UserStatus::Active)
+            .require(UserStatus::Active)
+            .is_ok());
+        assert!(principal(UserStatus::Admin)
+            .require(UserStatus::Active)
+            .is_ok());
+        assert!(principal(UserStatus::Active)
+            .require(UserStatus::Admin)
+            .is_err());
+        assert!(principal(UserStatus::Disabled)
+            .require(UserStatus::Disabled)
+            .is_err());
+        assert_eq!(UserStatus::from_code(2), UserStatus::Admin);
+        assert_eq!(UserStatus::from_code(42), UserStatus::Disabled);
+    }
+}
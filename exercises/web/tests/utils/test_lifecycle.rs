@@ -0,0 +1,165 @@
+#[cfg(test)]
+mod tests {
+    use crate::persistence::test_common::TestDb;
+    use crate::presentation::token_for;
+    use serde_json::json;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::time::Instant;
+    use utils::log::configuration::init_logger;
+    use web::models::user_status::UserStatus;
+    use web::persistence::db::Db;
+    use web::persistence::migration::migrate_down;
+    use web::services::sample_rec_service;
+    use web::utils::app_context::AppContext;
+    use web::utils::lifecycle::Lifecycle;
+
+    fn lifecycle(timeout: Duration) -> Lifecycle {
+        let mut lifecycle = Lifecycle::default();
+        lifecycle.http_address = "127.0.0.1:0".to_string();
+        lifecycle.shutdown_timeout = timeout;
+        lifecycle
+    }
+
+    /// A context on pools of its own, so closing them is up to the lifecycle.
+    fn context(db: &TestDb) -> AppContext {
+        AppContext::new(Db::new(db.config().clone())).unwrap()
+    }
+
+    async fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request =
+            format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_start_and_stop_in_order() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let stopped_worker = Arc::new(AtomicBool::new(false));
+        let seen = stopped_worker.clone();
+        let running = lifecycle(Duration::from_secs(5))
+            .worker("waiter", move |_ctx, shutdown| async move {
+                shutdown.wait().await;
+                seen.store(true, Ordering::SeqCst);
+                Ok(())
+            })
+            .start(context(&db))
+            .await
+            .unwrap();
+
+        let response = get(running.local_addr, "/openapi.json").await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(!stopped_worker.load(Ordering::SeqCst));
+
+        let addr = running.local_addr;
+        let stopped = running.stop().await;
+        assert!(stopped.drained);
+        assert_eq!(stopped.workers_stopped, ["waiter"]);
+        assert!(stopped.workers_abandoned.is_empty());
+        assert!(stopped.pools_closed);
+        assert!(stopped_worker.load(Ordering::SeqCst));
+        // no longer accepting
+        assert!(TcpStream::connect(addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_workers_stop_after_the_drain() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let ctx = context(&db);
+        let found = Arc::new(AtomicBool::new(false));
+        let seen = found.clone();
+        let running = lifecycle(Duration::from_secs(5))
+            .worker("reader", move |ctx, shutdown| async move {
+                shutdown.wait().await;
+                let stored = sample_rec_service::find_by_id(&ctx.db, 4001).await;
+                seen.store(stored.is_ok(), Ordering::SeqCst);
+                Ok(())
+            })
+            .start(ctx.clone())
+            .await
+            .unwrap();
+
+        // a request still sending its body when the shutdown begins
+        let body = json!({
+            "id": 4001,
+            "name": "name of 4001",
+            "available": false,
+            "created_at": "2024-01-02T03:04:05"
+        })
+        .to_string();
+        let request = format!(
+            "POST /sample-records HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             Authorization: Bearer {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\n\r\n{}",
+            token_for(&ctx, 0, UserStatus::Admin).await,
+            body.len(),
+            body
+        );
+        let (head, tail) = request.split_at(request.len() - 1);
+        let mut stream = TcpStream::connect(running.local_addr).await.unwrap();
+        stream.write_all(head.as_bytes()).await.unwrap();
+        drop(ctx);
+        // the server has read up to the body
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let stopping = tokio::spawn(running.stop());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        stream.write_all(tail.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 201"), "{response}");
+
+        let stopped = stopping.await.unwrap();
+        assert!(stopped.drained);
+        assert_eq!(stopped.workers_stopped, ["reader"]);
+        assert!(found.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_stuck_worker_is_abandoned() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let running = lifecycle(Duration::from_millis(200))
+            .worker("stuck", |_ctx, _shutdown| async {
+                tokio::time::sleep(Duration::from_secs(3600)).await;
+                Ok(())
+            })
+            .start(context(&db))
+            .await
+            .unwrap();
+
+        let started = Instant::now();
+        let stopped = running.stop().await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(stopped.drained);
+        assert_eq!(stopped.workers_abandoned, ["stuck"]);
+        // the aborted worker gave back its context
+        assert!(stopped.pools_closed);
+    }
+
+    #[tokio::test]
+    async fn test_refuses_to_start_with_pending_migrations() {
+        init_logger();
+        let db = TestDb::new().unwrap();
+        let mut conn = db.async_conn().await.unwrap();
+        migrate_down(&mut conn, 1).await.unwrap();
+        drop(conn);
+
+        let err = lifecycle(Duration::from_secs(1))
+            .start(context(&db))
+            .await
+            .err()
+            .expect("started with a pending migration");
+        assert!(err.to_string().contains("pending"), "{err}");
+    }
+}